@@ -0,0 +1,131 @@
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+/// Failures reported by chat model providers, grouped by what the user can do about them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatModelError {
+    /// The API key is missing, invalid or lacks permission.
+    Auth(String),
+    /// The account has run out of credits or hit a billing limit.
+    Quota(String),
+    /// Too many requests; `retry_after` is the wait the provider asked for.
+    RateLimit { message: String, retry_after: Option<Duration> },
+    /// The provider failed or is overloaded.
+    Server { status: u16, message: String },
+    /// The connection failed or dropped mid-stream.
+    Network(String),
+    /// The provider rejected the request itself.
+    InvalidRequest(String),
+}
+
+impl ChatModelError {
+    /// Classifies an unsuccessful HTTP response. `code` is the provider's
+    /// error code or type when the body contained one.
+    pub fn from_status(status: StatusCode, retry_after: Option<Duration>, code: Option<&str>, message: String) -> ChatModelError {
+        let code = code.unwrap_or_default();
+        if code.contains("insufficient_quota") || code.contains("billing") || status == StatusCode::PAYMENT_REQUIRED {
+            return ChatModelError::Quota(message);
+        }
+        match status.as_u16() {
+            401 | 403 => ChatModelError::Auth(message),
+            429 => ChatModelError::RateLimit { message, retry_after },
+            // 529 is Anthropic's "overloaded" status
+            408 | 500..=599 => ChatModelError::Server { status: status.as_u16(), message },
+            _ => ChatModelError::InvalidRequest(message),
+        }
+    }
+
+    /// Classifies a response from the JSON error body shared by OpenAI style
+    /// and Anthropic APIs: `{"error": {"type": ..., "code": ..., "message": ...}}`.
+    pub fn from_response(status: StatusCode, retry_after: Option<Duration>, body: &str) -> ChatModelError {
+        let error = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|value| value.get("error").cloned());
+        let field = |name: &str| error.as_ref()
+            .and_then(|error| error.get(name))
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_string());
+
+        let code = field("code").or_else(|| field("type"));
+        let message = field("message").unwrap_or_else(|| {
+            if body.trim().is_empty() {
+                status.to_string()
+            } else {
+                body.trim().to_string()
+            }
+        });
+        ChatModelError::from_status(status, retry_after, code.as_deref(), message)
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ChatModelError::RateLimit { .. } | ChatModelError::Server { .. } | ChatModelError::Network(_))
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ChatModelError::RateLimit { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// A suggestion on how to resolve the error, shown below the message.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            ChatModelError::Auth(_) => "Check that the API key environment variable for this model is set and valid.",
+            ChatModelError::Quota(_) => "Your account is out of credits or over its spending limit; check your provider's billing page.",
+            ChatModelError::RateLimit { .. } => "The provider is rate limiting requests; wait a moment and try again.",
+            ChatModelError::Server { .. } => "The provider is having problems; try again shortly.",
+            ChatModelError::Network(_) => "Check your network connection and the model's base_url.",
+            ChatModelError::InvalidRequest(_) => "The provider rejected the request; check the model settings in settings.json.",
+        }
+    }
+}
+
+impl fmt::Display for ChatModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatModelError::Auth(message) => write!(f, "authentication failed: {}", message),
+            ChatModelError::Quota(message) => write!(f, "quota exceeded: {}", message),
+            ChatModelError::RateLimit { message, .. } => write!(f, "rate limited: {}", message),
+            ChatModelError::Server { status, message } => write!(f, "server error ({}): {}", status, message),
+            ChatModelError::Network(message) => write!(f, "network error: {}", message),
+            ChatModelError::InvalidRequest(message) => write!(f, "invalid request: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ChatModelError {}
+
+impl From<reqwest::Error> for ChatModelError {
+    fn from(error: reqwest::Error) -> Self {
+        ChatModelError::Network(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_provider_errors() {
+        let quota = "{\"error\":{\"message\":\"You exceeded your current quota\",\"type\":\"insufficient_quota\",\"code\":\"insufficient_quota\"}}";
+        assert_eq!(
+            ChatModelError::from_response(StatusCode::TOO_MANY_REQUESTS, None, quota),
+            ChatModelError::Quota(String::from("You exceeded your current quota"))
+        );
+
+        let rate_limit = "{\"type\":\"error\",\"error\":{\"type\":\"rate_limit_error\",\"message\":\"slow down\"}}";
+        let error = ChatModelError::from_response(StatusCode::TOO_MANY_REQUESTS, Some(Duration::from_secs(2)), rate_limit);
+        assert!(error.is_retryable());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(2)));
+
+        let auth = ChatModelError::from_response(StatusCode::UNAUTHORIZED, None, "");
+        assert_eq!(auth, ChatModelError::Auth(String::from("401 Unauthorized")));
+        assert!(!auth.is_retryable());
+
+        let overloaded = StatusCode::from_u16(529).unwrap();
+        assert!(ChatModelError::from_response(overloaded, None, "overloaded").is_retryable());
+    }
+}
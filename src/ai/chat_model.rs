@@ -3,6 +3,7 @@ use std::error::Error;
 use async_trait::async_trait;
 
 use super::{chat_types::ChatCompletionStream, chat_types::ChatCompletionRequestMessage};
+use super::retry::RetryListener;
 
 #[derive(Clone)]
 pub struct ChatModelRequest {
@@ -10,7 +11,11 @@ pub struct ChatModelRequest {
 }
 
 #[async_trait]
-pub trait ChatModel {
+pub trait ChatModel: Send {
     fn new(config: serde_json::Value) -> Self where Self: Sized;
     async fn create_response_stream(&mut self, client_request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>>;
+
+    /// Reports the retries made while opening a response stream to `listener`.
+    /// Models that never retry ignore it.
+    fn set_retry_listener(&mut self, _listener: RetryListener) {}
 }
\ No newline at end of file
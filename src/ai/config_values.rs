@@ -0,0 +1,29 @@
+use serde_json::Value;
+
+// Values edited through the config menu are written back as strings, so every
+// numeric lookup accepts both a JSON number and its string form.
+
+pub fn get_string(config: &Value, key: &str) -> Option<String> {
+    match config.get(key)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+pub fn get_u64(config: &Value, key: &str) -> Option<u64> {
+    match config.get(key)? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse::<u64>().ok(),
+        _ => None,
+    }
+}
+
+pub fn get_f64(config: &Value, key: &str) -> Option<f64> {
+    match config.get(key)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
@@ -1,2 +1,9 @@
+pub mod chat_error;
 pub mod chat_model;
-pub mod chat_types;
\ No newline at end of file
+pub mod chat_types;
+pub mod config_values;
+pub mod retry;
+pub mod tokens;
+
+#[cfg(test)]
+pub(crate) mod test_server;
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::header::HeaderMap;
+
+use super::chat_error::ChatModelError;
+use super::config_values;
+
+/// Exponential backoff with jitter for opening a response stream. Read from
+/// the model config keys `max_retries`, `retry_initial_delay_ms` and
+/// `retry_max_delay_ms`.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Told about each retry before waiting for it; retries are silent without one.
+    pub on_retry: Option<RetryListener>,
+}
+
+/// Shows a retry the way the front end making the request sees fit.
+pub type RetryListener = Arc<dyn Fn(&Retry) + Send + Sync>;
+
+/// A failed attempt that is about to be retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Retry {
+    pub error: ChatModelError,
+    pub delay: Duration,
+    /// The retry about to be made, counting from 1.
+    pub attempt: u32,
+    pub max_retries: u32,
+}
+
+impl fmt::Display for Retry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}; retrying in {:.1}s ({}/{})", self.error, self.delay.as_secs_f64(), self.attempt, self.max_retries)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(20),
+            on_retry: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &serde_json::Value) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: config_values::get_u64(config, "max_retries")
+                .map(|value| value as u32)
+                .unwrap_or(defaults.max_retries),
+            initial_delay: config_values::get_u64(config, "retry_initial_delay_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.initial_delay),
+            max_delay: config_values::get_u64(config, "retry_max_delay_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_delay),
+            on_retry: None,
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0). A provider's
+    /// Retry-After always wins over the computed backoff.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after;
+        }
+        let backoff = self.initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        // Equal jitter: keep half of the backoff and randomize the rest
+        let half = backoff / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+/// Reads `Retry-After` (in seconds) or OpenAI's `retry-after-ms` header.
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+    if let Some(millis) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(millis.max(0.0) / 1000.0));
+    }
+    header("retry-after")
+        .and_then(|value| value.parse::<f64>().ok())
+        .map(|seconds| Duration::from_secs_f64(seconds.max(0.0)))
+}
+
+/// Sends the request built by `build_request`, retrying rate limits, server
+/// errors and connection failures according to `policy`. Unsuccessful
+/// responses are classified with `ChatModelError::from_response`.
+pub async fn send_with_retry<F>(policy: &RetryPolicy, build_request: F) -> Result<reqwest::Response, ChatModelError>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt = 0;
+    loop {
+        let error = match build_request().send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = parse_retry_after(response.headers());
+                let body = response.text().await.unwrap_or_default();
+                ChatModelError::from_response(status, retry_after, &body)
+            }
+            Err(e) => ChatModelError::from(e),
+        };
+
+        if !error.is_retryable() || attempt >= policy.max_retries {
+            return Err(error);
+        }
+        let delay = policy.delay(attempt, error.retry_after());
+        if let Some(on_retry) = &policy.on_retry {
+            on_retry(&Retry { error, delay, attempt: attempt + 1, max_retries: policy.max_retries });
+        }
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::test_server::{self, TestResponse};
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            on_retry: None,
+        }
+    }
+
+    #[test]
+    fn test_delay_grows_and_respects_retry_after() {
+        let policy = RetryPolicy::from_config(&serde_json::json!({ "retry_initial_delay_ms": "100", "retry_max_delay_ms": 1000 }));
+        for attempt in 0..6 {
+            let expected = Duration::from_millis(100 * 2u64.pow(attempt)).min(Duration::from_millis(1000));
+            let delay = policy.delay(attempt, None);
+            assert!(delay >= expected / 2 && delay <= expected, "attempt {}: {:?}", attempt, delay);
+        }
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(7))), Duration::from_secs(7));
+    }
+
+    #[tokio::test]
+    async fn test_retries_rate_limit_then_succeeds() {
+        let server = test_server::serve_responses(vec![
+            TestResponse::new(429, "application/json", "{\"error\":{\"message\":\"slow down\"}}").header("retry-after", "0"),
+            TestResponse::new(503, "text/plain", "unavailable"),
+            TestResponse::new(200, "text/plain", "ok"),
+        ]).await;
+        let client = reqwest::Client::new();
+        let retries = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let policy = RetryPolicy {
+            on_retry: Some(Arc::new({
+                let retries = retries.clone();
+                move |retry: &Retry| retries.lock().unwrap().push(retry.clone())
+            })),
+            ..fast_policy()
+        };
+
+        let response = send_with_retry(&policy, || client.get(&server.url)).await.expect("retried request");
+        assert_eq!(response.text().await.unwrap(), "ok");
+        let retries = retries.lock().unwrap();
+        assert_eq!(retries.iter().map(|retry| retry.attempt).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(retries[0].to_string(), format!("{}; retrying in 0.0s (1/2)", retries[0].error));
+    }
+
+    #[tokio::test]
+    async fn test_auth_errors_are_not_retried() {
+        let server = test_server::serve_responses(vec![
+            TestResponse::new(401, "application/json", "{\"error\":{\"message\":\"bad key\"}}"),
+            TestResponse::new(200, "text/plain", "ok"),
+        ]).await;
+        let client = reqwest::Client::new();
+
+        let error = send_with_retry(&fast_policy(), || client.get(&server.url)).await.err().expect("auth error");
+        assert_eq!(error, ChatModelError::Auth(String::from("bad key")));
+    }
+}
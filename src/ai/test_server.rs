@@ -0,0 +1,106 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+/// A canned HTTP server for exercising model clients without a network. Each
+/// connection is answered with the next response in order and the first raw
+/// request received is handed back for inspection.
+pub struct TestServer {
+    pub url: String,
+    request: oneshot::Receiver<String>,
+}
+
+impl TestServer {
+    pub async fn request(self) -> String {
+        self.request.await.expect("test server did not receive a request")
+    }
+}
+
+pub struct TestResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl TestResponse {
+    pub fn new(status: u16, content_type: &str, body: &str) -> TestResponse {
+        TestResponse {
+            status,
+            headers: vec![(String::from("content-type"), content_type.to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> TestResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    fn to_http(&self) -> String {
+        let mut response = format!(
+            "HTTP/1.1 {} Test\r\ncontent-length: {}\r\nconnection: close\r\n",
+            self.status, self.body.len()
+        );
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(&self.body);
+        response
+    }
+}
+
+pub async fn serve(status: u16, content_type: &str, body: &str) -> TestServer {
+    serve_responses(vec![TestResponse::new(status, content_type, body)]).await
+}
+
+pub async fn serve_responses(responses: Vec<TestResponse>) -> TestServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind test server");
+    let url = format!("http://{}", listener.local_addr().expect("local addr"));
+
+    let (sender, receiver) = oneshot::channel();
+    tokio::spawn(async move {
+        let mut sender = Some(sender);
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            let request = read_request(&mut socket).await;
+            socket.write_all(response.to_http().as_bytes()).await.expect("write response");
+            socket.shutdown().await.ok();
+            if let Some(sender) = sender.take() {
+                sender.send(request).ok();
+            }
+        }
+    });
+
+    TestServer { url, request: receiver }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = socket.read(&mut chunk).await.expect("read request");
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        let text = String::from_utf8_lossy(&buffer);
+        if let Some(header_end) = text.find("\r\n\r\n") {
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    if name.eq_ignore_ascii_case("content-length") {
+                        value.trim().parse::<usize>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or(0);
+            if buffer.len() >= header_end + 4 + content_length {
+                break;
+            }
+        }
+    }
+    String::from_utf8_lossy(&buffer).to_string()
+}
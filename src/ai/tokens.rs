@@ -0,0 +1,62 @@
+use super::chat_types::{ChatCompletionRequestMessage, ModelUsage};
+
+// Chat formats wrap every message in a few role and separator tokens, and the
+// reply is primed with a few more.
+const TOKENS_PER_MESSAGE: u32 = 4;
+const TOKENS_PER_REPLY: u32 = 3;
+
+/// Approximates how many tokens `text` encodes to. BPE tokenizers keep common
+/// words whole, split longer ones about every six characters and give most
+/// punctuation a token of its own, which stays close enough for cost
+/// estimates when a provider does not report usage.
+pub fn estimate_tokens(text: &str) -> u32 {
+    let mut tokens = 0;
+    let mut word_length: u32 = 0;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word_length += 1;
+            continue;
+        }
+        tokens += word_length.div_ceil(6);
+        word_length = 0;
+        if !c.is_whitespace() {
+            tokens += 1;
+        }
+    }
+    tokens + word_length.div_ceil(6)
+}
+
+pub fn estimate_message_tokens(message: &ChatCompletionRequestMessage) -> u32 {
+    TOKENS_PER_MESSAGE + estimate_tokens(&message.content)
+}
+
+pub fn estimate_prompt_tokens(messages: &[ChatCompletionRequestMessage]) -> u32 {
+    messages.iter().map(estimate_message_tokens).sum::<u32>() + TOKENS_PER_REPLY
+}
+
+pub fn estimate_usage(messages: &[ChatCompletionRequestMessage], completion: &str) -> ModelUsage {
+    let prompt_tokens = estimate_prompt_tokens(messages);
+    let completion_tokens = estimate_tokens(completion);
+    ModelUsage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::Role;
+
+    #[test]
+    fn test_estimates_words_and_punctuation() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello, world!"), 4);
+        assert_eq!(estimate_tokens("internationalization"), 4);
+
+        let messages = vec![ChatCompletionRequestMessage { role: Role::User, content: String::from("Hello, world!"), name: None }];
+        let usage = estimate_usage(&messages, "Hi there");
+        assert_eq!(usage, ModelUsage { prompt_tokens: 11, completion_tokens: 2, total_tokens: 13 });
+    }
+}
@@ -0,0 +1,326 @@
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::chat_error::ChatModelError;
+use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::chat_types::{
+    ChatCompletionChoice, ChatCompletionChunk, ChatCompletionDelta, ChatCompletionStream, ModelUsage, Role,
+};
+use crate::ai::config_values;
+use crate::ai::retry::{send_with_retry, RetryListener, RetryPolicy};
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const DEFAULT_API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+const DEFAULT_MODEL: &str = "claude-3-5-sonnet-latest";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Clone)]
+pub struct ClaudeClient {
+    http: reqwest::Client,
+    config: ClaudeConfig,
+}
+
+#[derive(Clone)]
+pub struct ClaudeConfig {
+    max_tokens: u32,
+    temperature: f32,
+    model: String,
+    base_url: String,
+    api_key_env: String,
+    retry: RetryPolicy,
+}
+
+#[derive(Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<Message<'a>>,
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart { message: MessageStart },
+    ContentBlockDelta { delta: ContentDelta },
+    MessageDelta { delta: MessageDeltaBody, usage: Option<OutputUsage> },
+    Error { error: ApiError },
+    // content_block_start/stop, message_stop and ping carry nothing we render
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct MessageStart {
+    id: String,
+    model: String,
+    usage: Option<InputUsage>,
+}
+
+#[derive(Deserialize)]
+struct InputUsage {
+    input_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct OutputUsage {
+    output_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct ContentDelta {
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MessageDeltaBody {
+    stop_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+type SendError = Box<dyn Error + Send + Sync>;
+
+/// Tracks the message metadata announced in `message_start` so every chunk
+/// that follows carries the same id and model.
+struct StreamState {
+    id: Option<String>,
+    model: String,
+    created: u32,
+    input_tokens: u32,
+}
+
+impl StreamState {
+    fn convert(&mut self, data: &str) -> Result<Option<ChatCompletionChunk>, SendError> {
+        let event: StreamEvent = serde_json::from_str(data)?;
+        let chunk = match event {
+            StreamEvent::MessageStart { message } => {
+                self.id = Some(message.id);
+                self.model = message.model;
+                self.input_tokens = message.usage.map(|usage| usage.input_tokens).unwrap_or(0);
+                Some(self.chunk(ChatCompletionDelta { content: None, role: Some(Role::Assistant) }, None, None))
+            }
+            StreamEvent::ContentBlockDelta { delta } => delta.text.map(|text| {
+                self.chunk(ChatCompletionDelta { content: Some(text), role: Some(Role::Assistant) }, None, None)
+            }),
+            StreamEvent::MessageDelta { delta, usage } => {
+                let usage = usage.map(|usage| ModelUsage {
+                    prompt_tokens: self.input_tokens,
+                    completion_tokens: usage.output_tokens,
+                    total_tokens: self.input_tokens + usage.output_tokens,
+                });
+                Some(self.chunk(ChatCompletionDelta { content: None, role: Some(Role::Assistant) }, delta.stop_reason, usage))
+            }
+            StreamEvent::Error { error } => {
+                // Errors can also arrive mid-stream, e.g. when the API becomes overloaded
+                let status = match error.kind.as_str() {
+                    "overloaded_error" => reqwest::StatusCode::from_u16(529).unwrap_or(reqwest::StatusCode::SERVICE_UNAVAILABLE),
+                    "rate_limit_error" => reqwest::StatusCode::TOO_MANY_REQUESTS,
+                    _ => reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                return Err(Box::new(ChatModelError::from_status(status, None, Some(&error.kind), error.message)));
+            }
+            StreamEvent::Other => None,
+        };
+        Ok(chunk)
+    }
+
+    fn chunk(&self, delta: ChatCompletionDelta, finish_reason: Option<String>, usage: Option<ModelUsage>) -> ChatCompletionChunk {
+        ChatCompletionChunk {
+            id: self.id.clone(),
+            object: String::from("chat.completion.chunk"),
+            created: self.created,
+            model: self.model.clone(),
+            choices: vec![ChatCompletionChoice { index: 0, delta, finish_reason }],
+            usage,
+        }
+    }
+}
+
+#[async_trait]
+impl ChatModel for ClaudeClient {
+    fn new(config: serde_json::Value) -> ClaudeClient {
+        ClaudeClient {
+            http: reqwest::Client::new(),
+            config: ClaudeConfig {
+                max_tokens: config_values::get_u64(&config, "max_tokens").unwrap_or(1000) as u32,
+                temperature: config_values::get_f64(&config, "temperature").unwrap_or(0.8) as f32,
+                model: config_values::get_string(&config, "model").unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+                base_url: config_values::get_string(&config, "base_url").unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+                api_key_env: config_values::get_string(&config, "api_key_env").unwrap_or_else(|| DEFAULT_API_KEY_ENV.to_string()),
+                retry: RetryPolicy::from_config(&config),
+            },
+        }
+    }
+
+    fn set_retry_listener(&mut self, listener: RetryListener) {
+        self.config.retry.on_retry = Some(listener);
+    }
+
+    async fn create_response_stream(&mut self, client_request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+        let api_key = std::env::var(&self.config.api_key_env)
+            .map_err(|_| ChatModelError::Auth(format!("environment variable {} is not set", self.config.api_key_env)))?;
+
+        // Anthropic takes system prompts as a top-level field rather than a message role
+        let system = client_request.messages.iter()
+            .filter(|msg| msg.role == Role::System)
+            .map(|msg| msg.content.as_str())
+            .collect::<Vec<_>>();
+        let messages = client_request.messages.iter()
+            .filter(|msg| msg.role != Role::System)
+            .map(|msg| Message {
+                role: match msg.role {
+                    Role::Assistant => "assistant",
+                    _ => "user",
+                },
+                content: &msg.content,
+            })
+            .collect::<Vec<_>>();
+
+        let request = MessagesRequest {
+            model: &self.config.model,
+            max_tokens: self.config.max_tokens,
+            temperature: self.config.temperature,
+            system: if system.is_empty() { None } else { Some(system.join("\n\n")) },
+            messages,
+            stream: true,
+        };
+
+        let url = format!("{}/v1/messages", self.config.base_url.trim_end_matches('/'));
+        let response = send_with_retry(&self.config.retry, || {
+            self.http
+                .post(&url)
+                .header("x-api-key", &api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .json(&request)
+        }).await?;
+
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as u32)
+            .unwrap_or(0);
+        let mut state = StreamState {
+            id: None,
+            model: self.config.model.clone(),
+            created,
+            input_tokens: 0,
+        };
+
+        let stream = response
+            .bytes_stream()
+            .eventsource()
+            .filter_map(move |event| {
+                let result = match event {
+                    Ok(event) => state.convert(&event.data).transpose(),
+                    Err(e) => Some(Err(Box::new(ChatModelError::Network(e.to_string())) as SendError)),
+                };
+                futures::future::ready(result)
+            })
+            .map(|result| result.map_err(|e| e as Box<dyn Error>))
+            .boxed();
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::ChatCompletionRequestMessage;
+    use crate::ai::test_server;
+
+    const EVENTS: &str = "event: message_start\n\
+data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"model\":\"claude-test\",\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n\
+event: content_block_start\n\
+data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n\
+event: ping\n\
+data: {\"type\":\"ping\"}\n\n\
+event: content_block_delta\n\
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n\
+event: content_block_delta\n\
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\" there\"}}\n\n\
+event: content_block_stop\n\
+data: {\"type\":\"content_block_stop\",\"index\":0}\n\n\
+event: message_delta\n\
+data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":5}}\n\n\
+event: message_stop\n\
+data: {\"type\":\"message_stop\"}\n\n";
+
+    fn request() -> ChatModelRequest {
+        ChatModelRequest {
+            messages: vec![
+                ChatCompletionRequestMessage { role: Role::System, content: "Be brief.".to_string(), name: None },
+                ChatCompletionRequestMessage { role: Role::User, content: "Hi".to_string(), name: None },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_converts_events_to_chunks() {
+        let server = test_server::serve(200, "text/event-stream", EVENTS).await;
+        std::env::set_var("KAITI_TEST_ANTHROPIC_KEY", "test-key");
+        let mut client = ClaudeClient::new(serde_json::json!({
+            "base_url": server.url,
+            "api_key_env": "KAITI_TEST_ANTHROPIC_KEY",
+            "model": "claude-test",
+        }));
+
+        let stream = client.create_response_stream(&request()).await.expect("stream");
+        let chunks = stream.map(|chunk| chunk.expect("chunk")).collect::<Vec<_>>().await;
+
+        let text = chunks.iter()
+            .flat_map(|chunk| chunk.choices.iter())
+            .filter_map(|choice| choice.delta.content.clone())
+            .collect::<String>();
+        assert_eq!(text, "Hello there");
+        assert!(chunks.iter().all(|chunk| chunk.id.as_deref() == Some("msg_1")));
+
+        let last = chunks.last().expect("final chunk");
+        assert_eq!(last.choices[0].finish_reason.as_deref(), Some("end_turn"));
+        assert_eq!(last.usage, Some(ModelUsage { prompt_tokens: 12, completion_tokens: 5, total_tokens: 17 }));
+
+        let received = server.request().await;
+        assert!(received.contains("x-api-key: test-key"));
+        assert!(received.contains("\"system\":\"Be brief.\""));
+        assert!(received.contains("\"messages\":[{\"role\":\"user\",\"content\":\"Hi\"}]"));
+    }
+
+    #[tokio::test]
+    async fn test_error_status_is_reported() {
+        let body = "{\"type\":\"error\",\"error\":{\"type\":\"authentication_error\",\"message\":\"invalid x-api-key\"}}";
+        let server = test_server::serve(401, "application/json", body).await;
+        std::env::set_var("KAITI_TEST_ANTHROPIC_KEY", "test-key");
+        let mut client = ClaudeClient::new(serde_json::json!({
+            "base_url": server.url,
+            "api_key_env": "KAITI_TEST_ANTHROPIC_KEY",
+        }));
+
+        let error = match client.create_response_stream(&request()).await {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e,
+        };
+        assert_eq!(
+            error.downcast_ref::<ChatModelError>(),
+            Some(&ChatModelError::Auth(String::from("invalid x-api-key")))
+        );
+    }
+}
@@ -0,0 +1,3 @@
+mod claude_client;
+
+pub use claude_client::ClaudeClient;
@@ -1,2 +1,3 @@
 pub mod settings;
-pub mod profile;
\ No newline at end of file
+pub mod profile;
+pub mod usage;
\ No newline at end of file
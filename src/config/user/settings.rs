@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
 use super::super::config_trait::ConfigTrait;
 use serde::{Deserialize, Serialize};
 
+use crate::ai::chat_types::ModelUsage;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Application {
     pub name: String,
@@ -31,6 +35,62 @@ pub struct SettingsConfig {
     pub application: Application,
     pub models: Vec<ModelConfig>,
     pub modes: InteractionModes,
+    #[serde(default)]
+    pub pricing: PriceTable,
+    #[serde(default)]
+    pub capture: CaptureSettings,
+}
+
+/// How `kaiti debug` reads the terminal.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CaptureSettings {
+    /// Lines read back from the bottom of the tmux or screen window.
+    #[serde(default = "default_scrollback_lines")]
+    pub scrollback_lines: usize,
+}
+
+fn default_scrollback_lines() -> usize {
+    300
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        CaptureSettings { scrollback_lines: default_scrollback_lines() }
+    }
+}
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPricing {
+    pub fn cost(&self, usage: &ModelUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million) / 1_000_000.0
+    }
+}
+
+/// Prices keyed by the model name providers report, e.g. `gpt-4o` or
+/// `claude-3-5-sonnet`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct PriceTable(pub BTreeMap<String, ModelPricing>);
+
+impl PriceTable {
+    /// Finds the price for `model`, falling back to the longest entry it
+    /// starts with so dated snapshots such as `gpt-4o-2024-08-06` match `gpt-4o`.
+    pub fn find(&self, model: &str) -> Option<&ModelPricing> {
+        if let Some(pricing) = self.0.get(model) {
+            return Some(pricing);
+        }
+        self.0.iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, pricing)| pricing)
+    }
 }
 
 impl ConfigTrait for SettingsConfig {
@@ -41,4 +101,24 @@ impl ConfigTrait for SettingsConfig {
     fn config_filename() -> &'static str {
         "settings.json"
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_table_matches_model_snapshots() {
+        let table: PriceTable = serde_json::from_str(r#"{
+            "gpt-4o": { "input_per_million": 2.5, "output_per_million": 10.0 },
+            "gpt-4o-mini": { "input_per_million": 0.15, "output_per_million": 0.6 }
+        }"#).unwrap();
+
+        assert_eq!(table.find("gpt-4o-2024-08-06").unwrap().input_per_million, 2.5);
+        assert_eq!(table.find("gpt-4o-mini-2024-07-18").unwrap().input_per_million, 0.15);
+        assert!(table.find("claude-3-5-sonnet").is_none());
+
+        let usage = ModelUsage { prompt_tokens: 1000, completion_tokens: 500, total_tokens: 1500 };
+        let cost = table.find("gpt-4o").unwrap().cost(&usage);
+        assert!((cost - 0.0075).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use super::super::config_trait::ConfigTrait;
+use crate::ai::chat_types::ModelUsage;
+
+// Per-day totals are kept indefinitely; sessions are only kept for the most
+// recent conversations so the ledger does not grow without bound.
+const MAX_SESSIONS: usize = 200;
+
+/// Token usage and cost of a single response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    pub usage: ModelUsage,
+    /// The token counts are a local estimate because the provider did not report them.
+    pub estimated: bool,
+    /// Cost in USD, or `None` when the model is missing from the price table.
+    pub cost: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct UsageTotals {
+    pub requests: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Cost in USD of the requests with a known price.
+    pub cost: f64,
+    #[serde(default)]
+    pub estimated_requests: u32,
+    #[serde(default)]
+    pub unpriced_requests: u32,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        self.prompt_tokens += record.usage.prompt_tokens as u64;
+        self.completion_tokens += record.usage.completion_tokens as u64;
+        match record.cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced_requests += 1,
+        }
+        if record.estimated {
+            self.estimated_requests += 1;
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SessionUsage {
+    pub model: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Running usage totals stored in `~/.k-aiti/usage.json`. Days are keyed
+/// `YYYY-MM-DD` in local time and sessions by their id, which sorts by start time.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct UsageLedger {
+    #[serde(default)]
+    pub days: BTreeMap<String, UsageTotals>,
+    #[serde(default)]
+    pub sessions: BTreeMap<String, SessionUsage>,
+}
+
+impl UsageLedger {
+    /// Reads the ledger, starting a new one if none has been written yet.
+    pub fn load() -> Result<UsageLedger, Box<dyn Error>> {
+        if UsageLedger::config_exists() {
+            UsageLedger::read()
+        } else {
+            Ok(UsageLedger::default())
+        }
+    }
+
+    pub fn record(&mut self, day: &str, session_id: &str, model: &str, record: &UsageRecord) {
+        self.days.entry(day.to_string()).or_default().add(record);
+        let session = self.sessions.entry(session_id.to_string()).or_default();
+        session.model = model.to_string();
+        session.totals.add(record);
+
+        while self.sessions.len() > MAX_SESSIONS {
+            let oldest = match self.sessions.keys().next() {
+                Some(oldest) => oldest.clone(),
+                None => break,
+            };
+            self.sessions.remove(&oldest);
+        }
+    }
+}
+
+impl ConfigTrait for UsageLedger {
+    fn config_directory() -> &'static str {
+        ".k-aiti"
+    }
+
+    fn config_filename() -> &'static str {
+        "usage.json"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(prompt_tokens: u32, completion_tokens: u32, cost: Option<f64>) -> UsageRecord {
+        UsageRecord {
+            usage: ModelUsage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens },
+            estimated: cost.is_none(),
+            cost,
+        }
+    }
+
+    #[test]
+    fn test_records_day_and_session_totals() {
+        let mut ledger = UsageLedger::default();
+        ledger.record("2024-05-01", "20240501-090000", "gpt-4o", &record(100, 20, Some(0.01)));
+        ledger.record("2024-05-01", "20240501-120000", "llama", &record(50, 10, None));
+        ledger.record("2024-05-02", "20240501-120000", "llama", &record(5, 5, None));
+
+        let day = &ledger.days["2024-05-01"];
+        assert_eq!((day.requests, day.total_tokens(), day.unpriced_requests), (2, 180, 1));
+        assert!((day.cost - 0.01).abs() < 1e-12);
+        assert_eq!(ledger.sessions["20240501-120000"].totals.requests, 2);
+        assert_eq!(ledger.sessions["20240501-120000"].totals.estimated_requests, 2);
+
+        let round_trip: UsageLedger = serde_json::from_str(&serde_json::to_string(&ledger).unwrap()).unwrap();
+        assert_eq!(round_trip, ledger);
+    }
+}
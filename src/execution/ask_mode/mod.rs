@@ -0,0 +1,247 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{IsTerminal, Read, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::ai::chat_error::ChatModelError;
+use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::chat_types::{ChatCompletionRequestMessage, ChatCompletionStream, ModelUsage, Role};
+use crate::ai::config_values;
+use crate::ai::retry::RetryListener;
+use crate::config::user::settings::ModelConfig;
+use crate::execution::chat_mode::markdown::MarkdownStream;
+use crate::models::global_registry;
+
+/// Exit code for a missing prompt or an unknown option value.
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code when the provider rejected or failed the request.
+pub const EXIT_PROVIDER: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// The answer as the model wrote it.
+    Text,
+    /// The answer rendered as formatted markdown.
+    Markdown,
+    /// A single JSON object with the answer, model and usage once it completes.
+    Json,
+    /// Every streamed chunk as a line of JSON.
+    NdjsonChunks,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson-chunks" => Ok(OutputFormat::NdjsonChunks),
+            _ => Err(format!("Unknown format {}; expected text, markdown, json or ndjson-chunks", value)),
+        }
+    }
+}
+
+pub struct AskOptions {
+    pub prompt: Option<String>,
+    /// Defaults to markdown on a terminal and text everywhere else.
+    pub format: Option<OutputFormat>,
+    /// Overrides the `system_prompt` configured for the model.
+    pub system_prompt: Option<String>,
+}
+
+/// A problem with how the command was invoked rather than with the request.
+#[derive(Debug)]
+pub(crate) struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for UsageError {}
+
+#[derive(Serialize)]
+struct AskAnswer {
+    model: Option<String>,
+    content: String,
+    usage: Option<ModelUsage>,
+}
+
+/// Answers a single prompt, with anything piped to stdin as its context, and
+/// streams the answer to stdout.
+pub async fn run_ask(c_model: &ModelConfig, options: AskOptions) -> Result<(), Box<dyn Error>> {
+    let context = read_piped_input()?;
+    let system_prompt = options.system_prompt.or_else(|| config_values::get_string(&c_model.config, "system_prompt"));
+    let request = build_request(options.prompt.as_deref(), context.as_deref(), system_prompt.as_deref())?;
+
+    let mut chat_model: Box<dyn ChatModel> = {
+        let registry = global_registry().read().map_err(|_| "model registry is poisoned")?;
+        registry.create_model(c_model)?
+    };
+    let terminal = std::io::stdout().is_terminal();
+    let format = options.format.unwrap_or(if terminal { OutputFormat::Markdown } else { OutputFormat::Text });
+    // Machine-readable output stays limited to the answer
+    if matches!(format, OutputFormat::Text | OutputFormat::Markdown) {
+        chat_model.set_retry_listener(stderr_retry_listener());
+    }
+    let stream = chat_model.create_response_stream(&request).await?;
+
+    let width = crossterm::terminal::size().map(|(columns, _)| columns as usize).unwrap_or(80);
+    write_answer(stream, format, width, &mut std::io::stdout().lock()).await
+}
+
+/// Reports retries on stderr so they stay out of the answer.
+pub(crate) fn stderr_retry_listener() -> RetryListener {
+    Arc::new(|retry| eprintln!("{}", retry))
+}
+
+/// The exit code for an error returned by `run_ask`.
+pub fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    if error.downcast_ref::<ChatModelError>().is_some() {
+        EXIT_PROVIDER
+    } else if error.downcast_ref::<UsageError>().is_some() {
+        EXIT_USAGE
+    } else {
+        1
+    }
+}
+
+/// Whatever was piped to stdin, unless it is a terminal or the input is blank.
+pub(crate) fn read_piped_input() -> Result<Option<String>, Box<dyn Error>> {
+    if std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    Ok(Some(input).filter(|input| !input.trim().is_empty()))
+}
+
+/// The request for `prompt`, with any piped `context` fenced below it.
+pub(crate) fn build_request(prompt: Option<&str>, context: Option<&str>, system_prompt: Option<&str>) -> Result<ChatModelRequest, Box<dyn Error>> {
+    let prompt = prompt.map(str::trim).filter(|prompt| !prompt.is_empty());
+    let content = match (prompt, context) {
+        (Some(prompt), Some(context)) => format!("{}\n\n```\n{}\n```", prompt, context.trim_end()),
+        (Some(prompt), None) => prompt.to_string(),
+        (None, Some(context)) => context.trim_end().to_string(),
+        (None, None) => return Err(Box::new(UsageError(String::from("Nothing to ask; pass a prompt or pipe input to stdin")))),
+    };
+
+    let mut messages = Vec::new();
+    if let Some(system_prompt) = system_prompt.filter(|prompt| !prompt.trim().is_empty()) {
+        messages.push(ChatCompletionRequestMessage { role: Role::System, content: system_prompt.to_string(), name: None });
+    }
+    messages.push(ChatCompletionRequestMessage { role: Role::User, content, name: None });
+    Ok(ChatModelRequest { messages })
+}
+
+/// Streams the answer to `out` in `format`.
+pub(crate) async fn write_answer<W: Write>(mut stream: ChatCompletionStream, format: OutputFormat, width: usize, out: &mut W) -> Result<(), Box<dyn Error>> {
+    let mut answer = AskAnswer { model: None, content: String::new(), usage: None };
+    let mut markdown = (format == OutputFormat::Markdown).then(|| MarkdownStream::new(width));
+
+    while let Some(chunk) = stream.next().await {
+        // Unlike chat, a failure part way through fails the whole command so scripts notice
+        let chunk = chunk?;
+        if format == OutputFormat::NdjsonChunks {
+            writeln!(out, "{}", serde_json::to_string(&chunk)?)?;
+            out.flush()?;
+            continue;
+        }
+        if !chunk.model.is_empty() {
+            answer.model = Some(chunk.model.clone());
+        }
+        if let Some(usage) = chunk.usage.filter(|usage| usage.total_tokens > 0) {
+            answer.usage = Some(usage);
+        }
+        for content in chunk.choices.iter().filter_map(|choice| choice.delta.content.as_deref()) {
+            answer.content.push_str(content);
+            match (&mut markdown, format) {
+                (Some(markdown), _) => write!(out, "{}", markdown.push(content))?,
+                (None, OutputFormat::Text) => write!(out, "{}", content)?,
+                _ => continue,
+            }
+            out.flush()?;
+        }
+    }
+
+    match format {
+        OutputFormat::Text | OutputFormat::Markdown => {
+            if let Some(markdown) = &mut markdown {
+                write!(out, "{}", markdown.finish())?;
+            }
+            if !answer.content.ends_with('\n') {
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Json => writeln!(out, "{}", serde_json::to_string(&answer)?)?,
+        OutputFormat::NdjsonChunks => {}
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::{ChatCompletionChoice, ChatCompletionChunk, ChatCompletionDelta};
+
+    fn chunk(content: &str, usage: Option<ModelUsage>) -> ChatCompletionChunk {
+        ChatCompletionChunk {
+            id: None,
+            object: String::from("chat.completion.chunk"),
+            created: 0,
+            model: String::from("gpt-4o"),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                delta: ChatCompletionDelta { content: Some(content.to_string()), role: Some(Role::Assistant) },
+                finish_reason: None,
+            }],
+            usage,
+        }
+    }
+
+    fn stream() -> ChatCompletionStream {
+        let usage = ModelUsage { prompt_tokens: 12, completion_tokens: 3, total_tokens: 15 };
+        futures::stream::iter(vec![chunk("The disk ", None), chunk("is full.", Some(usage))]).map(Ok).boxed()
+    }
+
+    async fn output(format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write_answer(stream(), format, 80, &mut out).await.expect("answer");
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_piped_input_becomes_context() {
+        let request = build_request(Some("why did this fail"), Some("No space left on device\n"), Some("Be brief")).unwrap();
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, Role::System);
+        assert_eq!(request.messages[1].content, "why did this fail\n\n```\nNo space left on device\n```");
+
+        assert_eq!(build_request(None, Some("just the log"), None).unwrap().messages[0].content, "just the log");
+        let error = build_request(Some("  "), None, None).err().expect("usage error");
+        assert_eq!(exit_code(error.as_ref()), EXIT_USAGE);
+        assert_eq!("ndjson-chunks".parse::<OutputFormat>(), Ok(OutputFormat::NdjsonChunks));
+    }
+
+    #[tokio::test]
+    async fn test_output_formats() {
+        assert_eq!(output(OutputFormat::Text).await, "The disk is full.\n");
+
+        let json: serde_json::Value = serde_json::from_str(&output(OutputFormat::Json).await).unwrap();
+        assert_eq!(json["content"], "The disk is full.");
+        assert_eq!(json["model"], "gpt-4o");
+        assert_eq!(json["usage"]["total_tokens"], 15);
+
+        let chunks = output(OutputFormat::NdjsonChunks).await;
+        let lines = chunks.lines().map(|line| serde_json::from_str::<ChatCompletionChunk>(line).unwrap()).collect::<Vec<_>>();
+        assert_eq!(lines, vec![chunk("The disk ", None), chunk("is full.", lines[1].usage.clone())]);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+
+use crate::ai::{config_values, tokens};
+
+/// Limits on the files attached to a chat. Read from the model config keys
+/// `attachment_max_file_bytes` and `attachment_max_tokens`; the token budget
+/// covers every file attached over the whole conversation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachmentBudget {
+    pub max_file_bytes: u64,
+    pub max_tokens: u32,
+}
+
+impl Default for AttachmentBudget {
+    fn default() -> Self {
+        AttachmentBudget {
+            max_file_bytes: 256 * 1024,
+            max_tokens: 32_000,
+        }
+    }
+}
+
+impl AttachmentBudget {
+    pub fn from_config(config: &serde_json::Value) -> AttachmentBudget {
+        let defaults = AttachmentBudget::default();
+        AttachmentBudget {
+            max_file_bytes: config_values::get_u64(config, "attachment_max_file_bytes").unwrap_or(defaults.max_file_bytes),
+            max_tokens: config_values::get_u64(config, "attachment_max_tokens")
+                .map(|value| value as u32)
+                .unwrap_or(defaults.max_tokens),
+        }
+    }
+}
+
+pub struct Attachment {
+    pub path: PathBuf,
+    pub content: String,
+    pub tokens: u32,
+}
+
+/// The files read for one `--file` or `/add`, and the ones left out along with why.
+#[derive(Default)]
+pub struct AttachmentSet {
+    pub attached: Vec<Attachment>,
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+impl AttachmentSet {
+    /// The context message carrying the attached files, each in its own fence.
+    pub fn message(&self) -> String {
+        let mut message = String::from("The following files are attached as context for this conversation.\n");
+        for attachment in &self.attached {
+            let language = attachment.path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+            let fence = fence_for(&attachment.content);
+            message.push_str(&format!("\nFile: {}\n{}{}\n{}\n{}\n",
+                attachment.path.display(), fence, language, attachment.content.trim_end(), fence));
+        }
+        message
+    }
+
+    pub fn tokens(&self) -> u32 {
+        self.attached.iter().map(|attachment| attachment.tokens).sum()
+    }
+}
+
+/// Reads the files named by `patterns` (files, directories or globs) within
+/// `budget`, given that `used_tokens` are already taken by earlier attachments.
+pub fn collect(patterns: &[String], budget: &AttachmentBudget, used_tokens: u32) -> Result<AttachmentSet, Box<dyn Error>> {
+    let mut paths = BTreeSet::new();
+    for pattern in patterns {
+        let matched = expand(pattern)?;
+        if matched.is_empty() {
+            return Err(format!("No files match {}", pattern).into());
+        }
+        paths.extend(matched);
+    }
+
+    let mut set = AttachmentSet::default();
+    let mut used_tokens = used_tokens;
+    for path in paths {
+        match read_text(&path, budget.max_file_bytes) {
+            Ok(content) => {
+                let tokens = tokens::estimate_tokens(&content);
+                if used_tokens + tokens > budget.max_tokens {
+                    set.skipped.push((path, format!("~{} tokens would exceed the attachment budget of {}", tokens, budget.max_tokens)));
+                    continue;
+                }
+                used_tokens += tokens;
+                set.attached.push(Attachment { path, content, tokens });
+            }
+            Err(reason) => set.skipped.push((path, reason)),
+        }
+    }
+    Ok(set)
+}
+
+/// Files named by a path or glob. Directories and globs are walked with the
+/// `.gitignore` rules and hidden files skipped, while a file named directly is
+/// always used.
+fn expand(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let path = Path::new(pattern);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if path.is_dir() {
+        return Ok(walk(path).collect());
+    }
+    if !pattern.contains(['*', '?', '[', '{']) {
+        return Err(format!("No such file or directory: {}", pattern).into());
+    }
+
+    let matcher = GlobBuilder::new(pattern.trim_start_matches("./"))
+        .literal_separator(true)
+        .build()?
+        .compile_matcher();
+    // Only the directories before the first wildcard need walking
+    let base = path.components()
+        .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[', '{']))
+        .collect::<PathBuf>();
+    let base = if base.as_os_str().is_empty() { PathBuf::from(".") } else { base };
+    Ok(walk(&base)
+        .filter(|file| matcher.is_match(file.strip_prefix(".").unwrap_or(file)))
+        .collect())
+}
+
+fn walk(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkBuilder::new(dir)
+        // Honour .gitignore outside of a git checkout too
+        .require_git(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+        .map(|entry| entry.into_path())
+}
+
+fn read_text(path: &Path, max_bytes: u64) -> Result<String, String> {
+    let size = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > max_bytes {
+        return Err(format!("{} KB is over the {} KB file limit", size.div_ceil(1024), max_bytes / 1024));
+    }
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.iter().take(8192).any(|byte| *byte == 0) {
+        return Err(String::from("binary file"));
+    }
+    String::from_utf8(bytes).map_err(|_| String::from("binary file"))
+}
+
+/// A backtick fence longer than any run of backticks in `content`.
+fn fence_for(content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(paths: &[Attachment], root: &Path) -> Vec<String> {
+        paths.iter()
+            .map(|attachment| attachment.path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_respects_gitignore_binary_and_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub mod app;\n").unwrap();
+        fs::write(root.join("src/logo.png"), [0x89, b'P', b'N', b'G', 0, 0]).unwrap();
+        fs::write(root.join("target/out.rs"), "generated").unwrap();
+
+        let budget = AttachmentBudget::default();
+        let set = collect(&[root.to_string_lossy().to_string()], &budget, 0).unwrap();
+        assert_eq!(names(&set.attached, root), vec!["src/lib.rs", "src/main.rs"]);
+        assert_eq!(set.skipped.len(), 1);
+        assert_eq!(set.skipped[0].1, "binary file");
+
+        let glob = format!("{}/src/*.rs", root.display());
+        let set = collect(&[glob], &budget, 0).unwrap();
+        assert_eq!(set.attached.len(), 2);
+
+        let tight = AttachmentBudget { max_file_bytes: 1024, max_tokens: 10 };
+        let set = collect(&[root.join("src/main.rs").to_string_lossy().to_string()], &tight, 5).unwrap();
+        assert!(set.attached.is_empty());
+        assert!(set.skipped[0].1.contains("attachment budget"));
+
+        assert!(collect(&[root.join("missing.rs").to_string_lossy().to_string()], &budget, 0).is_err());
+    }
+
+    #[test]
+    fn test_message_fences_each_file() {
+        let set = AttachmentSet {
+            attached: vec![Attachment {
+                path: PathBuf::from("README.md"),
+                content: String::from("```bash\ncargo run\n```\n"),
+                tokens: 5,
+            }],
+            skipped: Vec::new(),
+        };
+        assert!(set.message().ends_with("\nFile: README.md\n````md\n```bash\ncargo run\n```\n````\n"));
+    }
+}
@@ -0,0 +1,123 @@
+use super::chat_log::ChatLogEntry;
+
+// The chat log holds the branch being followed. The first message of each
+// branch keeps its siblings, so the rest of the tree hangs off that path and
+// is saved along with it.
+
+/// The branch position and sibling count at `index`, both counting the branch being followed.
+pub fn position(log: &[ChatLogEntry], index: usize) -> (usize, usize) {
+    (log[index].branch, log[index].branches.len() + 1)
+}
+
+/// Indices of the messages where the conversation branches.
+pub fn forks(log: &[ChatLogEntry]) -> Vec<usize> {
+    log.iter().enumerate()
+        .filter(|(_, entry)| !entry.branches.is_empty())
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Takes every branch starting at `index` out of `log`, in order, leaving
+/// the conversation before it.
+pub fn split(log: &mut Vec<ChatLogEntry>, index: usize) -> Vec<Vec<ChatLogEntry>> {
+    let mut followed = log.split_off(index);
+    let mut branches = std::mem::take(&mut followed[0].branches);
+    let position = std::mem::take(&mut followed[0].branch).min(branches.len());
+    branches.insert(position, followed);
+    branches
+}
+
+/// Puts the branches taken by `split` back, following the one at `position`.
+pub fn join(log: &mut Vec<ChatLogEntry>, mut branches: Vec<Vec<ChatLogEntry>>, position: usize) {
+    let mut followed = branches.remove(position);
+    followed[0].branches = branches;
+    followed[0].branch = position;
+    log.extend(followed);
+}
+
+/// Follows the branch at `position` among those starting at `index`.
+pub fn switch(log: &mut Vec<ChatLogEntry>, index: usize, position: usize) -> Result<(), String> {
+    let (_, count) = position_at(log, index)?;
+    if position >= count {
+        return Err(format!("There are only {} branches here", count));
+    }
+    let branches = split(log, index);
+    join(log, branches, position);
+    Ok(())
+}
+
+/// Adds the messages from `index` on as a new branch beside the earlier
+/// ones, given what `split` took out at `index`, and follows it.
+pub fn add(log: &mut Vec<ChatLogEntry>, index: usize, mut branches: Vec<Vec<ChatLogEntry>>) {
+    let new_branch = log.split_off(index);
+    branches.push(new_branch);
+    let position = branches.len() - 1;
+    join(log, branches, position);
+}
+
+/// Removes the branch being followed from `index` on, following its
+/// nearest sibling instead when it has one.
+pub fn remove(log: &mut Vec<ChatLogEntry>, index: usize) -> Vec<ChatLogEntry> {
+    let mut removed = log.split_off(index);
+    let siblings = std::mem::take(&mut removed[0].branches);
+    let position = std::mem::take(&mut removed[0].branch);
+    if !siblings.is_empty() {
+        let position = position.min(siblings.len()).saturating_sub(1);
+        join(log, siblings, position);
+    }
+    removed
+}
+
+fn position_at(log: &[ChatLogEntry], index: usize) -> Result<(usize, usize), String> {
+    if index >= log.len() {
+        return Err(String::from("There is no message there"));
+    }
+    Ok(position(log, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::Role;
+
+    fn contents(log: &[ChatLogEntry]) -> Vec<&str> {
+        log.iter().map(|entry| entry.message.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_branches_are_kept_and_switched() {
+        let mut log = vec![
+            ChatLogEntry::new(Role::User, String::from("q1")),
+            ChatLogEntry::new(Role::Assistant, String::from("a1")),
+        ];
+
+        // Regenerate the answer
+        let branches = split(&mut log, 1);
+        log.push(ChatLogEntry::new(Role::Assistant, String::from("a1 again")));
+        add(&mut log, 1, branches);
+        assert_eq!(contents(&log), vec!["q1", "a1 again"]);
+        assert_eq!(position(&log, 1), (1, 2));
+
+        // Edit the question, which keeps both answers on the old branch
+        let branches = split(&mut log, 0);
+        log.push(ChatLogEntry::new(Role::User, String::from("q1 edited")));
+        log.push(ChatLogEntry::new(Role::Assistant, String::from("a2")));
+        add(&mut log, 0, branches);
+        assert_eq!(forks(&log), vec![0]);
+
+        switch(&mut log, 0, 0).unwrap();
+        assert_eq!(contents(&log), vec!["q1", "a1 again"]);
+        switch(&mut log, 1, 0).unwrap();
+        assert_eq!(contents(&log), vec!["q1", "a1"]);
+        assert_eq!(forks(&log), vec![0, 1]);
+        assert!(switch(&mut log, 1, 2).is_err());
+
+        switch(&mut log, 0, 1).unwrap();
+        assert_eq!(contents(&log), vec!["q1 edited", "a2"]);
+
+        let removed = remove(&mut log, 0);
+        assert_eq!(contents(&removed), vec!["q1 edited", "a2"]);
+        assert_eq!(contents(&log), vec!["q1", "a1"]);
+        assert_eq!(position(&log, 0), (0, 1));
+    }
+}
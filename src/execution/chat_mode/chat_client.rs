@@ -1,59 +1,961 @@
 use std::error::Error;
 
+use futures::StreamExt;
+
 use crate::ai::{
     chat_model::{ChatModel, ChatModelRequest},
-    chat_types::{ChatCompletionRequestMessage, Role}
+    chat_types::{ChatCompletionRequestMessage, ChatCompletionStream, Role},
+    retry::RetryListener,
 };
-use super::terminal_renderer::TerminalRenderer;
-use crate::execution::input_provider::get_user_input;
+use super::attachments::{self, AttachmentBudget, AttachmentSet};
+use super::branches;
+use super::chat_log::{self, ChatLogEntry, SUMMARY_HEADING};
+use super::commands::{self, ChatCommand, Input};
+use super::context_policy::{ContextPolicy, ContextStrategy};
+use super::model_selection::ModelSelection;
+use super::terminal_renderer::{RenderedResponse, TerminalRenderer};
+use super::usage_tracker::UsageTracker;
+use crate::config::user::usage::{UsageRecord, UsageTotals};
+use crate::execution::code_blocks;
+use crate::execution::input_provider::LineEditor;
+use crate::execution::sessions::{self, ExportFormat, Session, SessionStore};
 
 pub struct ChatClient {
     chat_model: Box<dyn ChatModel>,
-    chat_log: Vec<crate::ai::chat_types::ChatCompletionRequestMessage>,
+    chat_log: Vec<ChatLogEntry>,
+    usage: UsageTracker,
+    context: ContextPolicy,
+    attachments: AttachmentBudget,
+    /// Where the conversation is saved after every turn.
+    session: Option<(SessionStore, Session)>,
+    /// The configured models `/model` and `/set` work with.
+    models: Option<ModelSelection>,
+    /// Created on first use so tests never touch the terminal.
+    editor: Option<LineEditor>,
+    /// Where retries are shown, kept for the models switched to later.
+    retry_listener: Option<RetryListener>,
 }
 
+const SUMMARY_PROMPT: &str = "Summarize the following conversation between a user and an assistant in a few short paragraphs. \
+    Keep any facts, decisions, code identifiers and open questions that later turns may depend on.";
+
 impl ChatClient {
-    pub fn new(chat_model: Box<dyn ChatModel>,) -> Self {
-        Self { chat_model, chat_log: Vec::new() }
+    pub fn new(chat_model: Box<dyn ChatModel>, usage: UsageTracker) -> Self {
+        Self {
+            chat_model,
+            chat_log: Vec::new(),
+            usage,
+            context: ContextPolicy::default(),
+            attachments: AttachmentBudget::default(),
+            session: None,
+            models: None,
+            editor: None,
+            retry_listener: None,
+        }
+    }
+
+    pub fn set_retry_listener(&mut self, listener: RetryListener) {
+        self.chat_model.set_retry_listener(listener.clone());
+        self.retry_listener = Some(listener);
+    }
+
+    /// Saves the conversation to `session` as it goes, continuing from any
+    /// messages the session already has.
+    pub fn set_session(&mut self, store: SessionStore, session: Session) {
+        self.chat_log = session.messages.clone();
+        self.session = Some((store, session));
+    }
+
+    /// The id of the saved session, once anything has been saved to it.
+    pub fn saved_session_id(&self) -> Option<&str> {
+        match &self.session {
+            Some((_, session)) if !session.messages.is_empty() => Some(&session.id),
+            _ => None,
+        }
+    }
+
+    pub fn save_session(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some((store, session)) = &mut self.session {
+            session.messages = self.chat_log.clone();
+            session.usage = self.usage.session().clone();
+            session.updated_at = chrono::Local::now().to_rfc3339();
+            if session.title.is_none() {
+                session.title = session.generate_title();
+            }
+            store.save(session)?;
+        }
+        Ok(())
+    }
+
+    /// The conversation as a session, including any messages not yet saved.
+    fn session_snapshot(&self) -> Session {
+        let mut session = match &self.session {
+            Some((_, session)) => session.clone(),
+            None => Session::new(self.models.as_ref().map(|models| models.current().id.clone()).unwrap_or_default()),
+        };
+        session.messages = self.chat_log.clone();
+        session.usage = self.usage.session().clone();
+        if session.title.is_none() {
+            session.title = session.generate_title();
+        }
+        session
+    }
+
+    pub fn set_context_policy(&mut self, context: ContextPolicy) {
+        self.context = context;
+    }
+
+    pub fn set_attachment_budget(&mut self, attachments: AttachmentBudget) {
+        self.attachments = attachments;
+    }
+
+    pub fn set_model_selection(&mut self, models: ModelSelection) {
+        self.models = Some(models);
+    }
+
+    /// Sets the system message sent at the start of every request, replacing
+    /// any system prompt already in the conversation.
+    pub fn set_system_prompt(&mut self, prompt: String) {
+        let system_entry = ChatLogEntry::new(Role::System, prompt);
+        match self.chat_log.first_mut() {
+            Some(entry) if entry.message.role == Role::System => *entry = system_entry,
+            _ => self.chat_log.insert(0, system_entry),
+        }
+    }
+
+    /// Adds the files named by `patterns` to the conversation as a pinned
+    /// context message, within what is left of the attachment budget.
+    pub fn attach(&mut self, patterns: &[String]) -> Result<AttachmentSet, Box<dyn Error>> {
+        let used_tokens = self.chat_log.iter()
+            .filter(|entry| entry.attachment)
+            .map(|entry| crate::ai::tokens::estimate_tokens(&entry.message.content))
+            .sum();
+        let set = attachments::collect(patterns, &self.attachments, used_tokens)?;
+        if !set.attached.is_empty() {
+            self.chat_log.push(ChatLogEntry {
+                pinned: true,
+                attachment: true,
+                ..ChatLogEntry::new(Role::User, set.message())
+            });
+            self.save_session()?;
+        }
+        Ok(set)
     }
 
     pub async fn run(&mut self, renderer: &mut TerminalRenderer) -> Result<(), Box<dyn Error>> {
         loop {
-            let input = self.get_input(renderer).await?;
-            if input.trim() == "stop" {
-                break;
+            let input = match self.get_input().await? {
+                Some(input) => input,
+                // Ctrl-D ends the chat like /exit
+                None => break,
+            };
+            let result = match commands::parse_input(&input) {
+                Input::Message(message) if message.trim().is_empty() => continue,
+                Input::Message(message) => self.handle_response(message, renderer).await.map(|_| ()),
+                Input::Command(ChatCommand::Exit) => break,
+                Input::Command(command) => self.run_command(command, renderer).await,
+                Input::Invalid(message) => {
+                    renderer.print_notice(&message);
+                    continue;
+                }
+            };
+            // Failed requests and commands are reported and the conversation carries on
+            if let Err(e) = result {
+                renderer.print_error(e.as_ref());
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_command(&mut self, command: ChatCommand, renderer: &mut TerminalRenderer) -> Result<(), Box<dyn Error>> {
+        match command {
+            ChatCommand::Exit => {}
+            ChatCommand::Reset => {
+                self.reset();
+                renderer.print_notice("Started a new conversation");
+            }
+            ChatCommand::Model(None) => {
+                let models = self.models.as_ref().ok_or("Switching models is not available in this chat")?;
+                let list = models.available().iter()
+                    .map(|c_model| {
+                        let current = if c_model.id == models.current().id { "*" } else { " " };
+                        format!("{} {:<12} {}", current, c_model.id, c_model.name)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                renderer.print_notice(&list);
+            }
+            ChatCommand::Model(Some(id)) => {
+                let warning = self.switch_model(&id)?;
+                renderer.print_notice(&format!("Switched to {}", id));
+                if let Some(warning) = warning {
+                    renderer.print_notice(&warning);
+                }
+            }
+            ChatCommand::System(None) => {
+                match self.chat_log.first().filter(|entry| entry.message.role == Role::System) {
+                    Some(entry) => renderer.print_notice(&entry.message.content),
+                    None => renderer.print_notice("No system prompt is set"),
+                }
+            }
+            ChatCommand::System(Some(prompt)) => {
+                self.set_system_prompt(prompt);
+                renderer.print_notice("Updated the system prompt");
+                self.save_session()?;
+            }
+            ChatCommand::Set { key, value } => {
+                let models = self.models.as_mut().ok_or("Changing model settings is not available in this chat")?;
+                self.chat_model = with_retry_listener(models.set(&key, &value)?, &self.retry_listener);
+                let (context, warning) = ContextPolicy::from_config(&models.current().config);
+                self.context = context;
+                self.attachments = AttachmentBudget::from_config(&models.current().config);
+                self.usage.set_model(models.model_name());
+                renderer.print_notice(&format!("Set {} to {} for this chat", key, value));
+                if let Some(warning) = warning {
+                    renderer.print_notice(&warning);
+                }
+            }
+            ChatCommand::Save(path) => {
+                std::fs::write(&path, chat_log::transcript_markdown(&self.chat_log))
+                    .map_err(|e| format!("Failed to save {}: {}", path.display(), e))?;
+                renderer.print_notice(&format!("Saved the conversation to {}", path.display()));
+            }
+            ChatCommand::Export(path) => {
+                let exported = sessions::export(&self.session_snapshot(), ExportFormat::from_path(&path))?;
+                std::fs::write(&path, exported)
+                    .map_err(|e| format!("Failed to export {}: {}", path.display(), e))?;
+                renderer.print_notice(&format!("Exported the conversation to {}", path.display()));
+            }
+            ChatCommand::Undo => {
+                match self.undo() {
+                    Some(_) => renderer.print_notice("Removed the last question and its answer"),
+                    None => renderer.print_notice("Nothing to undo"),
+                }
+                self.save_session()?;
+            }
+            ChatCommand::Retry => {
+                let answer = self.last_answer_index().ok_or("There is no answer to regenerate")?;
+                let fork = self.branch_at(answer, None, renderer).await?;
+                self.print_branch_position(fork, renderer);
+            }
+            ChatCommand::Edit { number, text } => {
+                let question = self.question_index(number)?;
+                let text = match text {
+                    Some(text) => text,
+                    None => {
+                        let original = self.chat_log[question].message.content.clone();
+                        match self.get_input_with(&original).await? {
+                            Some(text) if !text.trim().is_empty() => text,
+                            _ => {
+                                renderer.print_notice("Edit cancelled");
+                                return Ok(());
+                            }
+                        }
+                    }
+                };
+                let fork = self.branch_at(question, Some(text), renderer).await?;
+                self.print_branch_position(fork, renderer);
+            }
+            ChatCommand::Branches => {
+                let forks = branches::forks(&self.chat_log);
+                if forks.is_empty() {
+                    renderer.print_notice("The conversation has no branches; /retry and /edit start them");
+                    return Ok(());
+                }
+                let list = forks.iter().enumerate()
+                    .map(|(number, index)| {
+                        let entry = &self.chat_log[*index];
+                        let (position, count) = branches::position(&self.chat_log, *index);
+                        let preview = entry.message.content.lines().next().unwrap_or_default();
+                        let preview = match preview.char_indices().nth(50) {
+                            Some((cut, _)) => format!("{}…", &preview[..cut]),
+                            None => preview.to_string(),
+                        };
+                        format!("{}. {}: {}  (branch {} of {})", number + 1, entry.speaker(), preview, position + 1, count)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                renderer.print_notice(&list);
+            }
+            ChatCommand::Previous(fork) | ChatCommand::Next(fork) => {
+                let forward = matches!(command, ChatCommand::Next(_));
+                let forks = branches::forks(&self.chat_log);
+                let index = match fork {
+                    Some(number) => forks.get(number.wrapping_sub(1)).copied()
+                        .ok_or_else(|| format!("There is no branch point {}; type /branches to list them", number))?,
+                    None => *forks.last().ok_or("The conversation has no branches; /retry and /edit start them")?,
+                };
+                let (position, count) = branches::position(&self.chat_log, index);
+                let position = match (forward, position) {
+                    (true, position) if position + 1 < count => position + 1,
+                    (false, position) if position > 0 => position - 1,
+                    (true, _) => return Err("Already on the last branch".into()),
+                    (false, _) => return Err("Already on the first branch".into()),
+                };
+                branches::switch(&mut self.chat_log, index, position)?;
+                self.save_session()?;
+                renderer.print_messages(&self.chat_log[index..]);
+                self.print_branch_position(index, renderer);
+            }
+            ChatCommand::Pin => {
+                match self.pin_last_exchange() {
+                    0 => renderer.print_notice("Nothing to pin yet"),
+                    count => renderer.print_notice(&format!("Pinned the last {} messages", count)),
+                }
+                self.save_session()?;
+            }
+            ChatCommand::Add(patterns) => {
+                let set = self.attach(&patterns)?;
+                renderer.print_attachments(&set);
+            }
+            ChatCommand::Code(None) => {
+                let blocks = code_blocks::last_answer_blocks(&self.chat_log);
+                if blocks.is_empty() {
+                    return Err("The last answer has no code blocks".into());
+                }
+                renderer.print_notice(&code_blocks::format_block_list(&blocks));
+            }
+            ChatCommand::Code(Some(number)) => {
+                let blocks = code_blocks::last_answer_blocks(&self.chat_log);
+                // Raw, without markdown rendering, so it can be selected and copied as-is
+                println!("{}", code_blocks::select_block(&blocks, Some(number))?.code);
+            }
+            ChatCommand::Copy(number) => {
+                let blocks = code_blocks::last_answer_blocks(&self.chat_log);
+                let block = code_blocks::select_block(&blocks, number)?;
+                let method = code_blocks::copy_block(block)?;
+                renderer.print_notice(&format!("Copied {} ({})", block.summary(), method));
+            }
+            ChatCommand::Write { number, path } => {
+                let blocks = code_blocks::last_answer_blocks(&self.chat_log);
+                let block = code_blocks::select_block(&blocks, number)?;
+                code_blocks::write_block(block, &path)?;
+                renderer.print_notice(&format!("Wrote {} to {}", block.summary(), path.display()));
+            }
+            ChatCommand::Help => {
+                let help = commands::COMMANDS.iter()
+                    .map(|spec| format!("{:<20} {}", spec.usage, spec.description))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                renderer.print_notice(&help);
             }
-            self.handle_response(input, renderer).await?;
         }
         Ok(())
     }
 
-    pub async fn get_input(&mut self, renderer: &mut TerminalRenderer) -> Result<String, Box<dyn Error>> {
-        renderer.render_user("You");
-        get_user_input().await
+    /// Clears the conversation, keeping the system prompt, and continues in a
+    /// new session so the old one stays as it was.
+    pub fn reset(&mut self) {
+        self.chat_log.retain(|entry| entry.message.role == Role::System && !entry.summary);
+        if let Some((store, session)) = &mut self.session {
+            *session = store.create(session.model_id.clone());
+            self.usage.start_session(session.id.clone());
+        }
+    }
+
+    /// Continues the chat with the configured model `id`. Returns a warning
+    /// about its config for the caller to show.
+    pub fn switch_model(&mut self, id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let models = self.models.as_mut().ok_or("Switching models is not available in this chat")?;
+        self.chat_model = with_retry_listener(models.select(id)?, &self.retry_listener);
+        let (context, warning) = ContextPolicy::from_config(&models.current().config);
+        self.context = context;
+        self.attachments = AttachmentBudget::from_config(&models.current().config);
+        self.usage.set_model(models.model_name());
+        if let Some((_, session)) = &mut self.session {
+            session.model_id = id.to_string();
+        }
+        self.save_session()?;
+        Ok(warning)
+    }
+
+    /// Replaces the conversation with a saved session, carrying on with its
+    /// model when it is still configured.
+    pub fn open_session(&mut self, session: Session) -> Result<Option<String>, Box<dyn Error>> {
+        let model_switch = match &self.models {
+            Some(models) if models.current().id != session.model_id
+                && models.available().iter().any(|c_model| c_model.id == session.model_id) => Some(session.model_id.clone()),
+            _ => None,
+        };
+        self.chat_log = session.messages.clone();
+        self.usage.continue_session(session.id.clone(), session.usage.clone());
+        if let Some((_, current)) = &mut self.session {
+            *current = session;
+        }
+        match model_switch {
+            Some(id) => self.switch_model(&id),
+            None => Ok(None),
+        }
+    }
+
+    pub fn chat_log(&self) -> &[ChatLogEntry] {
+        &self.chat_log
+    }
+
+    pub fn usage_totals(&self) -> &UsageTotals {
+        self.usage.session()
+    }
+
+    /// The configured id of the model being chatted with, when models can be switched.
+    pub fn model_id(&self) -> Option<&str> {
+        self.models.as_ref().map(|models| models.current().id.as_str())
+    }
+
+    /// The ids of the configured models `/model` can switch to.
+    pub fn model_ids(&self) -> Vec<&str> {
+        self.models.as_ref()
+            .map(|models| models.available().iter().map(|c_model| c_model.id.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session.as_ref().map(|(_, session)| session.id.as_str())
+    }
+
+    /// Removes the most recent question and everything after it, switching
+    /// to a sibling branch when the question had been edited.
+    pub fn undo(&mut self) -> Option<Vec<ChatLogEntry>> {
+        let start = self.question_index(None).ok()?;
+        Some(branches::remove(&mut self.chat_log, start))
+    }
+
+    /// The index of the user's `number`th message, counting from 1, or of the last one.
+    fn question_index(&self, number: Option<usize>) -> Result<usize, Box<dyn Error>> {
+        let questions = self.chat_log.iter().enumerate()
+            .filter(|(_, entry)| entry.message.role == Role::User && !entry.attachment)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let question = match number {
+            Some(number) => questions.get(number.wrapping_sub(1)),
+            None => questions.last(),
+        };
+        question.copied().ok_or_else(|| match number {
+            Some(number) => format!("There is no message {}; you have sent {}", number, questions.len()).into(),
+            None => "There is no question yet".into(),
+        })
+    }
+
+    /// The index of the answer to the last question, if it has one.
+    fn last_answer_index(&self) -> Option<usize> {
+        let answer = self.question_index(None).ok()? + 1;
+        match self.chat_log.get(answer) {
+            Some(entry) if entry.message.role == Role::Assistant => Some(answer),
+            _ => None,
+        }
+    }
+
+    /// Starts a new branch at `index`, keeping what was there as a sibling
+    /// branch. The branch starts with `question`, or with a new answer to the
+    /// question before `index` when there is none. Returns where the new
+    /// branch starts, which is earlier than `index` when older turns were
+    /// summarized. The conversation is left as it was if the request fails.
+    async fn branch_at(&mut self, index: usize, question: Option<String>, renderer: &mut TerminalRenderer) -> Result<usize, Box<dyn Error>> {
+        let (position, _) = branches::position(&self.chat_log, index);
+        let previous = branches::split(&mut self.chat_log, index);
+        // Summarizing can shorten the log before the fork, so the new branch
+        // is found from the end: the question, if any, and its answer
+        let added = if question.is_some() { 2 } else { 1 };
+        let result = match question {
+            Some(question) => self.handle_response(question, renderer).await,
+            None => self.respond(renderer).await,
+        };
+        match result {
+            Ok(_) => {
+                let fork = self.chat_log.len() - added;
+                branches::add(&mut self.chat_log, fork, previous);
+                self.save_session()?;
+                Ok(fork)
+            }
+            // A failed request adds nothing, so the old branch goes back at the end
+            Err(e) => {
+                branches::join(&mut self.chat_log, previous, position);
+                Err(e)
+            }
+        }
+    }
+
+    fn print_branch_position(&self, index: usize, renderer: &mut TerminalRenderer) {
+        let (position, count) = branches::position(&self.chat_log, index);
+        if count > 1 {
+            renderer.print_notice(&format!("Branch {} of {}; /prev and /next switch between them", position + 1, count));
+        }
+    }
+
+    pub async fn get_input(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        self.get_input_with("").await
+    }
+
+    /// Reads a line that starts out as `initial`, for editing an earlier message.
+    async fn get_input_with(&mut self, initial: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let mut editor = match self.editor.take() {
+            Some(editor) => editor,
+            None => LineEditor::new()?,
+        };
+        // The read blocks, so it runs on its own thread. That is a plain thread
+        // rather than the blocking pool, which the runtime waits on at exit.
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let initial = initial.to_string();
+        std::thread::spawn(move || {
+            let input = editor.read_with("You: ", &initial).map_err(|e| e.to_string());
+            sender.send((editor, input)).ok();
+        });
+        // A terminal reports Ctrl-C as a key. Otherwise the signal handler that
+        // cancels responses catches it, and it ends the chat like Ctrl-D.
+        tokio::select! {
+            read = receiver => {
+                let (editor, input) = read?;
+                self.editor = Some(editor);
+                Ok(input?)
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Pins the most recent question and its answer so they survive context
+    /// trimming, returning how many messages were pinned.
+    pub fn pin_last_exchange(&mut self) -> usize {
+        let start = match self.chat_log.iter().rposition(|entry| entry.message.role == Role::User && !entry.attachment) {
+            Some(start) => start,
+            None => return 0,
+        };
+        for entry in &mut self.chat_log[start..] {
+            entry.pinned = true;
+        }
+        self.chat_log.len() - start
+    }
+
+    /// Builds the request for the current conversation, trimming it to the
+    /// context policy first. A failed summary is added to `warnings`.
+    async fn request(&mut self, warnings: &mut Vec<Box<dyn Error>>) -> ChatModelRequest {
+        if self.context.strategy == ContextStrategy::Summarize && !self.context.fits(&self.chat_log) {
+            if let Err(e) = self.summarize_older_turns().await {
+                // Trimming below still keeps the request within the context window
+                warnings.push(format!("failed to summarize earlier turns, dropping them instead: {}", e).into());
+            }
+        }
+        ChatModelRequest {
+            messages: self.context.select(&self.chat_log)
+                .into_iter()
+                .map(|index| self.chat_log[index].message.clone())
+                .collect(),
+        }
+    }
+
+    /// Replaces the turns that no longer fit the context window with a
+    /// summary written by the model.
+    async fn summarize_older_turns(&mut self) -> Result<(), Box<dyn Error>> {
+        let indices = self.context.summarizable(&self.chat_log);
+        let first = match indices.first() {
+            Some(first) => *first,
+            None => return Ok(()),
+        };
+        let transcript = indices.iter()
+            .map(|index| {
+                let message = &self.chat_log[*index].message;
+                let speaker = match message.role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                    Role::System => "earlier summary",
+                };
+                format!("{}: {}", speaker, message.content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let request = ChatModelRequest {
+            messages: vec![
+                ChatCompletionRequestMessage { role: Role::System, content: SUMMARY_PROMPT.to_string(), name: None },
+                ChatCompletionRequestMessage { role: Role::User, content: transcript, name: None },
+            ],
+        };
+
+        let mut stream = self.chat_model.create_response_stream(&request).await?;
+        let mut summary = String::new();
+        while let Some(chunk) = stream.next().await {
+            for choice in chunk?.choices {
+                if let Some(content) = choice.delta.content {
+                    summary.push_str(&content);
+                }
+            }
+        }
+
+        let summary_entry = ChatLogEntry {
+            summary: true,
+            ..ChatLogEntry::new(Role::System, format!("{}:\n{}", SUMMARY_HEADING, summary.trim()))
+        };
+        for index in indices.iter().rev() {
+            self.chat_log.remove(*index);
+        }
+        self.chat_log.insert(first, summary_entry);
+        Ok(())
     }
 
     pub async fn handle_response(&mut self, user_input: String, renderer: &mut TerminalRenderer) -> Result<String, Box<dyn Error>> {
-        let user_message = ChatCompletionRequestMessage {
-            content: user_input,
-            role: Role::User,
-            name: None
+        self.push_question(user_input);
+        let response = self.respond(renderer).await;
+        if response.is_err() {
+            // Drop the unanswered message so it is not sent twice on the next turn
+            self.discard_question();
+        }
+        response
+    }
+
+    /// Adds the user's message to the conversation.
+    pub fn push_question(&mut self, question: String) {
+        self.chat_log.push(ChatLogEntry::new(Role::User, question));
+    }
+
+    /// Removes the question whose request failed. It is still the last
+    /// message, though summarizing may have moved it up the log.
+    pub fn discard_question(&mut self) {
+        if self.chat_log.last().is_some_and(|entry| entry.message.role == Role::User && !entry.attachment) {
+            self.chat_log.pop();
+        }
+    }
+
+    /// Answers the conversation so far, adding the answer to it.
+    async fn respond(&mut self, renderer: &mut TerminalRenderer) -> Result<String, Box<dyn Error>> {
+        let mut warnings = Vec::new();
+        // Ctrl-C before the answer starts, such as during retries or a summary,
+        // cancels the request like a failed one
+        let opened = tokio::select! {
+            opened = self.open_stream(&mut warnings) => Some(opened),
+            _ = tokio::signal::ctrl_c() => None,
         };
-        self.chat_log.push(user_message);
-        let client_request = ChatModelRequest { messages: self.chat_log.clone() };
+        for warning in warnings.drain(..) {
+            renderer.print_error(warning.as_ref());
+        }
+        let (client_request, stream) = opened.ok_or("Request cancelled")??;
 
+        // Delegate to renderer to process the stream
+        let response = renderer.render_stream(stream).await?;
+
+        match self.finish_response(&client_request, &response, &mut warnings) {
+            Some(record) => renderer.print_usage(&record, self.usage.session()),
+            None => {
+                for warning in warnings.drain(..) {
+                    renderer.print_error(warning.as_ref());
+                }
+                println!();
+            }
+        }
+        for warning in warnings {
+            renderer.print_error(warning.as_ref());
+        }
+        Ok(response.content)
+    }
+
+    /// Sends the conversation to the model, returning the request along with
+    /// the response stream. Problems that don't stop the request are added to `warnings`.
+    pub async fn open_stream(&mut self, warnings: &mut Vec<Box<dyn Error>>) -> Result<(ChatModelRequest, ChatCompletionStream), Box<dyn Error>> {
+        let client_request = self.request(warnings).await;
         let stream = self.chat_model.create_response_stream(&client_request).await?;
+        Ok((client_request, stream))
+    }
 
-        // Delegate to renderer to process the stream
-        let response_string = renderer.render_stream(stream).await?;
+    /// Records the usage of a streamed response and adds it to the conversation,
+    /// returning the usage unless recording it failed. Failures to record
+    /// usage or save the session are added to `warnings`.
+    pub fn finish_response(&mut self, request: &ChatModelRequest, response: &RenderedResponse, warnings: &mut Vec<Box<dyn Error>>) -> Option<UsageRecord> {
+        let usage = self.usage.record(
+            response.model.as_deref(),
+            response.usage.clone(),
+            &request.messages,
+            &response.content,
+        );
+        let record = match usage {
+            Ok(record) => Some(record),
+            Err(e) => {
+                warnings.push(format!("failed to record token usage: {}", e).into());
+                None
+            }
+        };
+
+        let mut response_entry = ChatLogEntry::new(Role::Assistant, response.content.clone());
+        response_entry.interrupted = response.interrupted;
+        response_entry.model = response.model.clone();
+        self.chat_log.push(response_entry);
+
+        if let Err(e) = self.save_session() {
+            warnings.push(format!("failed to save the session: {}", e).into());
+        }
+        record
+    }
+}
+
+fn with_retry_listener(mut chat_model: Box<dyn ChatModel>, listener: &Option<RetryListener>) -> Box<dyn ChatModel> {
+    if let Some(listener) = listener {
+        chat_model.set_retry_listener(listener.clone());
+    }
+    chat_model
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::ai::chat_types::{ChatCompletionChoice, ChatCompletionChunk, ChatCompletionDelta, ChatCompletionStream};
+    use crate::replay::fixture::{request_hash, FixtureEntry, FixtureWriter};
+    use crate::replay::ReplayModel;
+
+    fn chunk(content: &str) -> ChatCompletionChunk {
+        ChatCompletionChunk {
+            id: Some(String::from("fixture")),
+            object: String::from("chat.completion.chunk"),
+            created: 0,
+            model: String::from("replay"),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                delta: ChatCompletionDelta { content: Some(content.to_string()), role: Some(Role::Assistant) },
+                finish_reason: None,
+            }],
+            usage: None,
+        }
+    }
 
-        let response_message = ChatCompletionRequestMessage {
-            content: response_string.clone(),
-            role: Role::Assistant,
-            name: None
+    #[tokio::test]
+    async fn test_handle_response_with_replayed_model() {
+        let directory = tempfile::tempdir().expect("temp dir");
+        let path = directory.path().join("chat.jsonl");
+        let request = ChatModelRequest {
+            messages: vec![ChatCompletionRequestMessage { role: Role::User, content: "Hello".to_string(), name: None }],
         };
-        self.chat_log.push(response_message);
+        let mut writer = FixtureWriter::open(&path).expect("fixture");
+        for (sequence, content) in ["Hi, ", "how can I help?"].iter().enumerate() {
+            writer.append(&FixtureEntry { request: request_hash(&request), sequence: sequence as u32, chunk: chunk(content) })
+                .expect("append");
+        }
+
+        let model = ReplayModel::new(serde_json::json!({ "fixture": path.to_string_lossy() }));
+        let mut client = ChatClient::new(Box::new(model), UsageTracker::in_memory(String::from("replay"), Default::default()));
+        let mut renderer = TerminalRenderer::new();
+
+        let response = client.handle_response("Hello".to_string(), &mut renderer).await.expect("response");
+        assert_eq!(response, "Hi, how can I help?");
+        assert_eq!(client.chat_log.len(), 2);
+        assert_eq!(client.chat_log[1].message.role, Role::Assistant);
+        assert_eq!(client.chat_log[1].message.content, "Hi, how can I help?");
+        assert!(!client.chat_log[1].interrupted);
+        // The fixture has no usage data, so the tokens were estimated locally
+        assert_eq!(client.usage.session().requests, 1);
+        assert_eq!(client.usage.session().estimated_requests, 1);
+
+        // The follow-up turn was never recorded, so replay reports it instead of guessing
+        assert!(client.handle_response("And then?".to_string(), &mut renderer).await.is_err());
+        assert_eq!(client.chat_log.len(), 2);
+    }
+
+    /// Answers every request with the same text and remembers what it was sent.
+    struct CannedModel {
+        reply: String,
+        requests: std::sync::Arc<std::sync::Mutex<Vec<ChatModelRequest>>>,
+    }
+
+    #[async_trait]
+    impl ChatModel for CannedModel {
+        fn new(_: serde_json::Value) -> Self {
+            CannedModel { reply: String::new(), requests: Default::default() }
+        }
+
+        async fn create_response_stream(&mut self, request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+            self.requests.lock().unwrap().push(request.clone());
+            Ok(futures::stream::iter(vec![chunk(&self.reply)]).map(Ok).boxed())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_older_turns_are_summarized_to_fit_context() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let model = CannedModel { reply: String::from("ok"), requests: requests.clone() };
+        let mut client = ChatClient::new(Box::new(model), UsageTracker::in_memory(String::from("canned"), Default::default()));
+        client.set_system_prompt(String::from("be brief"));
+        client.set_context_policy(ContextPolicy::from_config(&serde_json::json!({
+            "max_context_tokens": 40,
+            "context_strategy": "summarize",
+        })).0);
+        let mut renderer = TerminalRenderer::new();
+
+        for turn in 0..4 {
+            client.handle_response(format!("question number {}", turn), &mut renderer).await.expect("response");
+        }
+
+        let requests = requests.lock().unwrap();
+        let summary_request = requests.iter()
+            .find(|request| request.messages[0].content == SUMMARY_PROMPT)
+            .expect("summary request");
+        assert!(summary_request.messages[1].content.starts_with("user: question number 0\n\nassistant: ok"));
+
+        assert_eq!(client.chat_log[0].message.content, "be brief");
+        assert!(client.chat_log[1].summary);
+        assert_eq!(client.chat_log[1].message.content, "Summary of the earlier conversation:\nok");
+        assert!(client.context.fits(&client.chat_log[..client.chat_log.len() - 1]));
+    }
+
+    #[tokio::test]
+    async fn test_resumed_session_is_saved_after_each_turn() {
+        let directory = tempfile::tempdir().expect("temp dir");
+        let store = SessionStore::new(directory.path().to_path_buf());
+        let mut session = Session::new(String::from("canned"));
+        session.messages = vec![
+            ChatLogEntry::new(Role::User, String::from("first question")),
+            ChatLogEntry::new(Role::Assistant, String::from("first answer")),
+        ];
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let model = CannedModel { reply: String::from("second answer"), requests: requests.clone() };
+        let mut client = ChatClient::new(Box::new(model), UsageTracker::in_memory(String::from("canned"), Default::default()));
+        client.set_session(SessionStore::new(directory.path().to_path_buf()), session.clone());
+
+        client.handle_response(String::from("second question"), &mut TerminalRenderer::new()).await.expect("response");
+
+        // The resumed messages are sent as context for the new question
+        assert_eq!(requests.lock().unwrap()[0].messages.len(), 3);
+        let saved = store.load(&session.id).expect("saved session");
+        assert_eq!(saved.messages.len(), 4);
+        assert_eq!(saved.messages[3].message.content, "second answer");
+        assert_eq!(saved.title.as_deref(), Some("first question"));
+        assert_eq!(saved.usage.requests, 1);
+        assert_eq!(client.saved_session_id(), Some(session.id.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_commands_edit_the_conversation() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let model = CannedModel { reply: String::from("answer"), requests: requests.clone() };
+        let mut client = ChatClient::new(Box::new(model), UsageTracker::in_memory(String::from("canned"), Default::default()));
+        let mut renderer = TerminalRenderer::new();
+        client.run_command(ChatCommand::System(Some(String::from("be brief"))), &mut renderer).await.expect("system");
+        client.handle_response(String::from("first"), &mut renderer).await.expect("response");
+        client.handle_response(String::from("second"), &mut renderer).await.expect("response");
+
+        client.run_command(ChatCommand::Retry, &mut renderer).await.expect("retry");
+        assert_eq!(client.chat_log.len(), 5);
+        let retried = requests.lock().unwrap().last().cloned().expect("retried request");
+        assert_eq!(retried.messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), vec!["be brief", "first", "answer", "second"]);
 
-        Ok(response_string)
+        client.run_command(ChatCommand::Undo, &mut renderer).await.expect("undo");
+        assert_eq!(client.chat_log.last().map(|entry| entry.message.content.as_str()), Some("answer"));
+        assert_eq!(client.chat_log.len(), 3);
+
+        let directory = tempfile::tempdir().expect("temp dir");
+        let path = directory.path().join("chat.md");
+        client.run_command(ChatCommand::Save(path.clone()), &mut renderer).await.expect("save");
+        let transcript = std::fs::read_to_string(&path).expect("transcript");
+        assert_eq!(transcript, "## System\n\nbe brief\n\n## You\n\nfirst\n\n## AI\n\nanswer\n");
+        let path = directory.path().join("chat.json");
+        client.run_command(ChatCommand::Export(path.clone()), &mut renderer).await.expect("export");
+        let exported: Session = serde_json::from_str(&std::fs::read_to_string(&path).expect("export")).expect("session json");
+        assert_eq!(exported.messages, client.chat_log);
+        assert_eq!(exported.title.as_deref(), Some("first"));
+
+        client.run_command(ChatCommand::Reset, &mut renderer).await.expect("reset");
+        assert_eq!(client.chat_log.len(), 1);
+        assert!(client.run_command(ChatCommand::Model(Some(String::from("claude"))), &mut renderer).await.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_attached_files_are_sent_and_kept_by_undo() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let model = CannedModel { reply: String::from("answer"), requests: requests.clone() };
+        let mut client = ChatClient::new(Box::new(model), UsageTracker::in_memory(String::from("canned"), Default::default()));
+        let mut renderer = TerminalRenderer::new();
+        let directory = tempfile::tempdir().expect("temp dir");
+        let path = directory.path().join("main.rs");
+        std::fs::write(&path, "fn main() {}\n").expect("source file");
+
+        client.run_command(ChatCommand::Add(vec![path.to_string_lossy().to_string()]), &mut renderer).await.expect("add");
+        client.handle_response(String::from("what does it do?"), &mut renderer).await.expect("response");
+        let request = requests.lock().unwrap().last().cloned().expect("request");
+        assert!(request.messages[0].content.ends_with("main.rs\n```rs\nfn main() {}\n```\n"));
+        assert!(client.chat_log[0].pinned && client.chat_log[0].attachment);
+
+        client.run_command(ChatCommand::Undo, &mut renderer).await.expect("undo");
+        assert_eq!(client.chat_log.len(), 1);
+        assert!(client.undo().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_edits_and_retries_keep_branches() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let model = CannedModel { reply: String::from("answer"), requests: requests.clone() };
+        let mut client = ChatClient::new(Box::new(model), UsageTracker::in_memory(String::from("canned"), Default::default()));
+        let mut renderer = TerminalRenderer::new();
+        client.handle_response(String::from("first"), &mut renderer).await.expect("response");
+        client.handle_response(String::from("second"), &mut renderer).await.expect("response");
+
+        let edit = ChatCommand::Edit { number: Some(1), text: Some(String::from("first, edited")) };
+        client.run_command(edit, &mut renderer).await.expect("edit");
+        let edited = requests.lock().unwrap().last().cloned().expect("edited request");
+        assert_eq!(edited.messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), vec!["first, edited"]);
+        assert_eq!(client.chat_log.len(), 2);
+        assert_eq!(branches::position(&client.chat_log, 0), (1, 2));
+
+        client.run_command(ChatCommand::Retry, &mut renderer).await.expect("retry");
+        assert_eq!(branches::forks(&client.chat_log), vec![0, 1]);
+
+        client.run_command(ChatCommand::Previous(Some(1)), &mut renderer).await.expect("previous");
+        let contents = client.chat_log.iter().map(|entry| entry.message.content.as_str()).collect::<Vec<_>>();
+        assert_eq!(contents, vec!["first", "answer", "second", "answer"]);
+        assert!(client.run_command(ChatCommand::Previous(None), &mut renderer).await.is_err());
+        client.run_command(ChatCommand::Next(None), &mut renderer).await.expect("next");
+        assert_eq!(client.chat_log[0].message.content, "first, edited");
+        assert_eq!(client.chat_log[1].branches.len(), 1);
+    }
+
+    /// Writes summaries but fails every other request.
+    struct SummaryOnlyModel;
+
+    #[async_trait]
+    impl ChatModel for SummaryOnlyModel {
+        fn new(_: serde_json::Value) -> Self {
+            SummaryOnlyModel
+        }
+
+        async fn create_response_stream(&mut self, request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+            if request.messages[0].content != SUMMARY_PROMPT {
+                return Err("the model is unavailable".into());
+            }
+            Ok(futures::stream::iter(vec![chunk("earlier")]).map(Ok).boxed())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_branches_survive_summarizing() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let model = CannedModel { reply: String::from("ok"), requests: requests.clone() };
+        let mut client = ChatClient::new(Box::new(model), UsageTracker::in_memory(String::from("canned"), Default::default()));
+        client.set_context_policy(ContextPolicy::from_config(&serde_json::json!({
+            "max_context_tokens": 60,
+            "context_strategy": "summarize",
+        })).0);
+        let mut renderer = TerminalRenderer::new();
+        for turn in 0..3 {
+            client.handle_response(format!("question number {}", turn), &mut renderer).await.expect("response");
+        }
+        let summaries = |requests: &[ChatModelRequest]| requests.iter().filter(|request| request.messages[0].content == SUMMARY_PROMPT).count();
+        let before = summaries(&requests.lock().unwrap());
+
+        // The long edit only fits once older turns are summarized, which moves the fork up
+        let long = "please explain this again in much more detail than before ".repeat(3);
+        let edit = ChatCommand::Edit { number: None, text: Some(long.clone()) };
+        client.run_command(edit, &mut renderer).await.expect("edit");
+        assert!(summaries(&requests.lock().unwrap()) > before);
+        let fork = client.chat_log.len() - 2;
+        assert_eq!(client.chat_log[fork].message.content, long);
+        assert_eq!(branches::position(&client.chat_log, fork), (1, 2));
+        assert_eq!(client.chat_log[fork].branches[0][0].message.content, "question number 2");
+
+        // Later summaries leave the fork alone
+        client.handle_response(long.clone(), &mut renderer).await.expect("response");
+        assert_eq!(branches::forks(&client.chat_log), vec![fork]);
+
+        // A failed request, after summarizing again, only leaves the new summary
+        client.chat_model = Box::new(SummaryOnlyModel);
+        let log = client.chat_log.clone();
+        assert!(client.run_command(ChatCommand::Retry, &mut renderer).await.is_err());
+        assert!(client.handle_response(long.clone(), &mut renderer).await.is_err());
+        assert_eq!(client.chat_log[0].message.content, "Summary of the earlier conversation:\nearlier");
+        assert_eq!(client.chat_log[1..], log[1..]);
+    }
+}
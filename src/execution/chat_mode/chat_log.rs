@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ai::chat_types::{ChatCompletionRequestMessage, Role};
+
+/// Opens the system note that replaces summarized turns.
+pub const SUMMARY_HEADING: &str = "Summary of the earlier conversation";
+
+/// A message in the conversation along with what the chat client knows about it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChatLogEntry {
+    pub message: ChatCompletionRequestMessage,
+    /// The response was cancelled before it finished streaming.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// Pinned messages are kept when older turns are trimmed to fit the context window.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// A system note summarizing turns that no longer fit the context window.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub summary: bool,
+    /// File contents attached with `--file` or `/add`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub attachment: bool,
+    /// RFC 3339 time the message was added; missing from sessions saved before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// The model the provider reported writing a response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The other branches of the conversation that start at this message, each
+    /// running to its own last message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<Vec<ChatLogEntry>>,
+    /// Where this message's branch falls among `branches`, counting from 0.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub branch: usize,
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+impl ChatLogEntry {
+    pub fn new(role: Role, content: String) -> ChatLogEntry {
+        ChatLogEntry {
+            message: ChatCompletionRequestMessage {
+                role,
+                content,
+                name: None,
+            },
+            interrupted: false,
+            pinned: false,
+            summary: false,
+            attachment: false,
+            created_at: Some(chrono::Local::now().to_rfc3339()),
+            model: None,
+            branches: Vec::new(),
+            branch: 0,
+        }
+    }
+
+    /// Who the message is from, as shown in transcripts.
+    pub fn speaker(&self) -> &'static str {
+        match self.message.role {
+            Role::System if self.summary => SUMMARY_HEADING,
+            Role::System => "System",
+            Role::User if self.attachment => "Attached files",
+            Role::User => "You",
+            Role::Assistant => "AI",
+        }
+    }
+
+    /// The message as shown in transcripts, without the heading of a summary.
+    pub fn display_content(&self) -> &str {
+        let content = self.message.content.trim();
+        match content.strip_prefix(SUMMARY_HEADING) {
+            Some(summary) if self.summary => summary.trim_start_matches(':').trim(),
+            _ => content,
+        }
+    }
+}
+
+/// Renders the conversation as a Markdown transcript.
+pub fn transcript_markdown(entries: &[ChatLogEntry]) -> String {
+    entries.iter()
+        .map(|entry| {
+            let interrupted = if entry.interrupted { "\n\n*(interrupted)*" } else { "" };
+            format!("## {}\n\n{}{}\n", entry.speaker(), entry.display_content(), interrupted)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
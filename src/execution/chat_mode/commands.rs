@@ -0,0 +1,190 @@
+use std::path::PathBuf;
+
+/// A command typed at the chat prompt instead of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatCommand {
+    Exit,
+    Reset,
+    Model(Option<String>),
+    System(Option<String>),
+    Set { key: String, value: String },
+    Save(PathBuf),
+    Undo,
+    Retry,
+    Edit { number: Option<usize>, text: Option<String> },
+    Branches,
+    Previous(Option<usize>),
+    Next(Option<usize>),
+    Pin,
+    Export(PathBuf),
+    Add(Vec<String>),
+    Code(Option<usize>),
+    Copy(Option<usize>),
+    Write { number: Option<usize>, path: PathBuf },
+    Help,
+}
+
+pub struct CommandSpec {
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec { usage: "/exit", description: "End the chat" },
+    CommandSpec { usage: "/reset", description: "Start a new conversation, keeping the system prompt" },
+    CommandSpec { usage: "/model [id]", description: "Switch to another configured model, or list them" },
+    CommandSpec { usage: "/system [text]", description: "Replace the system prompt, or show it" },
+    CommandSpec { usage: "/set <key> <value>", description: "Change a model setting for this chat, e.g. /set temperature 0.2" },
+    CommandSpec { usage: "/save <file>", description: "Save the conversation as a Markdown transcript" },
+    CommandSpec { usage: "/export <file>", description: "Export the conversation as .md, .json or .html, by extension" },
+    CommandSpec { usage: "/undo", description: "Remove the last question and its answer" },
+    CommandSpec { usage: "/retry", description: "Regenerate the last answer, keeping the previous one as a branch" },
+    CommandSpec { usage: "/edit [n] [text]", description: "Edit your nth message (the last by default) and continue from it on a new branch" },
+    CommandSpec { usage: "/branches", description: "List the places where the conversation branches" },
+    CommandSpec { usage: "/prev [n]", description: "Switch to the previous branch at branch point n (the last by default)" },
+    CommandSpec { usage: "/next [n]", description: "Switch to the next branch at branch point n (the last by default)" },
+    CommandSpec { usage: "/pin", description: "Keep the last question and answer when trimming the context" },
+    CommandSpec { usage: "/add <path|glob>...", description: "Attach files or directories to the conversation" },
+    CommandSpec { usage: "/code [n]", description: "List the code blocks in the last answer, or print block n" },
+    CommandSpec { usage: "/copy [n]", description: "Copy a code block from the last answer to the clipboard" },
+    CommandSpec { usage: "/write [n] <file>", description: "Write a code block from the last answer to a file" },
+    CommandSpec { usage: "/help", description: "Show this list" },
+];
+
+/// What the chat loop should do with a line of input.
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Message(String),
+    Command(ChatCommand),
+    Invalid(String),
+}
+
+/// Parses a line typed at the prompt. Only a leading `/word` is a command, so
+/// messages starting with a path such as `/usr/bin` are sent as typed, and a
+/// leading `//` sends a message that starts with a slash.
+pub fn parse_input(input: &str) -> Input {
+    let trimmed = input.trim();
+    if let Some(message) = trimmed.strip_prefix("//") {
+        return Input::Message(format!("/{}", message));
+    }
+    let line = match trimmed.strip_prefix('/') {
+        Some(line) => line,
+        None => return Input::Message(input.to_string()),
+    };
+    let (name, argument) = match line.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (line, ""),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Input::Message(input.to_string());
+    }
+    let argument = if argument.is_empty() { None } else { Some(argument.to_string()) };
+
+    let command = match (name, argument) {
+        ("exit" | "quit", None) => ChatCommand::Exit,
+        ("reset", None) => ChatCommand::Reset,
+        ("model", argument) => ChatCommand::Model(argument),
+        ("system", argument) => ChatCommand::System(argument),
+        ("set", Some(argument)) => match argument.split_once(char::is_whitespace) {
+            Some((key, value)) => ChatCommand::Set { key: key.to_string(), value: value.trim().to_string() },
+            None => return Input::Invalid(String::from("Usage: /set <key> <value>")),
+        },
+        ("save", Some(path)) => ChatCommand::Save(PathBuf::from(path)),
+        ("export", Some(path)) => ChatCommand::Export(PathBuf::from(path)),
+        ("undo", None) => ChatCommand::Undo,
+        ("retry" | "regenerate", None) => ChatCommand::Retry,
+        ("edit", None) => ChatCommand::Edit { number: None, text: None },
+        ("edit", Some(argument)) => {
+            let (first, rest) = argument.split_once(char::is_whitespace).unwrap_or((argument.as_str(), ""));
+            match first.parse::<usize>() {
+                Ok(number) => ChatCommand::Edit {
+                    number: Some(number),
+                    text: Some(rest.trim().to_string()).filter(|text| !text.is_empty()),
+                },
+                Err(_) => ChatCommand::Edit { number: None, text: Some(argument) },
+            }
+        }
+        ("branches", None) => ChatCommand::Branches,
+        ("prev" | "next", argument) => match argument.map(|fork| fork.parse::<usize>()).transpose() {
+            Ok(fork) if name == "prev" => ChatCommand::Previous(fork),
+            Ok(fork) => ChatCommand::Next(fork),
+            Err(_) => return Input::Invalid(format!("Usage: /{} [n]", name)),
+        },
+        ("pin", None) => ChatCommand::Pin,
+        ("add", Some(paths)) => ChatCommand::Add(paths.split_whitespace().map(String::from).collect()),
+        ("code", None) => ChatCommand::Code(None),
+        ("copy", None) => ChatCommand::Copy(None),
+        ("code" | "copy", Some(number)) => match number.parse::<usize>() {
+            Ok(number) if name == "code" => ChatCommand::Code(Some(number)),
+            Ok(number) => ChatCommand::Copy(Some(number)),
+            Err(_) => return Input::Invalid(format!("Usage: /{} [n]", name)),
+        },
+        ("write", Some(argument)) => match argument.split_once(char::is_whitespace) {
+            Some((number, path)) if number.parse::<usize>().is_ok() => ChatCommand::Write {
+                number: number.parse().ok(),
+                path: PathBuf::from(path.trim()),
+            },
+            _ => ChatCommand::Write { number: None, path: PathBuf::from(argument) },
+        },
+        ("help", _) => ChatCommand::Help,
+        (name, _) => {
+            return match COMMANDS.iter().find(|spec| spec.usage[1..].split(' ').next() == Some(name)) {
+                Some(spec) => Input::Invalid(format!("Usage: {}", spec.usage)),
+                None => Input::Invalid(format!("Unknown command /{}; type /help for a list, or start with // to send it as a message", name)),
+            }
+        }
+    };
+    Input::Command(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_commands_and_messages() {
+        assert_eq!(parse_input("stop\n"), Input::Message(String::from("stop\n")));
+        assert_eq!(parse_input("/exit"), Input::Command(ChatCommand::Exit));
+        assert_eq!(parse_input("  /model claude \n"), Input::Command(ChatCommand::Model(Some(String::from("claude")))));
+        assert_eq!(parse_input("/model"), Input::Command(ChatCommand::Model(None)));
+        assert_eq!(
+            parse_input("/set temperature 0.2"),
+            Input::Command(ChatCommand::Set { key: String::from("temperature"), value: String::from("0.2") })
+        );
+        assert_eq!(
+            parse_input("/system You are terse.  Answer in one line."),
+            Input::Command(ChatCommand::System(Some(String::from("You are terse.  Answer in one line."))))
+        );
+        assert_eq!(parse_input("/save notes/chat.md"), Input::Command(ChatCommand::Save(PathBuf::from("notes/chat.md"))));
+        assert_eq!(
+            parse_input("/add src/main.rs src/**/*.toml"),
+            Input::Command(ChatCommand::Add(vec![String::from("src/main.rs"), String::from("src/**/*.toml")]))
+        );
+        assert_eq!(parse_input("/export chat.html"), Input::Command(ChatCommand::Export(PathBuf::from("chat.html"))));
+        assert_eq!(parse_input("/edit"), Input::Command(ChatCommand::Edit { number: None, text: None }));
+        assert_eq!(
+            parse_input("/edit 2 use tokio instead"),
+            Input::Command(ChatCommand::Edit { number: Some(2), text: Some(String::from("use tokio instead")) })
+        );
+        assert_eq!(
+            parse_input("/edit use tokio"),
+            Input::Command(ChatCommand::Edit { number: None, text: Some(String::from("use tokio")) })
+        );
+        assert_eq!(parse_input("/regenerate"), Input::Command(ChatCommand::Retry));
+        assert_eq!(parse_input("/prev 2"), Input::Command(ChatCommand::Previous(Some(2))));
+        assert_eq!(parse_input("/next"), Input::Command(ChatCommand::Next(None)));
+        assert_eq!(parse_input("/copy 2"), Input::Command(ChatCommand::Copy(Some(2))));
+        assert_eq!(
+            parse_input("/write 2 src/main.rs"),
+            Input::Command(ChatCommand::Write { number: Some(2), path: PathBuf::from("src/main.rs") })
+        );
+        assert_eq!(parse_input("/write fix.sh"), Input::Command(ChatCommand::Write { number: None, path: PathBuf::from("fix.sh") }));
+        assert_eq!(parse_input("/code first"), Input::Invalid(String::from("Usage: /code [n]")));
+
+        assert_eq!(parse_input("/usr/bin/env: bad interpreter"), Input::Message(String::from("/usr/bin/env: bad interpreter")));
+        assert_eq!(parse_input("//exit"), Input::Message(String::from("/exit")));
+        assert_eq!(parse_input("/save"), Input::Invalid(String::from("Usage: /save <file>")));
+        assert_eq!(parse_input("/undo now"), Input::Invalid(String::from("Usage: /undo")));
+        assert!(matches!(parse_input("/frobnicate"), Input::Invalid(message) if message.starts_with("Unknown command /frobnicate")));
+    }
+}
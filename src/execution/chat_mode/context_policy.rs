@@ -0,0 +1,211 @@
+use crate::ai::chat_types::Role;
+use crate::ai::{config_values, tokens};
+use super::chat_log::ChatLogEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContextStrategy {
+    /// Drops the oldest turns, keeping system messages.
+    DropOldest,
+    /// Like `DropOldest`, but also keeps pinned messages.
+    KeepPinned,
+    /// Asks the model to fold older turns into a summary, keeping pinned messages.
+    Summarize,
+}
+
+impl ContextStrategy {
+    fn from_name(name: &str) -> Option<ContextStrategy> {
+        match name.trim() {
+            "drop_oldest" => Some(ContextStrategy::DropOldest),
+            "keep_pinned" => Some(ContextStrategy::KeepPinned),
+            "summarize" => Some(ContextStrategy::Summarize),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps a conversation within the model's context window. Read from the model
+/// config keys `max_context_tokens` and `context_strategy`; the model's
+/// `max_tokens` is reserved for the response. Without `max_context_tokens`
+/// the whole conversation is sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextPolicy {
+    pub max_context_tokens: Option<u32>,
+    pub reserved_tokens: u32,
+    pub strategy: ContextStrategy,
+}
+
+impl Default for ContextPolicy {
+    fn default() -> Self {
+        ContextPolicy {
+            max_context_tokens: None,
+            reserved_tokens: 0,
+            strategy: ContextStrategy::KeepPinned,
+        }
+    }
+}
+
+impl ContextPolicy {
+    /// The policy for a model config, with a warning for the caller to show
+    /// when `context_strategy` is not one it knows.
+    pub fn from_config(config: &serde_json::Value) -> (ContextPolicy, Option<String>) {
+        let strategy = config_values::get_string(config, "context_strategy");
+        let (strategy, warning) = match strategy.as_deref().map(|name| (name, ContextStrategy::from_name(name))) {
+            Some((_, Some(strategy))) => (strategy, None),
+            Some((name, None)) => (ContextStrategy::KeepPinned, Some(format!("Unknown context_strategy {}, using keep_pinned", name))),
+            None => (ContextStrategy::KeepPinned, None),
+        };
+        let policy = ContextPolicy {
+            max_context_tokens: config_values::get_u64(config, "max_context_tokens").map(|value| value as u32),
+            reserved_tokens: config_values::get_u64(config, "max_tokens").unwrap_or(0) as u32,
+            strategy,
+        };
+        (policy, warning)
+    }
+
+    fn budget(&self) -> Option<u32> {
+        self.max_context_tokens.map(|max| max.saturating_sub(self.reserved_tokens))
+    }
+
+    pub fn fits(&self, log: &[ChatLogEntry]) -> bool {
+        match self.budget() {
+            Some(budget) => estimate(log, 0..log.len()) <= budget,
+            None => true,
+        }
+    }
+
+    /// Indices of the entries to send, in conversation order.
+    pub fn select(&self, log: &[ChatLogEntry]) -> Vec<usize> {
+        match self.budget() {
+            Some(budget) => self.select_within(log, budget),
+            None => (0..log.len()).collect(),
+        }
+    }
+
+    /// Indices of the entries to fold into a summary. Only half the budget is
+    /// kept verbatim so the next few turns fit without summarizing again.
+    /// Nothing from the first fork on is summarized, so no branch is lost.
+    pub fn summarizable(&self, log: &[ChatLogEntry]) -> Vec<usize> {
+        let budget = match self.budget() {
+            Some(budget) => budget,
+            None => return Vec::new(),
+        };
+        let kept = self.select_within(log, budget / 2);
+        let fork = log.iter().position(|entry| !entry.branches.is_empty()).unwrap_or(log.len());
+        (0..fork)
+            .filter(|index| !kept.contains(index))
+            .filter(|index| log[*index].message.role != Role::System || log[*index].summary)
+            .collect()
+    }
+
+    fn is_required(&self, log: &[ChatLogEntry], index: usize) -> bool {
+        let entry = &log[index];
+        // Earlier summaries are only kept while they fit, since summarizing
+        // again folds them into the new summary
+        (entry.message.role == Role::System && !entry.summary)
+            || (entry.pinned && self.strategy != ContextStrategy::DropOldest)
+            || index + 1 == log.len()
+    }
+
+    fn select_within(&self, log: &[ChatLogEntry], budget: u32) -> Vec<usize> {
+        let mut keep = vec![false; log.len()];
+        let mut used = estimate(log, 0..0);
+        for index in 0..log.len() {
+            if self.is_required(log, index) {
+                keep[index] = true;
+                used += tokens::estimate_message_tokens(&log[index].message);
+            }
+        }
+
+        // Fill the rest of the budget with the most recent turns
+        let mut oldest_kept = None;
+        for index in (0..log.len()).rev() {
+            if keep[index] {
+                continue;
+            }
+            let cost = tokens::estimate_message_tokens(&log[index].message);
+            if used + cost > budget {
+                break;
+            }
+            keep[index] = true;
+            used += cost;
+            oldest_kept = Some(index);
+        }
+        // Don't open the window with a reply whose question was dropped
+        if let Some(index) = oldest_kept {
+            if log[index].message.role == Role::Assistant && index > 0 && !keep[index - 1] {
+                keep[index] = false;
+            }
+        }
+
+        (0..log.len()).filter(|index| keep[*index]).collect()
+    }
+}
+
+fn estimate(log: &[ChatLogEntry], range: std::ops::Range<usize>) -> u32 {
+    let messages = log[range].iter().map(|entry| entry.message.clone()).collect::<Vec<_>>();
+    tokens::estimate_prompt_tokens(&messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every message costs 4 tokens of overhead plus one per short word
+    fn conversation() -> Vec<ChatLogEntry> {
+        let mut log = vec![ChatLogEntry::new(Role::System, String::from("be brief"))];
+        for turn in 0..5 {
+            log.push(ChatLogEntry::new(Role::User, format!("ask {}", turn)));
+            log.push(ChatLogEntry::new(Role::Assistant, format!("reply {}", turn)));
+        }
+        log.push(ChatLogEntry::new(Role::User, String::from("last one")));
+        log
+    }
+
+    fn policy(strategy: ContextStrategy) -> ContextPolicy {
+        let config = serde_json::json!({ "max_context_tokens": "54", "max_tokens": 20 });
+        ContextPolicy { strategy, ..ContextPolicy::from_config(&config).0 }
+    }
+
+    #[test]
+    fn test_drops_oldest_turns_but_keeps_system_prompt() {
+        let log = conversation();
+        assert!(ContextPolicy::default().fits(&log));
+        assert_eq!(ContextPolicy::default().select(&log).len(), log.len());
+
+        // A budget of 34 fits the system prompt, the new question and three
+        // earlier messages, but a reply is never sent without its question
+        let selected = policy(ContextStrategy::DropOldest).select(&log);
+        assert_eq!(selected, vec![0, 9, 10, 11]);
+    }
+
+    #[test]
+    fn test_keeps_pinned_messages() {
+        let mut log = conversation();
+        log[1].pinned = true;
+
+        assert_eq!(policy(ContextStrategy::DropOldest).select(&log), vec![0, 9, 10, 11]);
+        assert_eq!(policy(ContextStrategy::KeepPinned).select(&log), vec![0, 1, 9, 10, 11]);
+    }
+
+    #[test]
+    fn test_unknown_strategy_is_a_warning() {
+        let (policy, warning) = ContextPolicy::from_config(&serde_json::json!({ "context_strategy": "forget" }));
+        assert_eq!(policy.strategy, ContextStrategy::KeepPinned);
+        assert_eq!(warning.as_deref(), Some("Unknown context_strategy forget, using keep_pinned"));
+        assert_eq!(ContextPolicy::from_config(&serde_json::json!({ "context_strategy": "summarize" })).1, None);
+    }
+
+    #[test]
+    fn test_summarizes_older_turns_and_previous_summaries() {
+        let mut log = conversation();
+        log.insert(1, ChatLogEntry { summary: true, ..ChatLogEntry::new(Role::System, String::from("earlier")) });
+
+        let policy = policy(ContextStrategy::Summarize);
+        assert!(!policy.fits(&log));
+        assert_eq!(policy.summarizable(&log), (1..=11).collect::<Vec<_>>());
+
+        // A fork and everything after it stay as they are
+        log[4].branches.push(vec![ChatLogEntry::new(Role::User, String::from("ask again"))]);
+        assert_eq!(policy.summarizable(&log), (1..=3).collect::<Vec<_>>());
+    }
+}
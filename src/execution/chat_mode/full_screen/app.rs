@@ -0,0 +1,289 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::execution::sessions::Session;
+use crate::execution::ui::StatefulList;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    Input,
+    Sessions,
+}
+
+/// What the event loop should do after an event.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    None,
+    Quit,
+    /// Send the text typed in the input box as a message or command.
+    Submit(String),
+    /// Replace the conversation with the session at this index in the sidebar.
+    OpenSession(usize),
+    /// Stop the response being streamed.
+    Cancel,
+}
+
+/// A multi-line text box edited at a cursor.
+#[derive(Default)]
+pub struct InputBox {
+    text: String,
+    /// Byte offset of the cursor in `text`.
+    cursor: usize,
+}
+
+impl InputBox {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.text.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some((index, _)) = self.text[..self.cursor].char_indices().next_back() {
+            self.text.remove(index);
+            self.cursor = index;
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn left(&mut self) {
+        if let Some((index, _)) = self.text[..self.cursor].char_indices().next_back() {
+            self.cursor = index;
+        }
+    }
+
+    pub fn right(&mut self) {
+        if let Some(c) = self.text[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    /// Moves to the start of the cursor's line.
+    pub fn home(&mut self) {
+        self.cursor = self.text[..self.cursor].rfind('\n').map(|index| index + 1).unwrap_or(0);
+    }
+
+    /// Moves to the end of the cursor's line.
+    pub fn end(&mut self) {
+        self.cursor += self.text[self.cursor..].find('\n').unwrap_or(self.text.len() - self.cursor);
+    }
+
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    /// The line and column of the cursor, counting characters from 0.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let before = &self.text[..self.cursor];
+        let row = before.matches('\n').count();
+        let column = before.rsplit('\n').next().unwrap_or_default().chars().count();
+        (row, column)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.split('\n').count()
+    }
+}
+
+pub struct App {
+    pub input: InputBox,
+    pub focus: Focus,
+    /// Transcript lines scrolled back from the bottom; 0 keeps up with new output.
+    pub scroll: usize,
+    /// Height of the transcript pane, scrolled by a page at a time.
+    pub page: usize,
+    pub sessions: StatefulList<Session>,
+    pub show_sessions: bool,
+    /// The response being streamed, shown below the conversation.
+    pub streaming: Option<String>,
+    /// Shown in the status bar until the next message is sent.
+    pub notice: Option<String>,
+}
+
+impl App {
+    pub fn new(sessions: Vec<Session>) -> App {
+        App {
+            input: InputBox::default(),
+            focus: Focus::Input,
+            scroll: 0,
+            page: 10,
+            sessions: StatefulList::new(sessions),
+            show_sessions: true,
+            streaming: None,
+            notice: None,
+        }
+    }
+
+    /// Refreshes the sidebar, keeping the same session selected.
+    pub fn set_sessions(&mut self, sessions: Vec<Session>) {
+        let selected = self.selected_session().map(|session| session.id.clone());
+        let index = selected
+            .and_then(|id| sessions.iter().position(|session| session.id == id))
+            .unwrap_or(0);
+        self.sessions.items = sessions;
+        self.sessions.state.select(Some(index));
+    }
+
+    pub fn selected_session(&self) -> Option<&Session> {
+        self.sessions.state.selected().and_then(|index| self.sessions.items.get(index))
+    }
+
+    pub fn handle_event(&mut self, event: Event) -> Action {
+        match event {
+            Event::Paste(text) if self.focus == Focus::Input => {
+                self.input.insert(&text);
+                Action::None
+            }
+            Event::Key(key) if key.kind != KeyEventKind::Release => self.handle_key(key),
+            _ => Action::None,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let streaming = self.streaming.is_some();
+        match key.code {
+            KeyCode::Char('c') if control => {
+                if streaming {
+                    return Action::Cancel;
+                }
+                if self.input.text().is_empty() {
+                    return Action::Quit;
+                }
+                self.input.take();
+            }
+            KeyCode::Char('d') if control && !streaming && self.input.text().is_empty() => return Action::Quit,
+            KeyCode::PageUp => self.scroll += self.page.max(1),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(self.page.max(1)),
+            KeyCode::Tab => {
+                self.show_sessions = true;
+                self.focus = match self.focus {
+                    Focus::Input => Focus::Sessions,
+                    Focus::Sessions => Focus::Input,
+                };
+            }
+            _ => {
+                return match self.focus {
+                    Focus::Sessions => self.handle_sessions_key(key),
+                    Focus::Input => self.handle_input_key(key),
+                }
+            }
+        }
+        Action::None
+    }
+
+    fn handle_sessions_key(&mut self, key: KeyEvent) -> Action {
+        if self.sessions.items.is_empty() {
+            if key.code == KeyCode::Esc {
+                self.focus = Focus::Input;
+            }
+            return Action::None;
+        }
+        match key.code {
+            KeyCode::Up => self.sessions.previous(),
+            KeyCode::Down => self.sessions.next(),
+            KeyCode::Enter if self.streaming.is_none() => {
+                if let Some(index) = self.sessions.state.selected() {
+                    self.focus = Focus::Input;
+                    return Action::OpenSession(index);
+                }
+            }
+            KeyCode::Esc => self.focus = Focus::Input,
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) -> Action {
+        let newline = match key.code {
+            KeyCode::Enter => key.modifiers.intersects(KeyModifiers::ALT | KeyModifiers::SHIFT),
+            KeyCode::Char('j') => key.modifiers.contains(KeyModifiers::CONTROL),
+            _ => false,
+        };
+        match key.code {
+            _ if newline => self.input.insert("\n"),
+            // Typing ahead is fine while an answer streams in, but sending waits for it
+            KeyCode::Enter if self.streaming.is_none() && !self.input.text().trim().is_empty() => {
+                return Action::Submit(self.input.take());
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.insert(c.encode_utf8(&mut [0; 4]));
+            }
+            KeyCode::Backspace => self.input.backspace(),
+            KeyCode::Delete => self.input.delete(),
+            KeyCode::Left => self.input.left(),
+            KeyCode::Right => self.input.right(),
+            KeyCode::Home => self.input.home(),
+            KeyCode::End => self.input.end(),
+            KeyCode::Up => self.scroll += 1,
+            KeyCode::Down => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+        Action::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent::new(code, modifiers))
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            app.handle_event(key(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn test_input_box_edits_multiple_lines() {
+        let mut app = App::new(Vec::new());
+        type_text(&mut app, "fn main()");
+        app.handle_event(key(KeyCode::Enter, KeyModifiers::ALT));
+        app.handle_event(Event::Paste(String::from("{}\r\nend")));
+        assert_eq!(app.input.text(), "fn main()\n{}\nend");
+        assert_eq!(app.input.cursor_position(), (2, 3));
+
+        app.handle_event(key(KeyCode::Home, KeyModifiers::NONE));
+        app.handle_event(key(KeyCode::Backspace, KeyModifiers::NONE));
+        assert_eq!(app.input.text(), "fn main()\n{}end");
+        assert_eq!(app.input.line_count(), 2);
+
+        let submitted = app.handle_event(key(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(submitted, Action::Submit(String::from("fn main()\n{}end")));
+        assert_eq!(app.input.text(), "");
+    }
+
+    #[test]
+    fn test_streaming_keys_and_sessions() {
+        let mut app = App::new(vec![Session::new(String::from("a")), Session::new(String::from("b"))]);
+        app.streaming = Some(String::new());
+        type_text(&mut app, "next");
+        assert_eq!(app.handle_event(key(KeyCode::Enter, KeyModifiers::NONE)), Action::None);
+        assert_eq!(app.handle_event(key(KeyCode::Char('c'), KeyModifiers::CONTROL)), Action::Cancel);
+        app.streaming = None;
+
+        app.handle_event(key(KeyCode::Tab, KeyModifiers::NONE));
+        app.handle_event(key(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(app.handle_event(key(KeyCode::Enter, KeyModifiers::NONE)), Action::OpenSession(1));
+        assert_eq!(app.focus, Focus::Input);
+
+        app.page = 20;
+        app.handle_event(key(KeyCode::PageUp, KeyModifiers::NONE));
+        assert_eq!(app.scroll, 20);
+        app.handle_event(key(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert_eq!(app.input.text(), "");
+        assert_eq!(app.handle_event(key(KeyCode::Char('d'), KeyModifiers::CONTROL)), Action::Quit);
+    }
+}
@@ -0,0 +1,292 @@
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+
+use crossterm::{
+    event::{DisableBracketedPaste, EnableBracketedPaste, EventStream},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::StreamExt;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tui::{backend::CrosstermBackend, Terminal};
+
+use crate::ai::chat_error::ChatModelError;
+use crate::config::user::usage::UsageTotals;
+use crate::execution::sessions::SessionStore;
+use super::chat_client::ChatClient;
+use super::chat_log::ChatLogEntry;
+use super::commands::{self, ChatCommand, Input};
+use super::terminal_renderer::RenderedResponse;
+use app::{Action, App};
+
+mod app;
+mod view;
+
+type ChatTerminal = Terminal<CrosstermBackend<io::Stdout>>;
+
+/// Runs the chat full screen, with the transcript, an input box, a status
+/// bar and the saved sessions, until the user quits.
+pub async fn run(client: &mut ChatClient) -> Result<(), Box<dyn Error>> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste)?;
+    let result = match Terminal::new(CrosstermBackend::new(io::stdout())) {
+        Ok(mut terminal) => event_loop(&mut terminal, client).await,
+        Err(e) => Err(e.into()),
+    };
+    // Restore the terminal even when the loop failed, so the error is readable
+    execute!(io::stdout(), DisableBracketedPaste, LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    result
+}
+
+fn saved_sessions() -> Vec<crate::execution::sessions::Session> {
+    SessionStore::default_location()
+        .and_then(|store| store.list())
+        .unwrap_or_default()
+}
+
+fn draw(terminal: &mut ChatTerminal, app: &mut App, client: &ChatClient) -> Result<(), Box<dyn Error>> {
+    let usage = client.usage_totals();
+    let status = view::Status {
+        model: client.model_id().unwrap_or("chat"),
+        session_id: client.saved_session_id(),
+        usage,
+    };
+    terminal.draw(|f| view::draw(f, app, client.chat_log(), &status))?;
+    Ok(())
+}
+
+/// A copy of what `draw` shows from the client, for redrawing while a
+/// request holds on to the client.
+struct Snapshot {
+    log: Vec<ChatLogEntry>,
+    model: String,
+    session_id: Option<String>,
+    usage: UsageTotals,
+}
+
+impl Snapshot {
+    fn of(client: &ChatClient) -> Snapshot {
+        Snapshot {
+            log: client.chat_log().to_vec(),
+            model: client.model_id().unwrap_or("chat").to_string(),
+            session_id: client.saved_session_id().map(String::from),
+            usage: client.usage_totals().clone(),
+        }
+    }
+
+    fn draw(&self, terminal: &mut ChatTerminal, app: &mut App) -> Result<(), Box<dyn Error>> {
+        let status = view::Status { model: &self.model, session_id: self.session_id.as_deref(), usage: &self.usage };
+        terminal.draw(|f| view::draw(f, app, &self.log, &status))?;
+        Ok(())
+    }
+}
+
+async fn event_loop(terminal: &mut ChatTerminal, client: &mut ChatClient) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(saved_sessions());
+    let mut events = EventStream::new();
+    // Retries are shown in the status bar, since anything printed would land on the screen
+    let (sender, mut retries) = mpsc::unbounded_channel();
+    client.set_retry_listener(Arc::new(move |retry| {
+        sender.send(retry.to_string()).ok();
+    }));
+    loop {
+        draw(terminal, &mut app, client)?;
+        let event = match events.next().await {
+            Some(event) => event?,
+            None => return Ok(()),
+        };
+        match app.handle_event(event) {
+            Action::Quit => return Ok(()),
+            Action::Submit(input) => {
+                app.notice = None;
+                let quit = match commands::parse_input(&input) {
+                    Input::Message(message) => {
+                        send(terminal, &mut events, &mut retries, &mut app, client, message).await?;
+                        false
+                    }
+                    Input::Command(command) => match run_command(command, &mut app, client) {
+                        Ok(quit) => quit,
+                        Err(e) => {
+                            app.notice = Some(describe(e.as_ref()));
+                            false
+                        }
+                    },
+                    Input::Invalid(message) => {
+                        app.notice = Some(message);
+                        false
+                    }
+                };
+                if quit {
+                    return Ok(());
+                }
+            }
+            Action::OpenSession(index) => {
+                let session = app.sessions.items[index].clone();
+                if client.session_id() != Some(session.id.as_str()) {
+                    let title = session.title.clone().unwrap_or_else(|| session.id.clone());
+                    app.notice = Some(match client.open_session(session) {
+                        Ok(None) => format!("Opened {}", title),
+                        Ok(Some(warning)) => format!("Opened {}; {}", title, warning),
+                        Err(e) => describe(e.as_ref()),
+                    });
+                    app.scroll = 0;
+                }
+            }
+            Action::None | Action::Cancel => {}
+        }
+    }
+}
+
+/// Sends a message and streams the answer into the transcript. Keys still
+/// work while it streams, and Ctrl-C stops it keeping what has arrived.
+async fn send(
+    terminal: &mut ChatTerminal,
+    events: &mut EventStream,
+    retries: &mut UnboundedReceiver<String>,
+    app: &mut App,
+    client: &mut ChatClient,
+    message: String,
+) -> Result<(), Box<dyn Error>> {
+    client.push_question(message);
+    app.scroll = 0;
+    app.streaming = Some(String::new());
+    draw(terminal, app, client)?;
+
+    let mut warnings = Vec::new();
+    // Ctrl-C before the answer starts, such as during retries or a summary,
+    // cancels the request like a failed one
+    let snapshot = Snapshot::of(client);
+    let opened = {
+        let open = client.open_stream(&mut warnings);
+        tokio::pin!(open);
+        loop {
+            tokio::select! {
+                opened = &mut open => break opened,
+                Some(retry) = retries.recv() => app.notice = Some(retry),
+                event = events.next() => match event {
+                    Some(Ok(event)) => {
+                        if app.handle_event(event) == Action::Cancel {
+                            break Err("Request cancelled".into());
+                        }
+                    }
+                    Some(Err(e)) => return Err(e.into()),
+                    None => break Err("Request cancelled".into()),
+                },
+            }
+            snapshot.draw(terminal, app)?;
+        }
+    };
+    // The answer is on its way, so a retry notice no longer applies
+    app.notice = None;
+    let (request, mut stream) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            // Drop the unanswered message so it is not sent twice on the next turn
+            client.discard_question();
+            app.streaming = None;
+            app.notice = Some(describe(e.as_ref()));
+            return Ok(());
+        }
+    };
+
+    let mut response = RenderedResponse::default();
+    loop {
+        tokio::select! {
+            chunk = stream.next() => match chunk {
+                Some(Ok(chunk)) => {
+                    let text = response.add_chunk(chunk);
+                    if let Some(streaming) = &mut app.streaming {
+                        streaming.push_str(&text);
+                    }
+                }
+                Some(Err(e)) => warnings.push(e),
+                None => break,
+            },
+            event = events.next() => match event {
+                Some(Ok(event)) => {
+                    if app.handle_event(event) == Action::Cancel {
+                        response.interrupted = true;
+                        break;
+                    }
+                }
+                Some(Err(e)) => return Err(e.into()),
+                None => break,
+            },
+        }
+        draw(terminal, app, client)?;
+    }
+    drop(stream);
+
+    app.streaming = None;
+    client.finish_response(&request, &response, &mut warnings);
+    app.notice = warnings.first().map(|warning| describe(warning.as_ref()));
+    app.set_sessions(saved_sessions());
+    Ok(())
+}
+
+/// Runs the commands that make sense full screen, returning whether to quit.
+fn run_command(command: ChatCommand, app: &mut App, client: &mut ChatClient) -> Result<bool, Box<dyn Error>> {
+    let notice = match command {
+        ChatCommand::Exit => return Ok(true),
+        ChatCommand::Reset => {
+            client.reset();
+            String::from("Started a new conversation")
+        }
+        ChatCommand::Model(None) => {
+            let current = client.model_id();
+            let ids = client.model_ids().into_iter()
+                .map(|id| if Some(id) == current { format!("*{}", id) } else { id.to_string() })
+                .collect::<Vec<_>>();
+            if ids.is_empty() {
+                return Err("Switching models is not available in this chat".into());
+            }
+            format!("Models: {}", ids.join(", "))
+        }
+        ChatCommand::Model(Some(id)) => {
+            match client.switch_model(&id)? {
+                Some(warning) => format!("Switched to {}; {}", id, warning),
+                None => format!("Switched to {}", id),
+            }
+        }
+        ChatCommand::Undo => {
+            let notice = match client.undo() {
+                Some(_) => "Removed the last question and its answer",
+                None => "Nothing to undo",
+            };
+            client.save_session()?;
+            notice.to_string()
+        }
+        ChatCommand::Pin => {
+            let notice = match client.pin_last_exchange() {
+                0 => String::from("Nothing to pin yet"),
+                count => format!("Pinned the last {} messages", count),
+            };
+            client.save_session()?;
+            notice
+        }
+        ChatCommand::Add(patterns) => {
+            let set = client.attach(&patterns)?;
+            let mut notice = format!("Attached {} file{} (~{} tokens)",
+                set.attached.len(), if set.attached.len() == 1 { "" } else { "s" }, set.tokens());
+            if !set.skipped.is_empty() {
+                notice.push_str(&format!(", skipped {}", set.skipped.len()));
+            }
+            notice
+        }
+        ChatCommand::Help => String::from("Here: /reset /model [id] /undo /pin /add <path> /exit; the rest need `kaiti chat` without --tui"),
+        _ => String::from("That command only works in `kaiti chat` without --tui"),
+    };
+    app.notice = Some(notice);
+    app.set_sessions(saved_sessions());
+    Ok(false)
+}
+
+/// An error on one line, with the provider's hint when there is one.
+fn describe(error: &(dyn Error + 'static)) -> String {
+    match error.downcast_ref::<ChatModelError>() {
+        Some(model_error) => format!("error: {} ({})", error, model_error.hint()),
+        None => format!("error: {}", error),
+    }
+}
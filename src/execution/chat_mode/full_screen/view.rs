@@ -0,0 +1,234 @@
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+
+use crate::ai::chat_types::Role;
+use crate::config::user::usage::UsageTotals;
+use crate::execution::chat_mode::chat_log::ChatLogEntry;
+use crate::execution::ui::wrap;
+use super::app::{App, Focus};
+
+const SIDEBAR_WIDTH: u16 = 32;
+const MAX_INPUT_LINES: usize = 6;
+
+/// What the status bar reports about the chat.
+pub struct Status<'a> {
+    pub model: &'a str,
+    pub session_id: Option<&'a str>,
+    pub usage: &'a UsageTotals,
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App, entries: &[ChatLogEntry], status: &Status) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)].as_ref())
+        .split(f.size());
+    let main = if app.show_sessions && rows[0].width > SIDEBAR_WIDTH * 2 {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(SIDEBAR_WIDTH), Constraint::Min(20)].as_ref())
+            .split(rows[0]);
+        draw_sessions(f, app, status.session_id, columns[0]);
+        columns[1]
+    } else {
+        rows[0]
+    };
+
+    let input_height = app.input.line_count().min(MAX_INPUT_LINES) as u16 + 2;
+    let panes = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(input_height)].as_ref())
+        .split(main);
+    draw_transcript(f, app, entries, panes[0]);
+    draw_input(f, app, panes[1]);
+    draw_status(f, app, status, rows[1]);
+}
+
+fn border_style(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::LightGreen)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    }
+}
+
+fn draw_sessions<B: Backend>(f: &mut Frame<B>, app: &mut App, current: Option<&str>, area: Rect) {
+    let width = area.width.saturating_sub(4) as usize;
+    let items = app.sessions.items.iter()
+        .map(|session| {
+            let marker = if Some(session.id.as_str()) == current { "● " } else { "  " };
+            let title = truncate(session.title.as_deref().unwrap_or("(untitled)"), width.saturating_sub(2));
+            let updated = chrono::DateTime::parse_from_rfc3339(&session.updated_at)
+                .map(|updated| updated.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            ListItem::new(vec![
+                Spans::from(format!("{}{}", marker, title)),
+                Spans::from(Span::styled(format!("  {} · {} msgs", updated, session.messages.len()), Style::default().fg(Color::DarkGray))),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Sessions ").border_style(border_style(app.focus == Focus::Sessions)))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(list, area, &mut app.sessions.state);
+}
+
+fn draw_transcript<B: Backend>(f: &mut Frame<B>, app: &mut App, entries: &[ChatLogEntry], area: Rect) {
+    let width = area.width.saturating_sub(2) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    let lines = transcript_lines(entries, app.streaming.as_deref(), width);
+
+    // Clamp here, where the wrapped height is known, so paging back down is immediate
+    let max_scroll = lines.len().saturating_sub(height);
+    app.scroll = app.scroll.min(max_scroll);
+    app.page = height.saturating_sub(1).max(1);
+    let start = max_scroll - app.scroll;
+    let visible = lines[start..(start + height).min(lines.len())].to_vec();
+
+    let title = if app.scroll > 0 {
+        format!(" Conversation (scrolled back {} lines; PgDn to follow) ", app.scroll)
+    } else {
+        String::from(" Conversation ")
+    };
+    let transcript = Paragraph::new(visible)
+        .block(Block::default().borders(Borders::ALL).title(title).border_style(border_style(false)));
+    f.render_widget(transcript, area);
+}
+
+fn draw_input<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let (row, column) = app.input.cursor_position();
+    // Keep the cursor in view when the message is taller or wider than the box
+    let row_offset = (row + 1).saturating_sub(inner_height);
+    let column_offset = (column + 1).saturating_sub(inner_width);
+
+    let title = if app.streaming.is_some() { " Message (sends once the answer finishes) " } else { " Message " };
+    let input = Paragraph::new(app.input.text())
+        .scroll((row_offset as u16, column_offset as u16))
+        .block(Block::default().borders(Borders::ALL).title(title).border_style(border_style(app.focus == Focus::Input)));
+    f.render_widget(input, area);
+    if app.focus == Focus::Input {
+        f.set_cursor(area.x + 1 + (column - column_offset) as u16, area.y + 1 + (row - row_offset) as u16);
+    }
+}
+
+fn draw_status<B: Backend>(f: &mut Frame<B>, app: &App, status: &Status, area: Rect) {
+    let estimate = if status.usage.estimated_requests > 0 { "~" } else { "" };
+    let mut spans = vec![
+        Span::styled(format!(" {} ", status.model), Style::default().fg(Color::Black).bg(Color::Green)),
+        Span::raw(format!(" {}{} in / {}{} out tokens · ${:.4} ",
+            estimate, status.usage.prompt_tokens, estimate, status.usage.completion_tokens, status.usage.cost)),
+    ];
+    if let Some(id) = status.session_id {
+        spans.push(Span::styled(format!("· {} ", id), Style::default().fg(Color::DarkGray)));
+    }
+    let hint = match (&app.notice, &app.streaming) {
+        (Some(notice), _) => Span::styled(format!("· {}", notice), Style::default().fg(Color::Yellow)),
+        (None, Some(_)) => Span::styled("· answering… Ctrl-C stops", Style::default().fg(Color::DarkGray)),
+        (None, None) => Span::styled(
+            "· Enter send · Alt-Enter newline · PgUp/PgDn scroll · Tab sessions · Ctrl-D quit",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    spans.push(hint);
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+/// The conversation laid out as lines `width` columns wide.
+pub fn transcript_lines(entries: &[ChatLogEntry], streaming: Option<&str>, width: usize) -> Vec<Spans<'static>> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        let style = match entry.message.role {
+            Role::System => Style::default().fg(Color::DarkGray),
+            Role::User if entry.attachment => Style::default().fg(Color::Yellow),
+            Role::User => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            Role::Assistant => Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+        };
+        let mut heading = vec![Span::styled(entry.speaker().to_string(), style)];
+        if !entry.branches.is_empty() {
+            heading.push(Span::styled(format!("  (branch {} of {})", entry.branch + 1, entry.branches.len() + 1),
+                Style::default().fg(Color::DarkGray)));
+        }
+        lines.push(Spans::from(heading));
+
+        if entry.attachment {
+            // The files themselves would bury the conversation, so only their names are listed
+            for path in entry.message.content.lines().filter_map(|line| line.strip_prefix("File: ")) {
+                lines.push(Spans::from(Span::styled(format!("  {}", path), Style::default().fg(Color::DarkGray))));
+            }
+        } else {
+            push_markdown(&mut lines, entry.display_content(), width);
+        }
+        if entry.interrupted {
+            lines.push(Spans::from(Span::styled("[interrupted]", Style::default().fg(Color::Yellow))));
+        }
+        lines.push(Spans::default());
+    }
+
+    if let Some(partial) = streaming {
+        lines.push(Spans::from(Span::styled("AI", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))));
+        push_markdown(&mut lines, partial, width);
+        lines.push(Spans::from(Span::styled("▍", Style::default().fg(Color::Green))));
+    }
+    lines
+}
+
+/// Adds wrapped markdown lines, setting code blocks and headings apart.
+fn push_markdown(lines: &mut Vec<Spans<'static>>, markdown: &str, width: usize) {
+    let mut in_code = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        let fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+        let style = if fence {
+            Style::default().fg(Color::DarkGray)
+        } else if in_code {
+            Style::default().fg(Color::LightYellow)
+        } else if trimmed.starts_with('#') {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        if fence {
+            in_code = !in_code;
+        }
+        for wrapped in wrap(line, width) {
+            lines.push(Spans::from(Span::styled(wrapped, style)));
+        }
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    match text.char_indices().nth(width) {
+        Some((index, _)) if width > 0 => format!("{}…", &text[..text[..index].char_indices().next_back().map(|(i, _)| i).unwrap_or(0)]),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Spans]) -> Vec<String> {
+        lines.iter().map(|line| line.0.iter().map(|span| span.content.as_ref()).collect()).collect()
+    }
+
+    #[test]
+    fn test_transcript_lists_attachments_and_streams() {
+        let entries = vec![
+            ChatLogEntry { attachment: true, ..ChatLogEntry::new(Role::User, String::from("Files\n\nFile: src/main.rs\n```rs\nfn main() {}\n```")) },
+            ChatLogEntry::new(Role::User, String::from("What does main do?")),
+        ];
+        let lines = transcript_lines(&entries, Some("Nothing yet"), 40);
+        assert_eq!(text(&lines), vec![
+            "Attached files", "  src/main.rs", "",
+            "You", "What does main do?", "",
+            "AI", "Nothing yet", "▍",
+        ]);
+    }
+}
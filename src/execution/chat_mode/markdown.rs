@@ -0,0 +1,219 @@
+use std::sync::OnceLock;
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+use termimad::{FmtText, MadSkin};
+
+const THEME: &str = "base16-ocean.dark";
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+
+pub(crate) fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+pub(crate) fn theme() -> &'static Theme {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    &THEMES.get_or_init(ThemeSet::load_defaults).themes[THEME]
+}
+
+struct CodeBlock {
+    highlighter: HighlightLines<'static>,
+    /// The opening fence; only a bare fence of the same kind, at least as long, closes it.
+    fence: String,
+}
+
+/// Renders markdown as it streams in. Text is echoed as it arrives and each
+/// line is redrawn formatted once it is complete; tables are redrawn once
+/// their last row has arrived so the columns can be aligned.
+pub struct MarkdownStream {
+    width: usize,
+    skin: MadSkin,
+    /// The incomplete line being streamed.
+    line: String,
+    code: Option<CodeBlock>,
+    table: Vec<String>,
+    /// Terminal rows taken up by raw text that will be redrawn.
+    raw_rows: usize,
+}
+
+impl MarkdownStream {
+    pub fn new(width: usize) -> MarkdownStream {
+        MarkdownStream {
+            width: width.max(20),
+            skin: MadSkin::default(),
+            line: String::new(),
+            code: None,
+            table: Vec::new(),
+            raw_rows: 0,
+        }
+    }
+
+    /// Takes the next piece of the response and returns what to write to the terminal.
+    pub fn push(&mut self, text: &str) -> String {
+        let mut output = String::new();
+        let mut pieces = text.split('\n').peekable();
+        while let Some(piece) = pieces.next() {
+            self.line.push_str(piece);
+            output.push_str(piece);
+            if pieces.peek().is_some() {
+                let line = std::mem::take(&mut self.line);
+                output.push_str(&self.complete_line(line));
+            }
+        }
+        output
+    }
+
+    /// Formats whatever is left once the response has ended or was cancelled.
+    pub fn finish(&mut self) -> String {
+        let mut output = String::new();
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            output.push_str(&self.complete_line(line));
+        }
+        output.push_str(&self.flush_table());
+        if self.code.take().is_some() {
+            output.push_str(RESET);
+        }
+        output
+    }
+
+    fn complete_line(&mut self, line: String) -> String {
+        let is_table_row = self.code.is_none() && line.trim_start().starts_with('|');
+        if is_table_row {
+            // Leave the raw row on screen until the whole table can be drawn
+            self.raw_rows += self.rows(&line);
+            self.table.push(line);
+            return String::from("\n");
+        }
+
+        // Clear the raw line first so a pending table is directly above the cursor
+        let mut output = self.erase(self.rows(&line));
+        output.push_str(&self.flush_table());
+        output.push_str(&self.format_line(&line));
+        output
+    }
+
+    fn format_line(&mut self, line: &str) -> String {
+        if let Some((fence, info)) = split_fence(line) {
+            match &self.code {
+                Some(code) if fence.starts_with(&code.fence) && info.is_empty() => {
+                    self.code = None;
+                    return format!("{}{}{}\n", DIM, line, RESET);
+                }
+                Some(_) => {}
+                None => {
+                    let syntax = syntaxes().find_syntax_by_token(info)
+                        .unwrap_or_else(|| syntaxes().find_syntax_plain_text());
+                    self.code = Some(CodeBlock { highlighter: HighlightLines::new(syntax, theme()), fence: fence.to_string() });
+                    return format!("{}{}{}\n", DIM, line, RESET);
+                }
+            }
+        }
+        if let Some(code) = &mut self.code {
+            let source = format!("{}\n", line);
+            return match code.highlighter.highlight_line(&source, syntaxes()) {
+                Ok(ranges) => format!("{}{}\n", as_24_bit_terminal_escaped(&ranges, false).trim_end_matches('\n'), RESET),
+                Err(_) => source,
+            };
+        }
+        if line.trim().is_empty() {
+            return String::from("\n");
+        }
+        FmtText::from(&self.skin, line, Some(self.width)).to_string()
+    }
+
+    fn flush_table(&mut self) -> String {
+        if self.table.is_empty() {
+            return String::new();
+        }
+        let table = std::mem::take(&mut self.table).join("\n");
+        // The raw rows are all above the cursor, which sits at the start of a new row
+        let mut output = String::new();
+        if self.raw_rows > 0 {
+            output.push_str(&format!("\x1b[{}A", self.raw_rows));
+        }
+        self.raw_rows = 0;
+        output.push_str("\r\x1b[J");
+        output.push_str(&FmtText::from(&self.skin, &table, Some(self.width)).to_string());
+        output
+    }
+
+    /// Clears the `rows` rows of raw text the cursor is on the last of.
+    fn erase(&self, rows: usize) -> String {
+        match rows {
+            0 | 1 => String::from("\r\x1b[J"),
+            rows => format!("\x1b[{}A\r\x1b[J", rows - 1),
+        }
+    }
+
+    fn rows(&self, line: &str) -> usize {
+        let length = line.chars().count();
+        length.saturating_sub(1) / self.width + 1
+    }
+}
+
+/// Splits a ```` ``` ```` or `~~~` fence line into the fence and its info string.
+fn split_fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = trimmed.len() - trimmed.trim_start_matches(marker).len();
+    (length >= 3).then(|| (&trimmed[..length], trimmed[length..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_escapes(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                // Skip to the end of the CSI sequence
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_renders_markdown_as_it_streams() {
+        let mut stream = MarkdownStream::new(80);
+        let mut output = String::new();
+        for piece in ["# Fix", "ing it\n\nUse **", "borrow** here:\n```rust\nlet x = &", "value;\n```\n| a | b |\n", "|---|---|\n| 1 | 2 |\n",
+                      "~~~python\n# not a heading\n```\n~~~\nDone"] {
+            output.push_str(&stream.push(piece));
+        }
+        output.push_str(&stream.finish());
+
+        // Partial lines are echoed before being redrawn formatted
+        assert!(output.starts_with("# Fix"));
+        assert!(!strip_escapes(&output).contains("**borrow**\n"));
+        assert!(output.contains("\x1b[38;2;"), "code is highlighted");
+        assert!(strip_escapes(&output).contains("let x = &value;\n"));
+        // A tilde fence is code too, and only a tilde fence closes it
+        assert!(strip_escapes(&output).contains("# not a heading\r# not a heading\n```\r```\n~~~\r~~~\nDone"));
+        // The three raw table rows are redrawn as an aligned table
+        assert!(output.contains("\x1b[3A\r\x1b[J"));
+        assert!(strip_escapes(&output).trim_end().ends_with("Done"));
+    }
+
+    #[test]
+    fn test_wrapped_lines_are_fully_erased() {
+        let stream = MarkdownStream::new(20);
+        assert_eq!(stream.rows(""), 1);
+        assert_eq!(stream.rows(&"x".repeat(20)), 1);
+        assert_eq!(stream.rows(&"x".repeat(21)), 2);
+        assert_eq!(stream.erase(2), "\x1b[1A\r\x1b[J");
+    }
+}
@@ -1,29 +1,88 @@
 use std::error::Error;
+use std::path::PathBuf;
 
-use crate::ai::chat_model::ChatModel;
-use crate::config::user::settings::ModelConfig;
-use crate::open_ai_gpt::GptClient;
+use crate::ai::config_values;
+use crate::config::user::settings::{ModelConfig, PriceTable};
+use crate::execution::sessions::{Session, SessionStore};
+use model_selection::ModelSelection;
 
 mod terminal_renderer;
+mod attachments;
+mod branches;
 mod chat_client;
+pub mod chat_log;
+mod commands;
+mod context_policy;
+mod full_screen;
+pub(crate) mod markdown;
+mod model_selection;
+mod usage_tracker;
 
-pub async fn run_chat_mode(c_model: &ModelConfig) -> Result<(), Box<dyn Error>> {
-    let mut renderer = terminal_renderer::TerminalRenderer::new();
-    let chat_model = create_chat_model(c_model)?;
-    let mut chat_client = chat_client::ChatClient::new(chat_model);
-    chat_client.run(&mut renderer).await?;
-    Ok(())
+#[derive(Default)]
+pub struct ChatOptions {
+    /// Overrides the `system_prompt` configured for the model.
+    pub system_prompt: Option<String>,
+    /// Fixture file that every streamed response is recorded to.
+    pub record: Option<PathBuf>,
+    /// Prices used to report the cost of each response.
+    pub pricing: PriceTable,
+    /// A saved session to continue instead of starting a new one.
+    pub resume: Option<Session>,
+    /// The configured models that can be switched to with `/model`.
+    pub models: Vec<ModelConfig>,
+    /// Writes responses as they arrive instead of rendering their markdown.
+    pub raw: bool,
+    /// Files, directories or globs attached to the conversation before it starts.
+    pub files: Vec<String>,
+    /// Runs the chat full screen instead of line by line.
+    pub tui: bool,
 }
 
-// TODO: Implement a model path to determine the correct selection
-fn create_chat_model(c_model: &ModelConfig) -> Result<Box<dyn ChatModel>, Box<dyn Error>> {
-    let model = match c_model.name.as_str() {
-        "ChatGPT" => {
-            let config = serde_json::to_value(c_model.config.clone())?;
-            let gpt_client = GptClient::new(config);
-            Box::new(gpt_client) as Box<dyn ChatModel>
-        }
-        _ => return Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unsupported model name"))),
+pub async fn run_chat_mode(c_model: &ModelConfig, options: ChatOptions) -> Result<(), Box<dyn Error>> {
+    let mut renderer = terminal_renderer::TerminalRenderer::new();
+    renderer.set_raw(options.raw);
+    let models = ModelSelection::new(c_model.clone(), options.models, options.record);
+    let chat_model = models.create_model()?;
+
+    let store = SessionStore::default_location()?;
+    let session = options.resume.unwrap_or_else(|| store.create(c_model.id.clone()));
+    let resumed = !session.messages.is_empty();
+    let usage = usage_tracker::UsageTracker::new(session.id.clone(), models.model_name(), options.pricing)
+        .with_session_totals(session.usage.clone());
+    let mut chat_client = chat_client::ChatClient::new(chat_model, usage);
+    if resumed {
+        renderer.print_resumed(&session);
+    }
+    chat_client.set_session(store, session);
+
+    // A resumed session keeps its own system prompt unless one is given explicitly
+    let system_prompt = if resumed {
+        options.system_prompt
+    } else {
+        options.system_prompt.or_else(|| config_values::get_string(&c_model.config, "system_prompt"))
     };
-    Ok(model)
-}
\ No newline at end of file
+    if let Some(prompt) = system_prompt.filter(|prompt| !prompt.trim().is_empty()) {
+        chat_client.set_system_prompt(prompt);
+    }
+    let (context, warning) = context_policy::ContextPolicy::from_config(&c_model.config);
+    if let Some(warning) = warning {
+        renderer.print_notice(&warning);
+    }
+    chat_client.set_context_policy(context);
+    chat_client.set_attachment_budget(attachments::AttachmentBudget::from_config(&c_model.config));
+    if !options.files.is_empty() {
+        let set = chat_client.attach(&options.files)?;
+        renderer.print_attachments(&set);
+    }
+    chat_client.set_model_selection(models);
+    if options.tui {
+        full_screen::run(&mut chat_client).await?;
+    } else {
+        chat_client.set_retry_listener(terminal_renderer::TerminalRenderer::retry_listener());
+        chat_client.run(&mut renderer).await?;
+    }
+    if let Some(id) = chat_client.saved_session_id() {
+        println!("Resume this chat with `kaiti chat --resume {}`", id);
+    }
+    Ok(())
+}
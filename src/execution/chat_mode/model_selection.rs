@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::ai::chat_model::ChatModel;
+use crate::ai::config_values;
+use crate::config::user::settings::ModelConfig;
+use crate::models::global_registry;
+use crate::replay::RecordingModel;
+
+/// The model a chat is using and the configured models it can switch to.
+pub struct ModelSelection {
+    current: ModelConfig,
+    available: Vec<ModelConfig>,
+    /// Fixture file that every model's responses are recorded to.
+    record: Option<PathBuf>,
+}
+
+impl ModelSelection {
+    pub fn new(current: ModelConfig, available: Vec<ModelConfig>, record: Option<PathBuf>) -> ModelSelection {
+        ModelSelection { current, available, record }
+    }
+
+    pub fn current(&self) -> &ModelConfig {
+        &self.current
+    }
+
+    pub fn available(&self) -> &[ModelConfig] {
+        &self.available
+    }
+
+    /// The provider's name for the current model, used to price its responses.
+    pub fn model_name(&self) -> String {
+        config_values::get_string(&self.current.config, "model").unwrap_or_else(|| self.current.name.clone())
+    }
+
+    pub fn create_model(&self) -> Result<Box<dyn ChatModel>, Box<dyn Error>> {
+        let registry = global_registry().read().map_err(|_| "model registry is poisoned")?;
+        let chat_model = registry.create_model(&self.current)?;
+        Ok(match &self.record {
+            Some(fixture) => Box::new(RecordingModel::wrap(chat_model, fixture.clone())),
+            None => chat_model,
+        })
+    }
+
+    /// Switches to the configured model with `id`, returning the new chat model.
+    pub fn select(&mut self, id: &str) -> Result<Box<dyn ChatModel>, Box<dyn Error>> {
+        let c_model = self.available.iter()
+            .find(|c_model| c_model.id == id)
+            .ok_or_else(|| format!("No model with id {}; type /model to list them", id))?;
+        let previous = std::mem::replace(&mut self.current, c_model.clone());
+        let chat_model = self.create_model();
+        if chat_model.is_err() {
+            self.current = previous;
+        }
+        chat_model
+    }
+
+    /// Changes a setting of the current model for the rest of the chat,
+    /// returning the chat model rebuilt with it. Values are read as JSON when
+    /// they parse, so `0.2` is a number and anything else a string.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<Box<dyn ChatModel>, Box<dyn Error>> {
+        let value = serde_json::from_str::<serde_json::Value>(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        let previous = self.current.config.clone();
+        match self.current.config.as_object_mut() {
+            Some(config) => config.insert(key.to_string(), value),
+            None => return Err(format!("The config of model {} is not an object", self.current.id).into()),
+        };
+        let chat_model = self.create_model();
+        if chat_model.is_err() {
+            self.current.config = previous;
+        }
+        chat_model
+    }
+}
@@ -1,63 +1,197 @@
 use std::error::Error;
 use std::io::stdout;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
 use crossterm::{
     execute,
     style::{Color, Print, ResetColor, SetForegroundColor, SetAttribute, Attribute},
 };
 use futures::StreamExt;
 
-use crate::ai::chat_types::ChatCompletionStream;
+use crate::ai::chat_error::ChatModelError;
+use crate::ai::retry::{Retry, RetryListener};
+use crate::ai::chat_types::{ChatCompletionChunk, ChatCompletionStream, ModelUsage};
+use crate::ai::chat_types::Role;
+use crate::config::user::usage::{UsageRecord, UsageTotals};
+use crate::execution::sessions::Session;
+use super::attachments::AttachmentSet;
+use super::chat_log::ChatLogEntry;
+use super::markdown::MarkdownStream;
 
 pub struct TerminalRenderer {
+    /// Render responses as formatted markdown rather than writing them as-is.
+    markdown: bool,
+}
 
+#[derive(Default)]
+pub struct RenderedResponse {
+    pub content: String,
+    /// The user cancelled the response with Ctrl-C before it finished.
+    pub interrupted: bool,
+    /// The model the provider reported producing the response.
+    pub model: Option<String>,
+    /// Token usage, when the provider reported it.
+    pub usage: Option<ModelUsage>,
+}
+
+impl RenderedResponse {
+    /// Adds a streamed chunk to the response, returning the text it added.
+    pub fn add_chunk(&mut self, chunk: ChatCompletionChunk) -> String {
+        if !chunk.model.is_empty() {
+            self.model = Some(chunk.model);
+        }
+        // Providers report usage once, on the last chunk; older
+        // recordings may carry zeroed placeholders instead
+        if let Some(usage) = chunk.usage.filter(|usage| usage.total_tokens > 0) {
+            self.usage = Some(usage);
+        }
+        let text = chunk.choices.into_iter()
+            .filter_map(|choice| choice.delta.content)
+            .collect::<String>();
+        self.content.push_str(&text);
+        text
+    }
 }
 
 impl TerminalRenderer {
 
+    /// Formats markdown only when writing to a terminal, so piped output stays raw.
     pub fn new() -> Self {
-        TerminalRenderer {  }
+        TerminalRenderer { markdown: stdout().is_terminal() }
     }
 
-    pub fn render_user(&mut self, _: &str) {
-        self.print_entity("You", Color::Cyan);
+    pub fn set_raw(&mut self, raw: bool) {
+        self.markdown = self.markdown && !raw;
     }
 
-    pub async fn render_stream(&mut self, mut stream: ChatCompletionStream) -> Result<String, Box<dyn Error>> {
-        let mut response_string = String::new();
+    /// Streams the response to the terminal until it completes or the user
+    /// presses Ctrl-C, in which case the partial response is returned.
+    pub async fn render_stream(&mut self, mut stream: ChatCompletionStream) -> Result<RenderedResponse, Box<dyn Error>> {
+        let mut response = RenderedResponse::default();
         let mut lock = stdout().lock();
 
         self.print_entity("AI ", Color::Green);
+        let mut markdown = if self.markdown {
+            // Formatted responses start on their own line so headings and code line up
+            writeln!(lock)?;
+            let width = crossterm::terminal::size().map(|(columns, _)| columns as usize).unwrap_or(80);
+            Some(MarkdownStream::new(width))
+        } else {
+            None
+        };
 
-        while let Some(result) = stream.next().await {
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            let result = tokio::select! {
+                result = stream.next() => match result {
+                    Some(result) => result,
+                    None => break,
+                },
+                _ = &mut ctrl_c => {
+                    // Dropping the stream closes the connection to the provider
+                    response.interrupted = true;
+                    break;
+                }
+            };
             match result {
-                Ok(response) => {
-                    for chat_choice in &response.choices {
-                        if let Some(content) = &chat_choice.delta.content {
-                            write!(lock, "{}", content)?;
-                            response_string.push_str(content);
-                        }
+                Ok(chunk) => {
+                    let content = response.add_chunk(chunk);
+                    match &mut markdown {
+                        Some(markdown) => write!(lock, "{}", markdown.push(&content))?,
+                        None => write!(lock, "{}", content)?,
                     }
                 },
                 Err(err) => {
-                    self.print_error(&mut lock, err.to_string())
+                    self.print_error(err.as_ref())
                 }
             }
             stdout().flush()?;
         }
 
-        println!("\n");
-        Ok(response_string)
+        if let Some(markdown) = &mut markdown {
+            write!(lock, "{}", markdown.finish())?;
+        }
+        if response.interrupted {
+            execute!(lock, SetForegroundColor(Color::DarkYellow), Print(" [interrupted]"), ResetColor)?;
+        }
+        println!();
+        Ok(response)
+    }
+
+    /// Prints the token count and cost of a response below it, followed by
+    /// the blank line that separates turns.
+    pub fn print_usage(&mut self, record: &UsageRecord, session: &UsageTotals) {
+        let estimate = if record.estimated { "~" } else { "" };
+        let mut line = format!("[{}{} in / {}{} out tokens",
+            estimate, record.usage.prompt_tokens, estimate, record.usage.completion_tokens);
+        if let Some(cost) = record.cost {
+            line.push_str(&format!(" · ${:.4} · session ${:.4}", cost, session.cost));
+        }
+        line.push(']');
+        execute!(std::io::stdout(), SetForegroundColor(Color::DarkGrey), Print(format!("{}\n\n", line)), ResetColor).unwrap();
     }
     pub fn print_entity(&mut self, entity: &str, color: Color) {
         execute!(std::io::stdout(), SetForegroundColor(color), SetAttribute(Attribute::Bold), Print(format!("{}: ", entity)), ResetColor).unwrap();
     }
 
-    pub fn print_error(&mut self, lock: &mut std::io::StdoutLock, err: String) {
-        execute!(lock,
+    /// Reminds the user where a resumed conversation left off.
+    pub fn print_resumed(&mut self, session: &Session) {
+        self.print_notice(&format!("Resumed \"{}\" ({} messages)",
+            session.title.as_deref().unwrap_or("untitled session"), session.messages.len()));
+        let last_question = session.messages.iter().rposition(|entry| entry.message.role == Role::User && !entry.attachment);
+        self.print_messages(&session.messages[last_question.unwrap_or(session.messages.len())..]);
+    }
+
+    /// Reprints messages already in the conversation, such as a branch that was switched to.
+    pub fn print_messages(&mut self, entries: &[ChatLogEntry]) {
+        for entry in entries {
+            match entry.message.role {
+                Role::User if entry.attachment => continue,
+                Role::User => self.print_entity("You", Color::Cyan),
+                Role::Assistant => self.print_entity("AI ", Color::Green),
+                Role::System => continue,
+            }
+            println!("{}\n", entry.message.content);
+        }
+    }
+
+    /// Lists the files attached to the conversation and the ones that were left out.
+    pub fn print_attachments(&mut self, set: &AttachmentSet) {
+        let mut lines = set.attached.iter()
+            .map(|attachment| format!("Attached {} (~{} tokens)", attachment.path.display(), attachment.tokens))
+            .collect::<Vec<_>>();
+        lines.extend(set.skipped.iter().map(|(path, reason)| format!("Skipped {}: {}", path.display(), reason)));
+        if set.attached.len() > 1 {
+            lines.push(format!("{} files, ~{} tokens", set.attached.len(), set.tokens()));
+        }
+        self.print_notice(&lines.join("\n"));
+    }
+
+    pub fn print_notice(&mut self, notice: &str) {
+        execute!(std::io::stdout(), SetForegroundColor(Color::DarkYellow), Print(format!("{}\n\n", notice)), ResetColor).unwrap();
+    }
+
+    /// Shows each retry on its own line, styled like a notice, while the
+    /// request waits to try again.
+    pub fn retry_listener() -> RetryListener {
+        Arc::new(|retry: &Retry| {
+            execute!(std::io::stdout(), SetForegroundColor(Color::DarkYellow), Print(format!("{}\n", retry)), ResetColor).ok();
+        })
+    }
+
+    pub fn print_error(&mut self, err: &(dyn Error + 'static)) {
+        execute!(std::io::stdout(),
                 SetForegroundColor(Color::Red),
-                Print(format!("error: {}", err)),
+                Print(format!("error: {}\n", err)),
                 ResetColor)
                 .unwrap();
+        if let Some(model_error) = err.downcast_ref::<ChatModelError>() {
+            execute!(std::io::stdout(),
+                    SetForegroundColor(Color::DarkYellow),
+                    Print(format!("{}\n", model_error.hint())),
+                    ResetColor)
+                    .unwrap();
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,88 @@
+use std::error::Error;
+
+use crate::ai::chat_types::{ChatCompletionRequestMessage, ModelUsage};
+use crate::ai::tokens;
+use crate::config::ConfigTrait;
+use crate::config::user::settings::PriceTable;
+use crate::config::user::usage::{UsageLedger, UsageRecord, UsageTotals};
+
+/// Prices each response and keeps the session's running totals, adding them
+/// to the usage ledger as they come in.
+pub struct UsageTracker {
+    session_id: String,
+    model: String,
+    pricing: PriceTable,
+    session: UsageTotals,
+    persist: bool,
+}
+
+impl UsageTracker {
+    /// `model` is the configured model name, used when a response does not say
+    /// which model produced it.
+    pub fn new(session_id: String, model: String, pricing: PriceTable) -> UsageTracker {
+        UsageTracker { session_id, model, pricing, session: UsageTotals::default(), persist: true }
+    }
+
+    /// Starts counting a new session from zero.
+    pub fn start_session(&mut self, session_id: String) {
+        self.session_id = session_id;
+        self.session = UsageTotals::default();
+    }
+
+    /// Carries on counting a saved session from its totals.
+    pub fn continue_session(&mut self, session_id: String, totals: UsageTotals) {
+        self.session_id = session_id;
+        self.session = totals;
+    }
+
+    pub fn set_model(&mut self, model: String) {
+        self.model = model;
+    }
+
+    /// Continues the running totals of a resumed session.
+    pub fn with_session_totals(mut self, totals: UsageTotals) -> UsageTracker {
+        self.session = totals;
+        self
+    }
+
+    /// A tracker that keeps session totals without touching the ledger on disk.
+    #[cfg(test)]
+    pub fn in_memory(model: String, pricing: PriceTable) -> UsageTracker {
+        UsageTracker { persist: false, ..UsageTracker::new(String::new(), model, pricing) }
+    }
+
+    pub fn session(&self) -> &UsageTotals {
+        &self.session
+    }
+
+    /// Records a response, estimating the token counts from the conversation
+    /// when the provider did not report usage (or the response was cancelled
+    /// before it could).
+    pub fn record(
+        &mut self,
+        reported_model: Option<&str>,
+        reported_usage: Option<ModelUsage>,
+        messages: &[ChatCompletionRequestMessage],
+        completion: &str,
+    ) -> Result<UsageRecord, Box<dyn Error>> {
+        let model = reported_model.filter(|model| !model.is_empty()).unwrap_or(&self.model).to_string();
+        let (usage, estimated) = match reported_usage {
+            Some(usage) => (usage, false),
+            None => (tokens::estimate_usage(messages, completion), true),
+        };
+        let record = UsageRecord {
+            cost: self.pricing.find(&model).map(|pricing| pricing.cost(&usage)),
+            usage,
+            estimated,
+        };
+        self.session.add(&record);
+
+        if self.persist {
+            let day = chrono::Local::now().format("%Y-%m-%d").to_string();
+            let mut ledger = UsageLedger::load()?;
+            ledger.record(&day, &self.session_id, &model, &record);
+            ledger.write()?;
+        }
+        Ok(record)
+    }
+}
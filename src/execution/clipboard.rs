@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use base64::Engine;
+
+/// Clipboard tools tried in order, with the arguments that make them read stdin.
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("clip.exe", &[]),
+];
+
+/// Copies `text` to the system clipboard, returning how it was copied. Over
+/// SSH, or when no clipboard tool is installed, the text is sent to the
+/// terminal as an OSC 52 escape sequence, which most terminals turn into a
+/// clipboard write on the local machine.
+pub fn copy(text: &str) -> Result<&'static str, Box<dyn Error>> {
+    let remote = std::env::var_os("SSH_TTY").is_some() || std::env::var_os("SSH_CONNECTION").is_some();
+    if !remote {
+        for (program, args) in CLIPBOARD_COMMANDS {
+            if copy_with(program, args, text).is_ok() {
+                return Ok(program);
+            }
+        }
+    }
+    let mut stdout = std::io::stdout();
+    stdout.write_all(osc52_sequence(text, std::env::var_os("TMUX").is_some()).as_bytes())?;
+    stdout.flush()?;
+    Ok("OSC 52")
+}
+
+fn copy_with(program: &str, args: &[&str], text: &str) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().ok_or("clipboard stdin unavailable")?.write_all(text.as_bytes())?;
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed", program).into())
+    }
+}
+
+fn osc52_sequence(text: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64::engine::general_purpose::STANDARD.encode(text));
+    if tmux {
+        // tmux only forwards escape sequences wrapped in a passthrough
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence() {
+        assert_eq!(osc52_sequence("hi", false), "\x1b]52;c;aGk=\x07");
+        assert_eq!(osc52_sequence("hi", true), "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\");
+    }
+}
@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::ai::chat_types::Role;
+use crate::execution::chat_mode::chat_log::ChatLogEntry;
+use crate::execution::clipboard;
+use crate::execution::sessions::SessionStore;
+
+/// A fenced code block from a chat message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub code: String,
+}
+
+impl CodeBlock {
+    /// One line describing the block, e.g. `rust, 12 lines: fn main() {`.
+    pub fn summary(&self) -> String {
+        let lines = self.code.lines().count();
+        let first_line = self.code.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or("");
+        format!("{}, {} line{}: {}",
+            self.language.as_deref().unwrap_or("text"),
+            lines,
+            if lines == 1 { "" } else { "s" },
+            first_line)
+    }
+}
+
+/// Finds the fenced code blocks in `text`. A fence is closed by one at least
+/// as long using the same character, and a block cut off by the end of the
+/// message (e.g. an interrupted response) runs to the end.
+pub fn extract_code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(char, usize, CodeBlock)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let fence = trimmed.chars().next()
+            .filter(|c| *c == '`' || *c == '~')
+            .map(|c| (c, trimmed.chars().take_while(|next| *next == c).count()))
+            .filter(|(_, length)| *length >= 3);
+
+        match (&mut open, fence) {
+            (Some((open_char, open_length, _)), Some((c, length)))
+                if c == *open_char && length >= *open_length && trimmed[length..].trim().is_empty() => {
+                if let Some((_, _, block)) = open.take() {
+                    blocks.push(block);
+                }
+            }
+            (Some((_, _, block)), _) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+            }
+            (None, Some((c, length))) => {
+                let language = trimmed[length..].split_whitespace().next().map(|language| language.to_string());
+                open = Some((c, length, CodeBlock { language, code: String::new() }));
+            }
+            (None, None) => {}
+        }
+    }
+    if let Some((_, _, block)) = open {
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// The code blocks of the most recent assistant message.
+pub fn last_answer_blocks(chat_log: &[ChatLogEntry]) -> Vec<CodeBlock> {
+    chat_log.iter()
+        .rev()
+        .find(|entry| entry.message.role == Role::Assistant)
+        .map(|entry| extract_code_blocks(&entry.message.content))
+        .unwrap_or_default()
+}
+
+/// Picks block `number` (counting from 1), or the only block when no number is given.
+pub fn select_block(blocks: &[CodeBlock], number: Option<usize>) -> Result<&CodeBlock, Box<dyn Error>> {
+    match (number, blocks.len()) {
+        (_, 0) => Err("The last answer has no code blocks".into()),
+        (None, 1) => Ok(&blocks[0]),
+        (None, count) => Err(format!("The last answer has {} code blocks; pick one by number", count).into()),
+        (Some(number), count) => number.checked_sub(1)
+            .and_then(|index| blocks.get(index))
+            .ok_or_else(|| format!("There is no code block {}; the last answer has {}", number, count).into()),
+    }
+}
+
+pub fn format_block_list(blocks: &[CodeBlock]) -> String {
+    blocks.iter()
+        .enumerate()
+        .map(|(index, block)| format!("{}. {}", index + 1, block.summary()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn copy_block(block: &CodeBlock) -> Result<&'static str, Box<dyn Error>> {
+    clipboard::copy(&block.code)
+}
+
+pub fn write_block(block: &CodeBlock, path: &Path) -> Result<(), Box<dyn Error>> {
+    std::fs::write(path, &block.code).map_err(|e| format!("Failed to write {}: {}", path.display(), e).into())
+}
+
+pub struct LastCodeOptions {
+    /// Session to read, by id or `last`.
+    pub session: String,
+    pub number: Option<usize>,
+    pub copy: bool,
+    pub output: Option<PathBuf>,
+}
+
+/// Lists the code blocks of the last answer in a saved session, or prints,
+/// copies or writes one of them.
+pub fn run_last_code(options: LastCodeOptions) -> Result<(), Box<dyn Error>> {
+    let session = SessionStore::default_location()?.load(&options.session)?;
+    let blocks = last_answer_blocks(&session.messages);
+    if options.number.is_none() && !options.copy && options.output.is_none() {
+        if blocks.is_empty() {
+            return Err("The last answer has no code blocks".into());
+        }
+        println!("{}", format_block_list(&blocks));
+        return Ok(());
+    }
+
+    let block = select_block(&blocks, options.number)?;
+    if options.copy {
+        let method = copy_block(block)?;
+        eprintln!("Copied {} to the clipboard ({})", block.summary(), method);
+    }
+    if let Some(path) = &options.output {
+        write_block(block, path)?;
+        eprintln!("Wrote {}", path.display());
+    }
+    if !options.copy && options.output.is_none() {
+        // Raw so it can be piped
+        print!("{}", block.code);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = "Run this:\n\n```bash\ncargo build\n```\n\nThen change `main.rs`:\n\n\
+````rust\nfn main() {\n    println!(\"```\");\n}\n````\n\n~~~\nplain\n~~~\n\n```python\nprint('cut off')";
+
+    #[test]
+    fn test_extracts_fenced_blocks() {
+        let blocks = extract_code_blocks(ANSWER);
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0], CodeBlock { language: Some(String::from("bash")), code: String::from("cargo build\n") });
+        assert_eq!(blocks[1].code, "fn main() {\n    println!(\"```\");\n}\n");
+        assert_eq!(blocks[2].language, None);
+        assert_eq!(blocks[3].code, "print('cut off')\n");
+        assert_eq!(blocks[1].summary(), "rust, 3 lines: fn main() {");
+    }
+
+    #[test]
+    fn test_selects_blocks_from_the_last_answer() {
+        let chat_log = vec![
+            ChatLogEntry::new(Role::Assistant, ANSWER.to_string()),
+            ChatLogEntry::new(Role::User, String::from("```\nnot an answer\n```")),
+            ChatLogEntry::new(Role::Assistant, String::from("```sh\nls\n```")),
+        ];
+        let blocks = last_answer_blocks(&chat_log);
+        assert_eq!(select_block(&blocks, None).expect("only block").code, "ls\n");
+        assert!(select_block(&blocks, Some(2)).is_err());
+        assert!(select_block(&extract_code_blocks(ANSWER), None).is_err());
+        assert_eq!(select_block(&extract_code_blocks(ANSWER), Some(1)).expect("first").code, "cargo build\n");
+    }
+}
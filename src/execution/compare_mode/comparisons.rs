@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// One model's answer in a comparison and what it took to produce.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Answer {
+    /// The configured model id.
+    pub model_id: String,
+    /// The model the provider reported producing the answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub content: String,
+    /// Milliseconds until the first text arrived.
+    #[serde(default)]
+    pub first_token_ms: Option<u64>,
+    /// Milliseconds until the answer was complete.
+    pub total_ms: u64,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// The token counts are a local estimate because the provider did not report them.
+    #[serde(default)]
+    pub estimated: bool,
+    /// Cost in USD, or `None` when the model is missing from the price table.
+    #[serde(default)]
+    pub cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A prompt sent to several models, their answers and the one the user preferred.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Comparison {
+    pub id: String,
+    pub created_at: String,
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    pub answers: Vec<Answer>,
+    /// The model id of the preferred answer, when the user picked one.
+    #[serde(default)]
+    pub preferred: Option<String>,
+}
+
+impl Comparison {
+    pub fn new(prompt: String, system_prompt: Option<String>, answers: Vec<Answer>) -> Comparison {
+        let now = chrono::Local::now();
+        Comparison {
+            id: now.format("%Y%m%d-%H%M%S").to_string(),
+            created_at: now.to_rfc3339(),
+            prompt,
+            system_prompt,
+            answers,
+            preferred: None,
+        }
+    }
+}
+
+/// Every comparison run, one JSON object per line, so the votes can be
+/// totalled up when choosing a model.
+pub struct ComparisonLog {
+    path: PathBuf,
+}
+
+impl ComparisonLog {
+    pub fn new(path: PathBuf) -> ComparisonLog {
+        ComparisonLog { path }
+    }
+
+    pub fn default_location() -> Result<ComparisonLog, Box<dyn Error>> {
+        let home = dirs::home_dir().ok_or("Could not find the user's home directory")?;
+        Ok(ComparisonLog::new(home.join(".k-aiti").join("comparisons.jsonl")))
+    }
+
+    pub fn append(&self, comparison: &Comparison) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(comparison)?)?;
+        Ok(())
+    }
+
+    /// The comparisons logged so far, oldest first. Lines that cannot be read
+    /// are skipped so one bad entry does not hide the rest.
+    pub fn read(&self) -> Result<Vec<Comparison>, Box<dyn Error>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(fs::read_to_string(&self.path)?
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+/// How one model has done across the logged comparisons.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelStats {
+    pub model_id: String,
+    pub comparisons: u32,
+    /// Comparisons including this model where the user picked an answer.
+    pub votes: u32,
+    pub wins: u32,
+    pub failures: u32,
+    /// Average milliseconds to the first text, over the answers that had any.
+    pub first_token_ms: Option<u64>,
+    /// Average milliseconds to a complete answer, over the successful answers.
+    pub total_ms: Option<u64>,
+    pub average_completion_tokens: Option<u32>,
+    pub cost: f64,
+}
+
+/// Totals each model's results, most wins first.
+pub fn tally(comparisons: &[Comparison]) -> Vec<ModelStats> {
+    #[derive(Default)]
+    struct Sums {
+        stats: ModelStats,
+        first_token: Vec<u64>,
+        total: Vec<u64>,
+        completion_tokens: Vec<u64>,
+    }
+
+    let mut models: BTreeMap<&str, Sums> = BTreeMap::new();
+    for comparison in comparisons {
+        for answer in &comparison.answers {
+            let sums = models.entry(&answer.model_id).or_default();
+            sums.stats.comparisons += 1;
+            if let Some(preferred) = &comparison.preferred {
+                sums.stats.votes += 1;
+                if *preferred == answer.model_id {
+                    sums.stats.wins += 1;
+                }
+            }
+            sums.stats.cost += answer.cost.unwrap_or_default();
+            if answer.error.is_some() {
+                sums.stats.failures += 1;
+                continue;
+            }
+            sums.first_token.extend(answer.first_token_ms);
+            sums.total.push(answer.total_ms);
+            sums.completion_tokens.push(answer.completion_tokens as u64);
+        }
+    }
+
+    let average = |values: &[u64]| (!values.is_empty()).then(|| values.iter().sum::<u64>() / values.len() as u64);
+    let mut stats = models.into_iter()
+        .map(|(model_id, sums)| ModelStats {
+            model_id: model_id.to_string(),
+            first_token_ms: average(&sums.first_token),
+            total_ms: average(&sums.total),
+            average_completion_tokens: average(&sums.completion_tokens).map(|tokens| tokens as u32),
+            ..sums.stats
+        })
+        .collect::<Vec<_>>();
+    stats.sort_by(|a, b| b.wins.cmp(&a.wins).then(a.model_id.cmp(&b.model_id)));
+    stats
+}
+
+fn format_ms(ms: Option<u64>) -> String {
+    ms.map(|ms| format!("{:.1}s", ms as f64 / 1000.0)).unwrap_or_else(|| String::from("-"))
+}
+
+pub fn format_stats(stats: &[ModelStats]) -> String {
+    if stats.is_empty() {
+        return String::from("No comparisons yet; run `kaiti compare --models <a,b> \"prompt\"`\n");
+    }
+    let mut report = format!("{:<16} {:>9} {:>6} {:>7} {:>12} {:>8} {:>11} {:>10}\n",
+        "Model", "Wins", "Runs", "Failed", "First token", "Total", "Out tokens", "Cost");
+    for model in stats {
+        report.push_str(&format!("{:<16} {:>9} {:>6} {:>7} {:>12} {:>8} {:>11} {:>10}\n",
+            model.model_id,
+            format!("{}/{}", model.wins, model.votes),
+            model.comparisons,
+            model.failures,
+            format_ms(model.first_token_ms),
+            format_ms(model.total_ms),
+            model.average_completion_tokens.map(|tokens| tokens.to_string()).unwrap_or_else(|| String::from("-")),
+            format!("${:.4}", model.cost)));
+    }
+    report.push_str("\nWins count the comparisons where the model's answer was picked, out of those voted on.\nTimes and output tokens are averages over successful answers.\n");
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(model_id: &str, first_token_ms: u64, total_ms: u64, error: Option<&str>) -> Answer {
+        Answer {
+            model_id: model_id.to_string(),
+            first_token_ms: Some(first_token_ms),
+            total_ms,
+            completion_tokens: 100,
+            cost: Some(0.01),
+            error: error.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_log_round_trip_and_tally() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = ComparisonLog::new(dir.path().join("comparisons.jsonl"));
+        assert!(log.read().unwrap().is_empty());
+
+        let mut first = Comparison::new(String::from("q1"), None, vec![answer("chatgpt", 400, 2000, None), answer("claude", 600, 3000, None)]);
+        first.preferred = Some(String::from("claude"));
+        let second = Comparison::new(String::from("q2"), None, vec![answer("chatgpt", 200, 1000, None), answer("claude", 0, 10, Some("timed out"))]);
+        log.append(&first).unwrap();
+        log.append(&second).unwrap();
+        assert_eq!(log.read().unwrap(), vec![first, second]);
+
+        let stats = tally(&log.read().unwrap());
+        assert_eq!(stats[0].model_id, "claude");
+        assert_eq!((stats[0].wins, stats[0].votes, stats[0].comparisons, stats[0].failures), (1, 1, 2, 1));
+        assert_eq!(stats[0].total_ms, Some(3000));
+        assert_eq!((stats[1].wins, stats[1].votes, stats[1].comparisons), (0, 1, 2));
+        assert_eq!(stats[1].first_token_ms, Some(300));
+        assert!((stats[1].cost - 0.02).abs() < 1e-12);
+        assert!(format_stats(&stats).contains("claude                 1/1      2       1         0.6s     3.0s         100    $0.0200"));
+    }
+}
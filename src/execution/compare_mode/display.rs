@@ -0,0 +1,321 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+use crossterm::{
+    cursor::MoveUp,
+    queue,
+    terminal::{Clear, ClearType},
+};
+
+use crate::execution::chat_mode::markdown::MarkdownStream;
+use crate::execution::ui::wrap;
+use super::comparisons::Answer;
+
+const COLUMN_GAP: &str = " │ ";
+
+/// Progress from one of the models being compared, by its position in the list.
+pub enum Update {
+    Text(usize, String),
+    /// A failed attempt is being retried.
+    Retry(usize, String),
+    Done(usize, Answer),
+}
+
+/// Where answers go as they stream in.
+pub enum Display {
+    /// One labelled section per model, in order. The first model's answer
+    /// streams live while the others are buffered until their turn.
+    Sections(Box<Sections>),
+    /// A live progress line per model, then the answers side by side.
+    Columns(Columns),
+}
+
+impl Display {
+    pub fn start<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match self {
+            Display::Sections(sections) => sections.start(out),
+            Display::Columns(columns) => columns.draw_progress(out),
+        }
+    }
+
+    pub fn update<W: Write>(&mut self, update: Update, out: &mut W) -> io::Result<()> {
+        match self {
+            Display::Sections(sections) => sections.update(update, out),
+            Display::Columns(columns) => columns.update(update, out),
+        }
+    }
+
+    pub fn finish<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match self {
+            Display::Sections(_) => Ok(()),
+            Display::Columns(columns) => columns.finish(out),
+        }
+    }
+}
+
+/// The timings, tokens and cost shown under an answer.
+pub fn summary(answer: &Answer) -> String {
+    if let Some(error) = &answer.error {
+        return format!("failed after {:.1}s: {}", answer.total_ms as f64 / 1000.0, error);
+    }
+    let estimate = if answer.estimated { "~" } else { "" };
+    let mut parts = Vec::new();
+    parts.extend(answer.model.clone());
+    if let Some(first_token_ms) = answer.first_token_ms {
+        parts.push(format!("{:.1}s to first token", first_token_ms as f64 / 1000.0));
+    }
+    parts.push(format!("{:.1}s total", answer.total_ms as f64 / 1000.0));
+    parts.push(format!("{}{} in / {}{} out tokens", estimate, answer.prompt_tokens, estimate, answer.completion_tokens));
+    if let Some(cost) = answer.cost {
+        parts.push(format!("${:.4}", cost));
+    }
+    parts.join(" · ")
+}
+
+pub struct Sections {
+    labels: Vec<String>,
+    buffers: Vec<String>,
+    done: Vec<Option<Answer>>,
+    /// The section being written.
+    current: usize,
+    /// Renders markdown to this width when writing to a terminal.
+    width: Option<usize>,
+    markdown: Option<MarkdownStream>,
+}
+
+impl Sections {
+    pub fn new(labels: Vec<String>, width: Option<usize>) -> Sections {
+        let count = labels.len();
+        Sections {
+            labels,
+            buffers: vec![String::new(); count],
+            done: vec![None; count],
+            current: 0,
+            width,
+            markdown: None,
+        }
+    }
+
+    fn start<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.open_section(out)
+    }
+
+    fn update<W: Write>(&mut self, update: Update, out: &mut W) -> io::Result<()> {
+        match update {
+            Update::Text(index, text) => {
+                self.buffers[index].push_str(&text);
+                if index == self.current {
+                    self.write(&text, out)?;
+                }
+            }
+            // Only shown for the section being written, before its answer starts
+            Update::Retry(index, retry) => {
+                if index == self.current && self.buffers[index].is_empty() {
+                    writeln!(out, "[{}]", retry)?;
+                }
+            }
+            Update::Done(index, answer) => {
+                self.done[index] = Some(answer);
+                while self.current < self.labels.len() && self.done[self.current].is_some() {
+                    self.close_section(out)?;
+                    self.current += 1;
+                    if self.current < self.labels.len() {
+                        self.open_section(out)?;
+                    }
+                }
+            }
+        }
+        out.flush()
+    }
+
+    fn open_section<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "── {} ──", self.labels[self.current])?;
+        self.markdown = self.width.map(MarkdownStream::new);
+        // Whatever arrived while earlier sections were being written
+        let buffered = self.buffers[self.current].clone();
+        self.write(&buffered, out)
+    }
+
+    fn write<W: Write>(&mut self, text: &str, out: &mut W) -> io::Result<()> {
+        match &mut self.markdown {
+            Some(markdown) => write!(out, "{}", markdown.push(text)),
+            None => write!(out, "{}", text),
+        }
+    }
+
+    fn close_section<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if let Some(markdown) = &mut self.markdown {
+            write!(out, "{}", markdown.finish())?;
+        }
+        let buffer = &self.buffers[self.current];
+        if !buffer.is_empty() && !buffer.ends_with('\n') {
+            writeln!(out)?;
+        }
+        if let Some(answer) = &self.done[self.current] {
+            writeln!(out, "[{}]", summary(answer))?;
+        }
+        writeln!(out)
+    }
+}
+
+pub struct Columns {
+    labels: Vec<String>,
+    received: Vec<usize>,
+    /// The last retry of a model that has not sent anything yet.
+    retries: Vec<Option<String>>,
+    answers: Vec<Option<Answer>>,
+    started: Instant,
+    width: usize,
+    /// Redraws the progress lines in place; off when not writing to a terminal.
+    live: bool,
+    drawn: bool,
+}
+
+impl Columns {
+    pub fn new(labels: Vec<String>, width: usize, live: bool) -> Columns {
+        let count = labels.len();
+        Columns {
+            labels,
+            received: vec![0; count],
+            retries: vec![None; count],
+            answers: vec![None; count],
+            started: Instant::now(),
+            width,
+            live,
+            drawn: false,
+        }
+    }
+
+    fn update<W: Write>(&mut self, update: Update, out: &mut W) -> io::Result<()> {
+        match update {
+            Update::Text(index, text) => self.received[index] += text.chars().count(),
+            Update::Retry(index, retry) => self.retries[index] = Some(retry),
+            Update::Done(index, answer) => self.answers[index] = Some(answer),
+        }
+        self.draw_progress(out)
+    }
+
+    fn draw_progress<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if !self.live {
+            return Ok(());
+        }
+        if self.drawn {
+            queue!(out, MoveUp(self.labels.len() as u16))?;
+        }
+        for (index, label) in self.labels.iter().enumerate() {
+            let status = match &self.answers[index] {
+                Some(answer) if answer.error.is_some() => String::from("failed"),
+                Some(answer) => format!("done in {:.1}s", answer.total_ms as f64 / 1000.0),
+                None => match &self.retries[index] {
+                    Some(retry) if self.received[index] == 0 => format!("{:.1}s, {}", self.started.elapsed().as_secs_f64(), retry),
+                    _ => format!("{:.1}s, {} characters so far", self.started.elapsed().as_secs_f64(), self.received[index]),
+                },
+            };
+            queue!(out, Clear(ClearType::CurrentLine))?;
+            writeln!(out, "{:<24} {}", label, status)?;
+        }
+        self.drawn = true;
+        out.flush()
+    }
+
+    fn finish<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.drawn {
+            queue!(out, MoveUp(self.labels.len() as u16), Clear(ClearType::FromCursorDown))?;
+        }
+        let answers = self.answers.iter().map(|answer| answer.clone().unwrap_or_default()).collect::<Vec<_>>();
+        write!(out, "{}", columns(&self.labels, &answers, self.width))?;
+        out.flush()
+    }
+}
+
+/// The answers side by side, each wrapped to its share of `width`.
+pub fn columns(labels: &[String], answers: &[Answer], width: usize) -> String {
+    let count = labels.len().max(1);
+    let column_width = (width.saturating_sub(COLUMN_GAP.chars().count() * (count - 1)) / count).max(10);
+    let wrap_all = |text: &str| text.lines().flat_map(|line| wrap(line, column_width)).collect::<Vec<_>>();
+
+    let headers = labels.iter().map(|label| wrap(label, column_width)).collect::<Vec<_>>();
+    let bodies = answers.iter().map(|answer| wrap_all(&answer.content)).collect::<Vec<_>>();
+    let footers = answers.iter().map(|answer| wrap_all(&format!("[{}]", summary(answer)))).collect::<Vec<_>>();
+    let rule = vec![vec!["─".repeat(column_width)]; count];
+    let blank = vec![Vec::new(); count];
+
+    let mut output = String::new();
+    for block in [&headers, &rule, &bodies, &blank, &footers] {
+        let height = block.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        for row in 0..height {
+            let cells = block.iter()
+                .map(|lines| format!("{:<width$}", lines.get(row).map(String::as_str).unwrap_or(""), width = column_width))
+                .collect::<Vec<_>>();
+            output.push_str(cells.join(COLUMN_GAP).trim_end());
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(model_id: &str, content: &str) -> Answer {
+        Answer {
+            model_id: model_id.to_string(),
+            content: content.to_string(),
+            first_token_ms: Some(500),
+            total_ms: 1500,
+            prompt_tokens: 10,
+            completion_tokens: 4,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sections_buffer_later_models_until_their_turn() {
+        let labels = vec![String::from("1. fast"), String::from("2. slow")];
+        let mut display = Display::Sections(Box::new(Sections::new(labels, None)));
+        let mut out = Vec::new();
+        display.start(&mut out).unwrap();
+        display.update(Update::Text(1, String::from("Second ")), &mut out).unwrap();
+        display.update(Update::Text(0, String::from("First")), &mut out).unwrap();
+        assert_eq!(String::from_utf8_lossy(&out), "── 1. fast ──\nFirst");
+
+        display.update(Update::Text(1, String::from("answer")), &mut out).unwrap();
+        display.update(Update::Done(1, answer("slow", "Second answer")), &mut out).unwrap();
+        display.update(Update::Done(0, answer("fast", "First")), &mut out).unwrap();
+        display.finish(&mut out).unwrap();
+        assert_eq!(String::from_utf8_lossy(&out),
+            "── 1. fast ──\nFirst\n[0.5s to first token · 1.5s total · 10 in / 4 out tokens]\n\n\
+             ── 2. slow ──\nSecond answer\n[0.5s to first token · 1.5s total · 10 in / 4 out tokens]\n\n");
+    }
+
+    #[test]
+    fn test_sections_show_retries_only_before_the_live_answer() {
+        let labels = vec![String::from("1. fast"), String::from("2. slow")];
+        let mut display = Display::Sections(Box::new(Sections::new(labels, None)));
+        let mut out = Vec::new();
+        display.start(&mut out).unwrap();
+        display.update(Update::Retry(0, String::from("rate limited; retrying in 1.0s (1/3)")), &mut out).unwrap();
+        display.update(Update::Retry(1, String::from("rate limited; retrying in 1.0s (1/3)")), &mut out).unwrap();
+        display.update(Update::Text(0, String::from("First")), &mut out).unwrap();
+        display.update(Update::Retry(0, String::from("rate limited; retrying in 2.0s (2/3)")), &mut out).unwrap();
+        assert_eq!(String::from_utf8_lossy(&out), "── 1. fast ──\n[rate limited; retrying in 1.0s (1/3)]\nFirst");
+    }
+
+    #[test]
+    fn test_columns_line_up_answers() {
+        let labels = vec![String::from("1. a"), String::from("2. b")];
+        let failed = Answer { error: Some(String::from("401 Unauthorized")), ..answer("b", "") };
+        let output = columns(&labels, &[answer("a", "one two three"), failed], 23);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..6], [
+            "1. a       │ 2. b",
+            "────────── │ ──────────",
+            "one two    │",
+            "three      │",
+            "           │",
+            "[0.5s to   │ [failed",
+        ]);
+        assert_eq!(lines.len(), 12);
+    }
+}
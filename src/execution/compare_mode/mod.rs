@@ -0,0 +1,316 @@
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::future::join_all;
+use futures::StreamExt;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::{config_values, tokens};
+use crate::config::user::settings::{ModelConfig, PriceTable};
+use crate::execution::ask_mode::{self, UsageError};
+use crate::models::global_registry;
+use comparisons::{Answer, Comparison, ComparisonLog};
+use display::{Columns, Display, Sections, Update};
+
+mod comparisons;
+mod display;
+
+/// Narrowest column worth laying answers out side by side in.
+const MIN_COLUMN_WIDTH: usize = 36;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Columns,
+    Sections,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "columns" => Ok(Layout::Columns),
+            "sections" => Ok(Layout::Sections),
+            _ => Err(format!("Unknown layout {}; expected columns or sections", value)),
+        }
+    }
+}
+
+pub struct CompareOptions {
+    pub prompt: Option<String>,
+    /// Ids of the configured models to compare, in the order they are shown.
+    pub models: Vec<String>,
+    /// Defaults to columns when the terminal is wide enough and sections otherwise.
+    pub layout: Option<Layout>,
+    /// Sent to every model in place of their own configured system prompts,
+    /// so they all get the same request.
+    pub system_prompt: Option<String>,
+}
+
+/// Sends one prompt to several models at once and shows their answers
+/// together, then asks which was best and logs the comparison.
+pub async fn run_compare(models: &[ModelConfig], pricing: &PriceTable, options: CompareOptions) -> Result<(), Box<dyn Error>> {
+    let c_models = select_models(models, &options.models)?;
+    let context = ask_mode::read_piped_input()?;
+    let request = ask_mode::build_request(options.prompt.as_deref(), context.as_deref(), options.system_prompt.as_deref())?;
+    let chat_models = {
+        let registry = global_registry().read().map_err(|_| "model registry is poisoned")?;
+        c_models.iter().map(|c_model| registry.create_model(c_model)).collect::<Result<Vec<_>, _>>()?
+    };
+
+    let terminal = std::io::stdout().is_terminal();
+    let width = crossterm::terminal::size().map(|(columns, _)| columns as usize).unwrap_or(80);
+    let labels = c_models.iter().enumerate().map(|(index, c_model)| format!("{}. {}", index + 1, c_model.id)).collect::<Vec<_>>();
+    let layout = options.layout.unwrap_or(if terminal && width / c_models.len() >= MIN_COLUMN_WIDTH {
+        Layout::Columns
+    } else {
+        Layout::Sections
+    });
+    let mut display = match layout {
+        Layout::Columns => Display::Columns(Columns::new(labels, width, terminal)),
+        Layout::Sections => Display::Sections(Box::new(Sections::new(labels, terminal.then_some(width)))),
+    };
+
+    let (updates, mut received) = mpsc::unbounded_channel();
+    let answers = join_all(c_models.iter().zip(chat_models).enumerate()
+        .map(|(index, (c_model, chat_model))| stream_answer(index, c_model, chat_model, &request, pricing, updates.clone())));
+    // The display finishes once every model's sender is dropped
+    drop(updates);
+    let show = async {
+        let mut out = std::io::stdout();
+        display.start(&mut out)?;
+        while let Some(update) = received.recv().await {
+            display.update(update, &mut out)?;
+        }
+        display.finish(&mut out)
+    };
+    let (answers, shown) = tokio::join!(answers, show);
+    shown?;
+
+    let prompt = request.messages.last().map(|message| message.content.clone()).unwrap_or_default();
+    let mut comparison = Comparison::new(prompt, options.system_prompt, answers);
+    let answered = comparison.answers.iter().filter(|answer| answer.error.is_none()).count();
+    let interactive = terminal && std::io::stdin().is_terminal();
+    if interactive && answered > 1 {
+        comparison.preferred = ask_preference(&comparison.answers)?;
+    }
+    ComparisonLog::default_location()?.append(&comparison)?;
+    if interactive {
+        println!("Saved comparison {}; `kaiti compare --stats` totals the votes so far.", comparison.id);
+    }
+    Ok(())
+}
+
+/// Prints how each model has done across the logged comparisons.
+pub fn run_compare_stats() -> Result<(), Box<dyn Error>> {
+    let comparisons = ComparisonLog::default_location()?.read()?;
+    print!("{}", comparisons::format_stats(&comparisons::tally(&comparisons)));
+    Ok(())
+}
+
+fn select_models<'a>(models: &'a [ModelConfig], ids: &[String]) -> Result<Vec<&'a ModelConfig>, Box<dyn Error>> {
+    let mut selected: Vec<&ModelConfig> = Vec::new();
+    for id in ids {
+        let c_model = models.iter().find(|c_model| c_model.id == *id).ok_or_else(|| {
+            let available = models.iter().map(|c_model| c_model.id.as_str()).collect::<Vec<_>>().join(", ");
+            UsageError(format!("No model {} is configured; available models are {}", id, available))
+        })?;
+        if !selected.iter().any(|chosen| chosen.id == c_model.id) {
+            selected.push(c_model);
+        }
+    }
+    if selected.len() < 2 {
+        return Err(Box::new(UsageError(String::from("Name at least two models to compare, e.g. --models chatgpt,claude"))));
+    }
+    Ok(selected)
+}
+
+/// Streams one model's answer, passing text to the display as it arrives.
+/// Failures are kept in the answer so the other models carry on.
+async fn stream_answer(
+    index: usize,
+    c_model: &ModelConfig,
+    mut chat_model: Box<dyn ChatModel>,
+    request: &ChatModelRequest,
+    pricing: &PriceTable,
+    updates: UnboundedSender<Update>,
+) -> Answer {
+    let started = Instant::now();
+    let mut answer = Answer { model_id: c_model.id.clone(), ..Default::default() };
+    let retries = updates.clone();
+    chat_model.set_retry_listener(Arc::new(move |retry| {
+        let _ = retries.send(Update::Retry(index, retry.to_string()));
+    }));
+    let mut usage = None;
+    match chat_model.create_response_stream(request).await {
+        Ok(mut stream) => {
+            while let Some(chunk) = stream.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        answer.error = Some(e.to_string());
+                        break;
+                    }
+                };
+                if !chunk.model.is_empty() {
+                    answer.model = Some(chunk.model.clone());
+                }
+                if let Some(reported) = chunk.usage.filter(|usage| usage.total_tokens > 0) {
+                    usage = Some(reported);
+                }
+                for content in chunk.choices.iter().filter_map(|choice| choice.delta.content.as_deref()) {
+                    if answer.first_token_ms.is_none() && !content.is_empty() {
+                        answer.first_token_ms = Some(started.elapsed().as_millis() as u64);
+                    }
+                    answer.content.push_str(content);
+                    // The display only goes away once every answer is in
+                    let _ = updates.send(Update::Text(index, content.to_string()));
+                }
+            }
+        }
+        Err(e) => answer.error = Some(e.to_string()),
+    }
+    answer.total_ms = started.elapsed().as_millis() as u64;
+
+    let (usage, estimated) = match usage {
+        Some(usage) => (usage, false),
+        None => (tokens::estimate_usage(&request.messages, &answer.content), true),
+    };
+    let model_name = answer.model.clone()
+        .or_else(|| config_values::get_string(&c_model.config, "model"))
+        .unwrap_or_else(|| c_model.name.clone());
+    answer.cost = pricing.find(&model_name).map(|pricing| pricing.cost(&usage));
+    answer.prompt_tokens = usage.prompt_tokens;
+    answer.completion_tokens = usage.completion_tokens;
+    answer.estimated = estimated;
+    let _ = updates.send(Update::Done(index, answer.clone()));
+    answer
+}
+
+/// Asks which answer was best, by number or model id. Nothing is recorded
+/// when the user just presses Enter.
+fn ask_preference(answers: &[Answer]) -> Result<Option<String>, Box<dyn Error>> {
+    loop {
+        print!("Which answer was best? 1-{} or a model id, Enter to skip: ", answers.len());
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        match parse_preference(&input, answers) {
+            Ok(preferred) => return Ok(preferred),
+            Err(message) => println!("{}", message),
+        }
+    }
+}
+
+fn parse_preference(input: &str, answers: &[Answer]) -> Result<Option<String>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let answer = match input.parse::<usize>() {
+        Ok(number) => answers.get(number.wrapping_sub(1)),
+        Err(_) => answers.iter().find(|answer| answer.model_id == input),
+    };
+    match answer {
+        Some(answer) if answer.error.is_some() => Err(format!("{} did not answer", answer.model_id)),
+        Some(answer) => Ok(Some(answer.model_id.clone())),
+        None => Err(format!("{} is not one of the answers", input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::ai::chat_types::{ChatCompletionChoice, ChatCompletionChunk, ChatCompletionDelta, ChatCompletionStream, Role};
+
+    /// Streams a fixed answer in words, or fails to start.
+    struct WordsModel(Option<&'static str>);
+
+    #[async_trait]
+    impl ChatModel for WordsModel {
+        fn new(_config: serde_json::Value) -> Self {
+            WordsModel(None)
+        }
+
+        async fn create_response_stream(&mut self, _request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+            let text = self.0.ok_or("connection refused")?;
+            let chunks = text.split_inclusive(' ')
+                .map(|word| ChatCompletionChunk {
+                    id: None,
+                    object: String::from("chat.completion.chunk"),
+                    created: 0,
+                    model: String::from("words-1"),
+                    choices: vec![ChatCompletionChoice {
+                        index: 0,
+                        delta: ChatCompletionDelta { content: Some(word.to_string()), role: Some(Role::Assistant) },
+                        finish_reason: None,
+                    }],
+                    usage: None,
+                })
+                .collect::<Vec<_>>();
+            Ok(futures::stream::iter(chunks).map(Ok).boxed())
+        }
+    }
+
+    fn c_model(id: &str) -> ModelConfig {
+        ModelConfig { id: id.to_string(), name: id.to_string(), config: serde_json::json!({}) }
+    }
+
+    #[tokio::test]
+    async fn test_models_stream_concurrently_and_failures_are_kept() {
+        let request = ask_mode::build_request(Some("Which is faster?"), None, None).unwrap();
+        let (models, pricing) = ([c_model("a"), c_model("b")], PriceTable::default());
+        let (updates, mut received) = mpsc::unbounded_channel();
+        let answers = join_all(vec![
+            stream_answer(0, &models[0], Box::new(WordsModel(Some("It depends on the load"))), &request, &pricing, updates.clone()),
+            stream_answer(1, &models[1], Box::new(WordsModel(None)), &request, &pricing, updates.clone()),
+        ]).await;
+        drop(updates);
+
+        assert_eq!(answers[0].content, "It depends on the load");
+        assert_eq!(answers[0].model.as_deref(), Some("words-1"));
+        assert!(answers[0].estimated && answers[0].completion_tokens > 0 && answers[0].first_token_ms.is_some());
+        assert_eq!(answers[1].error.as_deref(), Some("connection refused"));
+
+        let mut texts = 0;
+        let mut done = Vec::new();
+        while let Some(update) = received.recv().await {
+            match update {
+                Update::Text(index, _) => texts += usize::from(index == 0),
+                Update::Done(index, _) => done.push(index),
+                Update::Retry(..) => {}
+            }
+        }
+        assert_eq!(texts, 5);
+        done.sort();
+        assert_eq!(done, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_selecting_models_and_preferences() {
+        let models = [c_model("chatgpt"), c_model("claude"), c_model("local")];
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let selected = select_models(&models, &ids(&["claude", "local", "claude"])).unwrap();
+        assert_eq!(selected.iter().map(|c_model| c_model.id.as_str()).collect::<Vec<_>>(), vec!["claude", "local"]);
+        let error = select_models(&models, &ids(&["gemini", "claude"])).err().unwrap();
+        assert_eq!(ask_mode::exit_code(error.as_ref()), ask_mode::EXIT_USAGE);
+        assert!(select_models(&models, &ids(&["claude"])).is_err());
+
+        let answers = [
+            Answer { model_id: String::from("claude"), ..Default::default() },
+            Answer { model_id: String::from("local"), error: Some(String::from("timeout")), ..Default::default() },
+        ];
+        assert_eq!(parse_preference("1\n", &answers), Ok(Some(String::from("claude"))));
+        assert_eq!(parse_preference("claude", &answers), Ok(Some(String::from("claude"))));
+        assert_eq!(parse_preference("\n", &answers), Ok(None));
+        assert!(parse_preference("2", &answers).is_err());
+        assert!(parse_preference("3", &answers).is_err());
+    }
+}
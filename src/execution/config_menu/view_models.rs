@@ -11,6 +11,7 @@ use tui::{
 };
 
 use crate::config::user::settings::SettingsConfig as Config;
+use crate::models::global_registry;
 use super::view_model;
 use super::super::ui::StatefulList;
 
@@ -20,13 +21,26 @@ pub fn draw_view_models(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, c
 
     let active_panel = 0;
 
-    // Initialize model list
-    // let models = vec!["Model 1", "Model 2", "Model 3", "Model 4"];
-    let model_names = config
-        .models
-        .iter()
-        .map(|model| ListItem::new(model.name.clone()))
-        .collect::<Vec<ListItem>>();
+    let (providers, model_names) = {
+        let registry = global_registry().read().map_err(|_| "model registry is poisoned")?;
+        let providers = registry
+            .provider_names()
+            .into_iter()
+            .map(ListItem::new)
+            .collect::<Vec<ListItem>>();
+
+        // Initialize model list
+        let model_names = config
+            .models
+            .iter()
+            .map(|model| if registry.is_registered(&model.name) {
+                ListItem::new(model.name.clone())
+            } else {
+                ListItem::new(format!("{} (unknown provider)", model.name))
+            })
+            .collect::<Vec<ListItem>>();
+        (providers, model_names)
+    };
 
     // let model_ids = config
     //     .models
@@ -78,8 +92,18 @@ pub fn draw_view_models(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, c
                 // .highlight_style(Style::default().bg(Color::Blue).fg(Color::White))
                 // .highlight_symbol("> ");
 
+            let providers_widget = List::new(providers.clone())
+                .block(Block::default().title("Providers").borders(Borders::ALL))
+                .style(Style::default().fg(Color::White));
+
+            let left_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(h_chunks[0]);
+
             frame.render_stateful_widget(models_widget, h_chunks[1], &mut models_list.state);
-            frame.render_stateful_widget(actions_widget, h_chunks[0], &mut actions_list.state);
+            frame.render_stateful_widget(actions_widget, left_chunks[0], &mut actions_list.state);
+            frame.render_widget(providers_widget, left_chunks[1]);
         })?;
 
         match event::read()? {
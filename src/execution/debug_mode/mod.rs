@@ -1,38 +1,192 @@
-// use std::error::Error;
-
-// use crate::ai::open_ai_gpt::GptClientRequest;
-// use crate::ai::{open_ai_gpt::GptClient, ChatClient};
-// use crate::render::terminal_renderer::TerminalRenderer;
-// use crate::terminal_capture::capture_instance;
-
-// pub async fn run_debug_mode(gpt_client: GptClient) -> Result<(), Box<dyn Error>> {
-//     let renderer = TerminalRenderer::new();
-//     let mut chat_client = ChatClient::new(gpt_client.clone(), renderer);
-//     let terminal = capture_instance();
-//     let output = terminal.capture_output();
-
-//     let mut messages = Vec::new();
-//     let message: &str = "You are the solveit-ai. solveit-ai is an ai that accepts console messages and prints actions based on certain conditions. \
-//         The supported conditions are: 1. \
-//         if the message involves missing dependencies print a list of commands to run. Only the commands listed in order. Say nothing else \
-//         if the message involves code errors, concisely explain the error, and list out the resolution steps. \
-//         all other messages can use a relevant and concise response on what to do next \
-//         these rules must be followed strictly. Do not break from these rules \
-//         To confirm this processes, say only \"Confirm\". If you do not say confirm, we will prompt this again and we will not move forward until you say \"Confirm\"";
-
-//     let prime_message = gpt_client.clone().create_user_message(message.to_string())?;
-//     messages.push(prime_message);
-
-//     let mut client_request = GptClientRequest { messages };
-//     loop {
-//         let response = gpt_client.clone().generate_response(&client_request).await;
-//         let result = response.unwrap();
-//         client_request.messages.push(gpt_client.clone().create_assistant_message(result.content.clone()).unwrap());
-//         if result.content.clone().contains("Confirm") {
-//             break;
-//         }
-//     }
-
-//     chat_client.render_response_with_custom_history(output.unwrap(), &mut client_request.messages).await?;
-//     Ok(())
-// }
\ No newline at end of file
+use std::error::Error;
+use std::io::IsTerminal;
+
+use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::chat_types::{ChatCompletionRequestMessage, Role};
+use crate::config::user::settings::{CaptureSettings, ModelConfig};
+use crate::execution::ask_mode::{self, OutputFormat, UsageError};
+use crate::execution::error_detection;
+use crate::models::global_registry;
+use crate::terminal_capture::capture_instance;
+use crate::terminal_capture::records::CommandRecord;
+
+/// Only the end of long output is sent; that is where the failure is.
+const MAX_OUTPUT_LINES: usize = 200;
+
+const SYSTEM_PROMPT: &str = "You are kaiti, a terminal assistant that diagnoses errors from command output. \
+Reply in Markdown with exactly two sections and nothing before them.\n\
+## What went wrong\n\
+One to three sentences explaining the cause in plain terms.\n\
+## How to fix it\n\
+A numbered list of steps, in the order to do them. Put each command to run in a fenced code block tagged with its shell, \
+one command per line. When the error is only missing dependencies, the steps are just the commands to install them.\n\
+Be concise. Don't restate the error, and say so when the output is not enough to be sure of the cause.";
+
+pub struct DebugOptions {
+    /// The error text given with `--error`.
+    pub error: Option<String>,
+    /// Lines of scrollback to read, overriding the capture settings.
+    pub scrollback_lines: Option<usize>,
+}
+
+/// A command that exited with an error under `kaiti run`.
+pub struct Failure {
+    pub command: String,
+    pub exit_code: i32,
+    pub output: String,
+}
+
+/// Where the error being explained came from.
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Argument,
+    Stdin,
+    Terminal,
+    Command { command: String, exit_code: i32 },
+    /// A failed command from the shell hooks, which do not record output.
+    Recorded(CommandRecord),
+}
+
+/// Explains an error and how to fix it. The error is taken from `--error`,
+/// then from anything piped to stdin, and otherwise from the terminal: the
+/// last failed command recorded by the shell hooks and, inside tmux or
+/// screen, the window's scrollback.
+pub async fn run_debug(c_model: &ModelConfig, capture: &CaptureSettings, options: DebugOptions) -> Result<(), Box<dyn Error>> {
+    let mut capture = capture.clone();
+    capture.scrollback_lines = options.scrollback_lines.unwrap_or(capture.scrollback_lines);
+    let (output, source) = match (options.error, ask_mode::read_piped_input()?) {
+        (Some(error), _) => (error, Source::Argument),
+        (None, Some(piped)) => (piped, Source::Stdin),
+        (None, None) => from_terminal(&capture)?,
+    };
+    explain(c_model, &output, source).await
+}
+
+fn from_terminal(settings: &CaptureSettings) -> Result<(String, Source), Box<dyn Error>> {
+    let capture = capture_instance(settings);
+    let failed = capture.recent_commands()?.into_iter().rev().find(|record| record.failed());
+    let screen = if capture.captures_screen() { Some(capture.capture_output()?) } else { None };
+    Ok(match (failed, screen) {
+        (Some(record), screen) => (screen.unwrap_or_default(), Source::Recorded(record)),
+        (None, Some(screen)) => (screen, Source::Terminal),
+        // Explains why there is nothing to go on
+        (None, None) => (capture.capture_output()?, Source::Terminal),
+    })
+}
+
+/// Explains why a command run by `kaiti run` failed, from its output.
+pub async fn explain_failure(c_model: &ModelConfig, failure: Failure) -> Result<(), Box<dyn Error>> {
+    let source = Source::Command { command: failure.command, exit_code: failure.exit_code };
+    explain(c_model, &failure.output, source).await
+}
+
+async fn explain(c_model: &ModelConfig, output: &str, source: Source) -> Result<(), Box<dyn Error>> {
+    let request = build_request(output, &source)?;
+
+    let mut chat_model: Box<dyn ChatModel> = {
+        let registry = global_registry().read().map_err(|_| "model registry is poisoned")?;
+        registry.create_model(c_model)?
+    };
+    chat_model.set_retry_listener(ask_mode::stderr_retry_listener());
+    let stream = chat_model.create_response_stream(&request).await?;
+
+    let format = if std::io::stdout().is_terminal() { OutputFormat::Markdown } else { OutputFormat::Text };
+    let width = crossterm::terminal::size().map(|(columns, _)| columns as usize).unwrap_or(80);
+    ask_mode::write_answer(stream, format, width, &mut std::io::stdout().lock()).await
+}
+
+fn build_request(output: &str, source: &Source) -> Result<ChatModelRequest, Box<dyn Error>> {
+    let output = output.trim_end();
+    // A failed command is worth explaining even when it printed nothing
+    if output.trim().is_empty() && !matches!(source, Source::Command { .. } | Source::Recorded(_)) {
+        return Err(Box::new(UsageError(String::from("No error to explain; pass --error or pipe the failing output to kaiti debug"))));
+    }
+    // A single line is already as specific as it gets
+    let detected = if output.contains('\n') { error_detection::primary_error(output) } else { None };
+
+    let mut content = match source {
+        Source::Command { command, exit_code } =>
+            format!("The command `{}` exited with code {}. Explain the error and how to fix it.\n", command, exit_code),
+        Source::Recorded(record) =>
+            format!("The command `{}` exited with code {} in {}. Explain the error and how to fix it.\n",
+                record.command, record.exit_code, record.cwd),
+        _ => String::from("Explain this error and how to fix it.\n"),
+    };
+    if let Some(report) = detected {
+        content.push_str(&format!("\nThe error appears to be: {}\n", report));
+    }
+    // What is read from the terminal is already cut to the configured depth
+    let lines = match source {
+        Source::Terminal | Source::Recorded(_) => usize::MAX,
+        _ => MAX_OUTPUT_LINES,
+    };
+    match source {
+        _ if !output.is_empty() => content.push_str(&format!("\nOutput:\n```\n{}\n```", tail(output, lines))),
+        Source::Recorded(_) => content.push_str("\nIts output was not recorded."),
+        _ => content.push_str("\nIt printed no output."),
+    }
+
+    Ok(ChatModelRequest {
+        messages: vec![
+            ChatCompletionRequestMessage { role: Role::System, content: SYSTEM_PROMPT.to_string(), name: None },
+            ChatCompletionRequestMessage { role: Role::User, content, name: None },
+        ],
+    })
+}
+
+/// The last `lines` lines of `output`, noting how many were left out.
+fn tail(output: &str, lines: usize) -> String {
+    let all = output.lines().collect::<Vec<_>>();
+    if all.len() <= lines {
+        return output.to_string();
+    }
+    format!("[{} earlier lines omitted]\n{}", all.len() - lines, all[all.len() - lines..].join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_points_out_the_detected_error() {
+        let output = "Compiling app\nError: could not find Cargo.toml in /tmp\nexit 101\n";
+        let request = build_request(output, &Source::Stdin).unwrap();
+        assert_eq!(request.messages[0].role, Role::System);
+        assert_eq!(request.messages[1].content,
+            "Explain this error and how to fix it.\n\nThe error appears to be: Error: could not find Cargo.toml in /tmp\n\n\
+             Output:\n```\nCompiling app\nError: could not find Cargo.toml in /tmp\nexit 101\n```");
+
+        // A single line given with --error is not repeated
+        let request = build_request("Error: permission denied", &Source::Argument).unwrap();
+        assert!(!request.messages[1].content.contains("appears to be"));
+
+        let output = "error[E0425]: cannot find value `x` in this scope\n --> src/main.rs:2:13\n";
+        assert!(build_request(output, &Source::Stdin).unwrap().messages[1].content
+            .contains("The error appears to be: rust error E0425 at src/main.rs:2:13: cannot find value `x` in this scope\n"));
+
+        let error = build_request("  \n", &Source::Stdin).err().unwrap();
+        assert_eq!(ask_mode::exit_code(error.as_ref()), ask_mode::EXIT_USAGE);
+
+        let source = Source::Command { command: String::from("make test"), exit_code: 2 };
+        assert_eq!(build_request("", &source).unwrap().messages[1].content,
+            "The command `make test` exited with code 2. Explain the error and how to fix it.\n\nIt printed no output.");
+
+        let record = CommandRecord {
+            command: String::from("npm test"),
+            exit_code: 1,
+            cwd: String::from("/srv/app"),
+            started_at: 0,
+            finished_at: 4,
+            shell_pid: 1,
+        };
+        assert_eq!(build_request("", &Source::Recorded(record)).unwrap().messages[1].content,
+            "The command `npm test` exited with code 1 in /srv/app. Explain the error and how to fix it.\n\nIts output was not recorded.");
+    }
+
+    #[test]
+    fn test_long_output_keeps_the_tail() {
+        let output = (1..=5).map(|line| line.to_string()).collect::<Vec<_>>().join("\n");
+        assert_eq!(tail(&output, 2), "[3 earlier lines omitted]\n4\n5");
+        assert_eq!(tail(&output, 5), output);
+    }
+}
@@ -0,0 +1,59 @@
+use regex::Regex;
+
+use super::{ErrorReport, Location, Severity, Tool};
+
+/// gcc and clang diagnostics and the linker errors they pass on.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    let diagnostic = Regex::new(r"^(.+?):(\d+):(\d+): (fatal error|error|warning): (.+?)(?: \[(-W[^\]]+)\])?$").unwrap();
+    let undefined = Regex::new(r"^(.+?):\(\.\w+(?:\+0x[0-9a-f]+)?\): (undefined reference to .+)$").unwrap();
+    let linker = Regex::new(r"^(?:/\S+/)?ld(?:\.\w+)?: (.+)$").unwrap();
+
+    let mut reports = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(captures) = diagnostic.captures(line) {
+            let severity = if &captures[4] == "warning" { Severity::Warning } else { Severity::Error };
+            let location = Location::new(&captures[1], Some(&captures[2]), Some(&captures[3]));
+            let report = ErrorReport::new(Tool::C, severity, &captures[5])
+                .with_code(captures.get(6).map(|code| code.as_str()))
+                .with_location(Some(location));
+            reports.push((index, report));
+        } else if let Some(captures) = undefined.captures(line) {
+            let location = Location::new(&captures[1], None, None);
+            reports.push((index, ErrorReport::new(Tool::C, Severity::Error, &captures[2]).with_location(Some(location))));
+        } else if let Some(captures) = linker.captures(line) {
+            // Only says which function the next line's reference is in
+            if captures[1].contains(": in function ") {
+                continue;
+            }
+            reports.push((index, ErrorReport::new(Tool::C, Severity::Error, &captures[1])));
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_fixture;
+
+    fn summaries(fixture: &str) -> Vec<String> {
+        parse_fixture(parse, fixture).iter().map(ErrorReport::to_string).collect()
+    }
+
+    #[test]
+    fn test_compiler_diagnostics() {
+        assert_eq!(summaries(include_str!("fixtures/gcc.txt")), [
+            "gcc/clang warning -Wunused-variable at main.c:5:9: unused variable 'count'",
+            "gcc/clang error -Wimplicit-function-declaration at main.c:7:5: implicit declaration of function 'prinf'; did you mean 'printf'?",
+            "gcc/clang error at util.c:2:10: config.h: No such file or directory",
+        ]);
+    }
+
+    #[test]
+    fn test_linker_errors() {
+        assert_eq!(summaries(include_str!("fixtures/clang_linker.txt")), [
+            "gcc/clang error at main.c: undefined reference to `compute_total'",
+            "gcc/clang error: cannot find -lssl: No such file or directory",
+        ]);
+    }
+}
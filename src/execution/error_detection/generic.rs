@@ -0,0 +1,12 @@
+use regex::Regex;
+
+use super::{ErrorReport, Severity, Tool};
+
+/// Any line that calls itself an error, for output no other parser knows.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    let error = Regex::new(r"(?i)\b(?:error|fatal)\b(?:\[[^\]]*\])?:").unwrap();
+    lines.iter().enumerate()
+        .filter(|(_, line)| error.is_match(line))
+        .map(|(index, line)| (index, ErrorReport::new(Tool::Generic, Severity::Error, line)))
+        .collect()
+}
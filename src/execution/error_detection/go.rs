@@ -0,0 +1,82 @@
+use regex::Regex;
+
+use super::{ErrorReport, Frame, Location, Severity, Tool};
+
+/// Go build errors, panics with their goroutine traces and failed tests.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    let build = Regex::new(r"^(\S+\.go):(\d+):(\d+): (.+)$").unwrap();
+    let panic = Regex::new(r"^(panic|fatal error): (.+?)(?: \[recovered\])?$").unwrap();
+    let failed_test = Regex::new(r"^\s*--- FAIL: (\S+)").unwrap();
+    let test_log = Regex::new(r"^\s+(\S+_test\.go):(\d+): (.+)$").unwrap();
+    // `fatal error:` is also how C compilers start a line
+    let goroutines = lines.iter().any(|line| line.starts_with("goroutine "));
+
+    let mut reports = Vec::new();
+    let mut test = None;
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(captures) = build.captures(line) {
+            let location = Location::new(&captures[1], Some(&captures[2]), Some(&captures[3]));
+            reports.push((index, ErrorReport::new(Tool::Go, Severity::Error, &captures[4]).with_location(Some(location))));
+        } else if let Some(captures) = panic.captures(line).filter(|captures| &captures[1] == "panic" || goroutines) {
+            let report = ErrorReport::new(Tool::Go, Severity::Error, &captures[2])
+                .with_code(Some(&captures[1]))
+                .with_frames(goroutine_trace(&lines[index + 1..]));
+            reports.push((index, report));
+        } else if let Some(captures) = failed_test.captures(line) {
+            test = Some(captures[1].to_string());
+        } else if let (Some(name), Some(captures)) = (&test, test_log.captures(line)) {
+            let location = Location::new(&captures[1], Some(&captures[2]), None);
+            let report = ErrorReport::new(Tool::Go, Severity::Error, &captures[3]).with_code(Some(name)).with_location(Some(location));
+            reports.push((index, report));
+        }
+    }
+    reports
+}
+
+/// The frames of the first goroutine after a panic; that is the one that panicked.
+fn goroutine_trace(lines: &[&str]) -> Vec<Frame> {
+    let function = Regex::new(r"^([^\s].*)\(.*\)$").unwrap();
+    let at = Regex::new(r"^\t(.+?):(\d+)(?: \+0x[0-9a-f]+)?$").unwrap();
+    let Some(start) = lines.iter().take(3).position(|line| line.starts_with("goroutine ")) else {
+        return Vec::new();
+    };
+
+    let mut frames = Vec::new();
+    let mut rest = lines[start + 1..].iter();
+    while let (Some(call), Some(position)) = (rest.next().and_then(|line| function.captures(line)), rest.next().and_then(|line| at.captures(line))) {
+        frames.push(Frame {
+            function: Some(call[1].to_string()),
+            location: Some(Location::new(&position[1], Some(&position[2]), None)),
+        });
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_fixture;
+
+    #[test]
+    fn test_build_errors_and_failed_tests() {
+        let summaries = parse_fixture(parse, include_str!("fixtures/go_build.txt")).iter().map(ErrorReport::to_string).collect::<Vec<_>>();
+        assert_eq!(summaries, [
+            "go error at ./main.go:9:2: undefined: fmt.Printn",
+            "go error at ./handlers.go:21:15: cannot use id (variable of type string) as int value in argument to lookup",
+        ]);
+
+        let reports = parse_fixture(parse, include_str!("fixtures/go_test.txt"));
+        assert_eq!(reports[0].to_string(), "go error TestParsePort at config_test.go:17: ParsePort(\"80a\") = 80, want error");
+    }
+
+    #[test]
+    fn test_panic() {
+        let reports = parse_fixture(parse, include_str!("fixtures/go_panic.txt"));
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].to_string(), "go error panic at /home/dev/app/main.go:14: runtime error: index out of range [3] with length 3");
+        let functions = reports[0].frames.iter().map(|frame| frame.function.as_deref().unwrap_or_default()).collect::<Vec<_>>();
+        assert_eq!(functions, ["main.pick", "main.main"]);
+
+        assert!(parse_fixture(parse, "fatal error: config.h: No such file or directory").is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+use regex::Regex;
+
+use super::{ErrorReport, Frame, Location, Severity, Tool};
+
+/// Uncaught Java exceptions, javac and Maven compile errors and Gradle's
+/// build failure summary.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    let exception = Regex::new(r#"^(?:Exception in thread "[^"]*" )?((?:[a-z_$][\w$]*\.)+[A-Z][\w$]*)(?:: (.*))?$"#).unwrap();
+    let caused_by = Regex::new(r"^Caused by: ((?:[a-z_$][\w$]*\.)+[A-Z][\w$]*)(?:: (.*))?$").unwrap();
+    let frame = Regex::new(r"^\s+at ([\w$.<>/]+)\(([^:)]+)(?::(\d+))?\)$").unwrap();
+    let javac = Regex::new(r"^(.+\.java):(\d+): (error|warning): (.+)$").unwrap();
+    let maven = Regex::new(r"^\[(ERROR|WARNING)\] (.+\.java):\[(\d+),(\d+)\] (.+)$").unwrap();
+
+    let mut reports = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+        if let Some(captures) = javac.captures(line) {
+            let severity = if &captures[3] == "error" { Severity::Error } else { Severity::Warning };
+            let location = Location::new(&captures[1], Some(&captures[2]), None);
+            reports.push((index - 1, ErrorReport::new(Tool::Java, severity, &captures[4]).with_location(Some(location))));
+        } else if let Some(captures) = maven.captures(line) {
+            let severity = if &captures[1] == "ERROR" { Severity::Error } else { Severity::Warning };
+            let location = Location::new(&captures[2], Some(&captures[3]), Some(&captures[4]));
+            reports.push((index - 1, ErrorReport::new(Tool::Java, severity, &captures[5]).with_location(Some(location))));
+        } else if line.trim_end() == "* What went wrong:" {
+            // The failure and the `> ` lines explaining it, up to the next blank line
+            let message = lines[index..].iter().map(|line| line.trim()).take_while(|line| !line.is_empty()).collect::<Vec<_>>().join(" ");
+            if !message.is_empty() {
+                reports.push((index - 1, ErrorReport::new(Tool::Gradle, Severity::Error, &message)));
+            }
+        } else if let Some(captures) = exception.captures(line) {
+            let start = index - 1;
+            let mut frames = stack(&frame, &lines[index..]);
+            if frames.is_empty() {
+                continue;
+            }
+            // The last cause in the chain is where it went wrong
+            let (mut class, mut message) = (captures[1].to_string(), captures.get(2).map_or("", |message| message.as_str()).to_string());
+            index += frames.len();
+            while let Some(cause) = lines.get(index).and_then(|line| caused_by.captures(line)) {
+                class = cause[1].to_string();
+                message = cause.get(2).map_or("", |message| message.as_str()).to_string();
+                frames = stack(&frame, &lines[index + 1..]);
+                index += 1 + frames.len();
+                // Skips the `... 1 more` ending a cause's stack
+                if lines.get(index).is_some_and(|line| line.trim_start().starts_with("... ")) {
+                    index += 1;
+                }
+            }
+            reports.push((start, ErrorReport::new(Tool::Java, Severity::Error, &message).with_code(Some(&class)).with_frames(frames)));
+        }
+    }
+    reports
+}
+
+fn stack(frame: &Regex, lines: &[&str]) -> Vec<Frame> {
+    lines.iter()
+        .map_while(|line| frame.captures(line))
+        .map(|at| Frame {
+            function: Some(at[1].to_string()),
+            location: Some(Location::new(&at[2], at.get(3).map(|line| line.as_str()), None)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_fixture;
+
+    #[test]
+    fn test_exception_reports_the_root_cause() {
+        let reports = parse_fixture(parse, include_str!("fixtures/java_exception.txt"));
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].to_string(),
+            "java error java.io.FileNotFoundException at Settings.java:40: settings.properties (No such file or directory)");
+        assert_eq!(reports[0].frames.len(), 3);
+        assert_eq!(reports[0].frames[0].location, Some(Location::new("Native Method", None, None)));
+    }
+
+    #[test]
+    fn test_gradle_build_failure() {
+        let summaries = parse_fixture(parse, include_str!("fixtures/gradle.txt")).iter().map(ErrorReport::to_string).collect::<Vec<_>>();
+        assert_eq!(summaries, [
+            "java error at /home/dev/app/src/main/java/com/example/app/Main.java:14: cannot find symbol",
+            "gradle error: Execution failed for task ':app:compileJava'. > Compilation failed; see the compiler error output for details.",
+        ]);
+        assert_eq!(parse_fixture(parse, "[ERROR] src/main/java/App.java:[3,8] class Ap is public").len(), 1);
+    }
+}
@@ -1,3 +1,230 @@
-pub mod error_parser;
+use std::fmt;
 
-pub use error_parser::parse_error_message;
\ No newline at end of file
+mod c;
+mod generic;
+mod go;
+mod java;
+mod node;
+mod python;
+mod rust;
+mod shell;
+
+/// The tool an error came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Rust,
+    /// gcc, clang and their linkers.
+    C,
+    Python,
+    Node,
+    TypeScript,
+    Go,
+    Java,
+    Gradle,
+    Shell,
+    /// A line that says it is an error, from a tool there is no parser for.
+    Generic,
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Tool::Rust => "rust",
+            Tool::C => "gcc/clang",
+            Tool::Python => "python",
+            Tool::Node => "node",
+            Tool::TypeScript => "typescript",
+            Tool::Go => "go",
+            Tool::Java => "java",
+            Tool::Gradle => "gradle",
+            Tool::Shell => "shell",
+            Tool::Generic => "unknown tool",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A position in a source file; the line and column are 1-based.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl Location {
+    fn new(file: &str, line: Option<&str>, column: Option<&str>) -> Location {
+        Location {
+            file: file.to_string(),
+            line: line.and_then(|line| line.parse().ok()),
+            column: column.and_then(|column| column.parse().ok()),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One call in a stack trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl Frame {
+    /// Whether the frame is in a language runtime or an installed package
+    /// rather than the code being worked on.
+    fn is_library(&self) -> bool {
+        const LIBRARY_FILES: [&str; 8] =
+            ["node:", "/node_modules/", "site-packages/", "/lib/python", "/go/src/runtime/", "/rustc/", "/.cargo/registry/", "<frozen "];
+        const LIBRARY_FUNCTIONS: [&str; 6] = ["java.", "javax.", "jdk.", "sun.", "kotlin.", "org.junit."];
+        let file = self.location.as_ref().map(|location| location.file.as_str()).unwrap_or_default();
+        let function = self.function.as_deref().unwrap_or_default();
+        LIBRARY_FILES.iter().any(|marker| file.contains(marker))
+            || LIBRARY_FUNCTIONS.iter().any(|prefix| function.starts_with(prefix))
+    }
+}
+
+/// An error, or a warning, found in a tool's output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    pub tool: Tool,
+    pub severity: Severity,
+    pub message: String,
+    /// The tool's code for the error, such as `E0433`, `TS2322` or the
+    /// exception class.
+    pub code: Option<String>,
+    pub location: Option<Location>,
+    /// The stack trace, innermost call first.
+    pub frames: Vec<Frame>,
+}
+
+impl ErrorReport {
+    fn new(tool: Tool, severity: Severity, message: &str) -> ErrorReport {
+        ErrorReport {
+            tool,
+            severity,
+            message: message.trim().to_string(),
+            code: None,
+            location: None,
+            frames: Vec::new(),
+        }
+    }
+
+    fn with_code(mut self, code: Option<&str>) -> ErrorReport {
+        self.code = code.map(String::from);
+        self
+    }
+
+    fn with_location(mut self, location: Option<Location>) -> ErrorReport {
+        self.location = location;
+        self
+    }
+
+    /// Adds a stack trace, given innermost call first, and takes the location
+    /// from the innermost call in the code being worked on when none is set.
+    fn with_frames(mut self, frames: Vec<Frame>) -> ErrorReport {
+        if self.location.is_none() {
+            self.location = frames.iter().find(|frame| !frame.is_library()).or(frames.first())
+                .and_then(|frame| frame.location.clone());
+        }
+        self.frames = frames;
+        self
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Nothing is known beyond the line itself
+        if self.tool == Tool::Generic {
+            return f.write_str(&self.message);
+        }
+        write!(f, "{} {}", self.tool, self.severity)?;
+        if let Some(code) = &self.code {
+            write!(f, " {}", code)?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Finds the reports in a tool's output, each with the index of the line it
+/// starts on.
+type Parser = fn(&[&str]) -> Vec<(usize, ErrorReport)>;
+
+const PARSERS: [Parser; 8] = [rust::parse, c::parse, python::parse, node::parse, go::parse, java::parse, shell::parse, generic::parse];
+
+/// Every error and warning found in `output`, in the order they appear. The
+/// generic parser, which only looks for lines mentioning an error, is used
+/// when none of the others recognise anything.
+pub fn parse_errors(output: &str) -> Vec<ErrorReport> {
+    let lines = output.lines().collect::<Vec<_>>();
+    let (specific, fallback) = PARSERS.split_at(PARSERS.len() - 1);
+    let mut found = specific.iter().flat_map(|parse| parse(&lines)).collect::<Vec<_>>();
+    if found.is_empty() {
+        found = fallback.iter().flat_map(|parse| parse(&lines)).collect();
+    }
+    found.sort_by_key(|(line, _)| *line);
+    found.into_iter().map(|(_, report)| report).collect()
+}
+
+/// The report most likely to explain a failure: the first error, since
+/// later ones often follow from it, or the first warning when there are none.
+pub fn primary_error(output: &str) -> Option<ErrorReport> {
+    let mut reports = parse_errors(output);
+    let index = reports.iter().position(|report| report.severity == Severity::Error).unwrap_or(0);
+    (index < reports.len()).then(|| reports.swap_remove(index))
+}
+
+/// Runs one parser over a fixture, keeping just the reports.
+#[cfg(test)]
+pub(super) fn parse_fixture(parse: Parser, fixture: &str) -> Vec<ErrorReport> {
+    parse(&fixture.lines().collect::<Vec<_>>()).into_iter().map(|(_, report)| report).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primary_error_prefers_the_first_error() {
+        let output = "warning: unused variable: `x`\n --> src/lib.rs:2:9\n\
+                      error[E0425]: cannot find value `y` in this scope\n --> src/lib.rs:3:5\n\
+                      error: aborting due to 1 previous error\n";
+        let report = primary_error(output).unwrap();
+        assert_eq!(report.to_string(), "rust error E0425 at src/lib.rs:3:5: cannot find value `y` in this scope");
+        assert_eq!(parse_errors(output).len(), 2);
+
+        let report = primary_error("Deploying...\nERROR: quota exceeded for project\n").unwrap();
+        assert_eq!((report.tool, report.to_string()), (Tool::Generic, String::from("ERROR: quota exceeded for project")));
+        assert_eq!(primary_error("all good\n"), None);
+    }
+}
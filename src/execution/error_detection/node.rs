@@ -0,0 +1,83 @@
+use regex::Regex;
+
+use super::{ErrorReport, Frame, Location, Severity, Tool};
+
+/// How far below an error's first line its stack trace may start, past
+/// lines such as node's `Require stack:`.
+const MAX_LINES_BEFORE_STACK: usize = 6;
+
+/// Uncaught errors from node with their stack traces, and tsc diagnostics.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    let header = Regex::new(r"^(?:Uncaught )?([A-Z]\w*(?:Error|Exception)|Error)(?: \[(\w+)\])?: (.+)$").unwrap();
+    let frame = Regex::new(r"^\s+at (?:(.+?) \()?(.+?):(\d+):(\d+)\)?(?: \{)?$").unwrap();
+    let code = Regex::new(r"^\s+code: '(\w+)'").unwrap();
+    let tsc = Regex::new(r"^(.+\.[cm]?tsx?)(?:\((\d+),(\d+)\): |:(\d+):(\d+) - )(error|warning) (TS\d+): (.+)$").unwrap();
+
+    let mut reports = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(captures) = tsc.captures(line) {
+            let line = captures.get(2).or(captures.get(4)).map(|line| line.as_str());
+            let column = captures.get(3).or(captures.get(5)).map(|column| column.as_str());
+            let severity = if &captures[6] == "error" { Severity::Error } else { Severity::Warning };
+            let report = ErrorReport::new(Tool::TypeScript, severity, &captures[8])
+                .with_code(Some(&captures[7]))
+                .with_location(Some(Location::new(&captures[1], line, column)));
+            reports.push((index, report));
+            continue;
+        }
+        let Some(captures) = header.captures(line) else {
+            continue;
+        };
+
+        // Without a stack trace this could be any language's error line
+        let rest = &lines[index + 1..];
+        let Some(first) = rest.iter().take(MAX_LINES_BEFORE_STACK).position(|line| frame.is_match(line)) else {
+            continue;
+        };
+        let frames = rest[first..].iter()
+            .map_while(|line| frame.captures(line))
+            .map(|at| Frame {
+                function: at.get(1).map(|function| function.as_str().to_string()),
+                location: Some(Location::new(&at[2], Some(&at[3]), Some(&at[4]))),
+            })
+            .collect::<Vec<_>>();
+        // The `code` property printed after the stack, as in `MODULE_NOT_FOUND`
+        let property = rest[first + frames.len()..].iter().take(3).find_map(|line| code.captures(line));
+        let error_code = captures.get(2).or_else(|| property.as_ref().and_then(|property| property.get(1))).unwrap_or(captures.get(1).unwrap());
+        let report = ErrorReport::new(Tool::Node, Severity::Error, &captures[3])
+            .with_code(Some(error_code.as_str()))
+            .with_frames(frames);
+        reports.push((index, report));
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_fixture;
+
+    #[test]
+    fn test_uncaught_errors() {
+        let reports = parse_fixture(parse, include_str!("fixtures/node.txt"));
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].to_string(), "node error TypeError at /home/dev/app/src/server.js:12:9: app.lisen is not a function");
+        assert_eq!(reports[0].frames[1].function.as_deref(), Some("Module._compile"));
+        assert_eq!(reports[0].frames[2].function, None);
+
+        let reports = parse_fixture(parse, include_str!("fixtures/node_module.txt"));
+        assert_eq!(reports[0].to_string(), "node error MODULE_NOT_FOUND at /home/dev/app/index.js:1:17: Cannot find module 'express'");
+        assert_eq!(reports[0].frames.len(), 3);
+
+        assert!(parse_fixture(parse, "TypeError: unsupported operand type(s) for +: 'int' and 'str'").is_empty());
+    }
+
+    #[test]
+    fn test_tsc_diagnostics() {
+        let summaries = parse_fixture(parse, include_str!("fixtures/tsc.txt")).iter().map(ErrorReport::to_string).collect::<Vec<_>>();
+        assert_eq!(summaries, [
+            "typescript error TS2322 at src/index.ts:4:7: Type 'string' is not assignable to type 'number'.",
+            "typescript error TS2339 at src/api/client.ts:18:3: Property 'fetchAll' does not exist on type 'Client'.",
+        ]);
+    }
+}
@@ -0,0 +1,72 @@
+use regex::Regex;
+
+use super::{ErrorReport, Frame, Location, Severity, Tool};
+
+/// Python tracebacks, and the syntax errors reported before anything runs.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    let file = Regex::new(r#"^\s*File "(.+)", line (\d+)(?:, in (.+))?$"#).unwrap();
+    let exception = Regex::new(r"^([A-Za-z_][\w.]*)(?::\s*(.*))?$").unwrap();
+
+    let mut reports = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let start = index;
+        index += 1;
+        // A syntax error has a single frame without a function and no traceback header
+        let syntax_error = file.captures(lines[start]).is_some_and(|captures| captures.get(3).is_none());
+        if lines[start].trim_end() != "Traceback (most recent call last):" && !syntax_error {
+            continue;
+        }
+
+        let mut frames = Vec::new();
+        let mut end = if syntax_error { start } else { start + 1 };
+        while let Some(line) = lines.get(end) {
+            if let Some(captures) = file.captures(line) {
+                frames.push(Frame {
+                    function: captures.get(3).map(|function| function.as_str().to_string()),
+                    location: Some(Location::new(&captures[1], Some(&captures[2]), None)),
+                });
+            } else if !line.starts_with(' ') {
+                break;
+            }
+            // Anything else indented is source or a marker under it
+            end += 1;
+        }
+        let Some(captures) = lines.get(end).and_then(|line| exception.captures(line)) else {
+            continue;
+        };
+        frames.reverse();
+        let report = ErrorReport::new(Tool::Python, Severity::Error, captures.get(2).map_or("", |message| message.as_str()))
+            .with_code(Some(&captures[1]))
+            .with_frames(frames);
+        reports.push((start, report));
+        index = end + 1;
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_fixture;
+
+    #[test]
+    fn test_traceback() {
+        let reports = parse_fixture(parse, include_str!("fixtures/python_traceback.txt"));
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!(report.code.as_deref(), Some("requests.exceptions.ConnectTimeout"));
+        assert_eq!(report.message, "HTTPSConnectionPool(host='api.example.com', port=443): Max retries exceeded");
+        // The innermost call outside installed packages
+        assert_eq!(report.location, Some(Location::new("/home/dev/app/client.py", Some("6"), None)));
+        let functions = report.frames.iter().map(|frame| frame.function.as_deref().unwrap_or_default()).collect::<Vec<_>>();
+        assert_eq!(functions, ["get", "fetch", "main", "<module>"]);
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let reports = parse_fixture(parse, include_str!("fixtures/python_syntax.txt"));
+        assert_eq!(reports[0].to_string(), "python error SyntaxError at /home/dev/app/settings.py:3: '(' was never closed");
+        assert!(parse_fixture(parse, "NameError: name 'x' is not defined").is_empty());
+    }
+}
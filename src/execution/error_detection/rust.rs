@@ -0,0 +1,107 @@
+use regex::Regex;
+
+use super::{ErrorReport, Frame, Location, Severity, Tool};
+
+/// rustc diagnostics, cargo errors and panics with their backtraces.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    let header = Regex::new(r"^(error|warning)(?:\[(E\d{4})\])?: (.+)$").unwrap();
+    let arrow = Regex::new(r"^\s*--> (.+?):(\d+):(\d+)$").unwrap();
+    let panic = Regex::new(r"^thread '(.+?)' (?:\(\d+\) )?panicked at (.+?):(\d+):(\d+):$").unwrap();
+    // Before Rust 1.73 the message came first, on the same line
+    let old_panic = Regex::new(r"^thread '(.+?)' panicked at '(.*)', (.+?):(\d+):(\d+)$").unwrap();
+    // Summaries that only repeat what the diagnostics above them said
+    let summary = Regex::new(r"^(aborting due to|could not compile|`.+` \(.+\) generated \d+ warnings?|build failed)").unwrap();
+    // Without a code or a source location, `error:` is only cargo's when cargo is involved
+    let cargo = lines.iter().any(|line| line.contains("Cargo.toml") || line.trim_start().starts_with("Compiling "));
+
+    let mut reports = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(captures) = header.captures(line) {
+            let message = &captures[3];
+            if summary.is_match(message) {
+                continue;
+            }
+            let location = lines[index + 1..].iter().take(2)
+                .find_map(|line| arrow.captures(line))
+                .map(|at| Location::new(&at[1], Some(&at[2]), Some(&at[3])));
+            let code = captures.get(2).map(|code| code.as_str());
+            if code.is_none() && location.is_none() && !cargo {
+                continue;
+            }
+            let severity = if &captures[1] == "error" { Severity::Error } else { Severity::Warning };
+            reports.push((index, ErrorReport::new(Tool::Rust, severity, message).with_code(code).with_location(location)));
+        } else if let Some(captures) = panic.captures(line) {
+            let message = lines.get(index + 1).copied().unwrap_or_default();
+            let location = Location::new(&captures[2], Some(&captures[3]), Some(&captures[4]));
+            let report = ErrorReport::new(Tool::Rust, Severity::Error, message).with_code(Some("panic")).with_location(Some(location));
+            reports.push((index, report.with_frames(backtrace(&lines[index..]))));
+        } else if let Some(captures) = old_panic.captures(line) {
+            let location = Location::new(&captures[3], Some(&captures[4]), Some(&captures[5]));
+            let report = ErrorReport::new(Tool::Rust, Severity::Error, &captures[2]).with_code(Some("panic")).with_location(Some(location));
+            reports.push((index, report.with_frames(backtrace(&lines[index..]))));
+        }
+    }
+    reports
+}
+
+/// The frames of the `stack backtrace:` following a panic, if it has one.
+fn backtrace(lines: &[&str]) -> Vec<Frame> {
+    let function = Regex::new(r"^\s*\d+: (.+)$").unwrap();
+    let at = Regex::new(r"^\s+at (.+?):(\d+):(\d+)$").unwrap();
+    let Some(start) = lines.iter().take(4).position(|line| line.trim() == "stack backtrace:") else {
+        return Vec::new();
+    };
+
+    let mut frames: Vec<Frame> = Vec::new();
+    for line in &lines[start + 1..] {
+        if let Some(captures) = at.captures(line) {
+            if let Some(frame) = frames.last_mut() {
+                frame.location = Some(Location::new(&captures[1], Some(&captures[2]), Some(&captures[3])));
+            }
+        } else if let Some(captures) = function.captures(line) {
+            frames.push(Frame { function: Some(captures[1].to_string()), location: None });
+        } else {
+            break;
+        }
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_fixture;
+
+    #[test]
+    fn test_rustc_diagnostics() {
+        let reports = parse_fixture(parse, include_str!("fixtures/rustc.txt"));
+        let summaries = reports.iter().map(ErrorReport::to_string).collect::<Vec<_>>();
+        assert_eq!(summaries, [
+            "rust warning at src/main.rs:1:5: unused import: `std::fs`",
+            "rust error E0433 at src/config.rs:3:5: failed to resolve: use of undeclared crate or module `serde`",
+            "rust error at src/main.rs:12:17: cannot find macro `json` in this scope",
+        ]);
+
+        let reports = parse_fixture(parse, include_str!("fixtures/cargo_manifest.txt"));
+        assert_eq!(reports[0].message, "failed to parse manifest at `/home/dev/app/Cargo.toml`");
+        assert!(parse_fixture(parse, "error: pathspec 'mian' did not match any file(s) known to git").is_empty());
+    }
+
+    #[test]
+    fn test_panic_with_backtrace() {
+        let reports = parse_fixture(parse, include_str!("fixtures/rust_panic.txt"));
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!(report.to_string(),
+            "rust error panic at src/main.rs:8:37: called `Result::unwrap()` on an `Err` value: \
+             Os { code: 2, kind: NotFound, message: \"No such file or directory\" }");
+        assert_eq!(report.frames.len(), 4);
+        assert_eq!(report.frames[2], Frame {
+            function: Some(String::from("app::load_config")),
+            location: Some(Location::new("./src/main.rs", Some("8"), Some("37"))),
+        });
+
+        let old = parse_fixture(parse, "thread 'main' panicked at 'index out of bounds', src/lib.rs:4:5");
+        assert_eq!(old[0].to_string(), "rust error panic at src/lib.rs:4:5: index out of bounds");
+    }
+}
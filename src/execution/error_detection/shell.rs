@@ -0,0 +1,59 @@
+use regex::Regex;
+
+use super::{ErrorReport, Location, Severity, Tool};
+
+/// Errors from the shell itself: missing commands and files it could not run.
+/// The code is the exit status the shell gives them.
+pub fn parse(lines: &[&str]) -> Vec<(usize, ErrorReport)> {
+    // `bash: foo: ...`, `sh: 1: foo: ...` or `./script.sh: line 4: foo: ...`
+    let shell = Regex::new(r"^(?:(?:-|/\S*/)?(?:bash|zsh|sh|dash|ksh|fish)|(\S+\.(?:sh|bash|zsh)))(?:: line (\d+))?(?:: (\d+))?: (.+)$").unwrap();
+    let env = Regex::new(r"^(?:/\S+/)?env: '?([^':]+)'?: No such file or directory$").unwrap();
+    let not_found = [
+        Regex::new(r"^command not found: (.+)$").unwrap(),
+        Regex::new(r"^(.+): (?:command )?not found$").unwrap(),
+        Regex::new(r"^Unknown command:? '?([^']+)'?$").unwrap(),
+    ];
+    let cannot_run = Regex::new(r"^(.+): (Permission denied|No such file or directory|Is a directory|cannot execute .+)$").unwrap();
+
+    let mut reports = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let (message, code, location) = if let Some(captures) = shell.captures(line) {
+            let message = &captures[4];
+            let location = captures.get(1).map(|script| {
+                Location::new(script.as_str(), captures.get(2).or(captures.get(3)).map(|line| line.as_str()), None)
+            });
+            if let Some(command) = not_found.iter().find_map(|pattern| pattern.captures(message)) {
+                (format!("{}: command not found", &command[1]), "127", location)
+            } else if cannot_run.is_match(message) {
+                (message.to_string(), "126", location)
+            } else {
+                continue;
+            }
+        } else if let Some(captures) = env.captures(line) {
+            (format!("{}: command not found", &captures[1]), "127", None)
+        } else {
+            continue;
+        };
+        reports.push((index, ErrorReport::new(Tool::Shell, Severity::Error, &message).with_code(Some(code)).with_location(location)));
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_fixture;
+
+    #[test]
+    fn test_shell_errors() {
+        let summaries = parse_fixture(parse, include_str!("fixtures/shell.txt")).iter().map(ErrorReport::to_string).collect::<Vec<_>>();
+        assert_eq!(summaries, [
+            "shell error 127: kubctl: command not found",
+            "shell error 126: ./deploy.sh: Permission denied",
+            "shell error 127: pyhton: command not found",
+            "shell error 127: jq: command not found",
+            "shell error 127 at ./setup.sh:4: yarn: command not found",
+            "shell error 127: python: command not found",
+        ]);
+    }
+}
@@ -1,9 +1,107 @@
+use std::borrow::Cow;
 use std::error::Error;
-use std::io::Write;
-
-pub async fn get_user_input() -> Result<String, Box<dyn Error>> {
-    std::io::stdout().flush().unwrap();
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
-    Ok(input.trim().to_string())
-}
\ No newline at end of file
+use std::path::PathBuf;
+
+use crossterm::style::Stylize;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::history::FileHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Cmd, Completer, Config, Editor, Helper, Hinter, KeyCode, KeyEvent, Modifiers};
+
+const MAX_HISTORY: usize = 1000;
+
+/// Reads chat messages with line editing and a history kept in
+/// `~/.k-aiti/history.txt`. Alt-Enter inserts a newline, and Enter inside an
+/// unclosed ``` fence continues the message on the next line.
+pub struct LineEditor {
+    editor: Editor<PromptHelper, FileHistory>,
+    history: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> Result<LineEditor, Box<dyn Error>> {
+        let config = Config::builder()
+            .max_history_size(MAX_HISTORY)?
+            .history_ignore_dups(true)?
+            // Lines starting with a space are left out of the history, e.g. ones with secrets
+            .history_ignore_space(true)
+            .bracketed_paste(true)
+            .build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(PromptHelper));
+        editor.bind_sequence(KeyEvent(KeyCode::Enter, Modifiers::ALT), Cmd::Newline);
+
+        let history = dirs::home_dir().map(|home| home.join(".k-aiti").join("history.txt"));
+        if let Some(path) = &history {
+            // A missing history file just means nothing has been typed yet
+            editor.load_history(path).ok();
+        }
+        Ok(LineEditor { editor, history })
+    }
+
+    /// Reads the next message. Ctrl-C discards the current line and returns an
+    /// empty message; Ctrl-D (end of input) returns `None`.
+    pub fn read(&mut self, prompt: &str) -> Result<Option<String>, Box<dyn Error>> {
+        self.read_with(prompt, "")
+    }
+
+    /// Like `read`, with the line starting out as `initial` for editing.
+    pub fn read_with(&mut self, prompt: &str, initial: &str) -> Result<Option<String>, Box<dyn Error>> {
+        match self.editor.readline_with_initial(prompt, (initial, "")) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    self.editor.add_history_entry(line.as_str())?;
+                    if let Some(path) = &self.history {
+                        if let Some(parent_dir) = path.parent() {
+                            std::fs::create_dir_all(parent_dir)?;
+                        }
+                        self.editor.append_history(path)?;
+                    }
+                }
+                Ok(Some(line.trim().to_string()))
+            }
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
+
+#[derive(Completer, Helper, Hinter)]
+struct PromptHelper;
+
+impl Highlighter for PromptHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _default: bool) -> Cow<'b, str> {
+        Cow::Owned(prompt.cyan().bold().to_string())
+    }
+}
+
+impl Validator for PromptHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if has_open_fence(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+/// Whether `input` opens a ``` block without closing it.
+fn has_open_fence(input: &str) -> bool {
+    input.lines().filter(|line| line.trim_start().starts_with("```")).count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fenced_blocks_continue_until_closed() {
+        assert!(!has_open_fence("why does this fail?"));
+        assert!(has_open_fence("why does this fail?\n```rust"));
+        assert!(has_open_fence("```\nerror[E0382]: borrow of moved value"));
+        assert!(!has_open_fence("```\nerror[E0382]: borrow of moved value\n```"));
+        assert!(has_open_fence("```\none\n```\nand\n  ```"));
+    }
+}
@@ -1,54 +1,288 @@
+use std::path::PathBuf;
+
 use clap::ArgMatches;
 use crate::config::ConfigTrait;
+use crate::terminal_capture::{records, shell_init};
 
+pub mod ask_mode;
 pub mod chat_mode;
+pub mod clipboard;
+pub mod code_blocks;
+pub mod compare_mode;
 pub mod debug_mode;
+pub mod error_detection;
 pub mod input_provider;
 pub mod config_menu;
+pub mod run_mode;
 pub mod user_profile;
+pub mod sessions;
+pub mod usage_report;
 mod ui;
 
 pub async fn process_command(matches: ArgMatches) {
     if let Some(_) = matches.subcommand_matches("search") {
-    } else if let Some(_) = matches.subcommand_matches("debug") {
-    } else if let Some(_) = matches.subcommand_matches("chat") { 
-        start_chat().await;
+    } else if let Some(debug_matches) = matches.subcommand_matches("debug") {
+        let scrollback_lines = match debug_matches.value_of("lines").map(|lines| lines.parse::<usize>()) {
+            Some(Ok(lines)) => Some(lines),
+            Some(Err(_)) => {
+                eprintln!("Error: the number of lines must be a positive integer");
+                std::process::exit(ask_mode::EXIT_USAGE);
+            }
+            None => None,
+        };
+        let options = debug_mode::DebugOptions {
+            error: debug_matches.value_of("error").map(String::from),
+            scrollback_lines,
+        };
+        start_debug(options).await;
+    } else if let Some(chat_matches) = matches.subcommand_matches("chat") {
+        let system_prompt = match read_system_prompt(chat_matches) {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        };
+        let resume = match chat_matches.value_of("resume") {
+            Some(id) => match sessions::SessionStore::default_location().and_then(|store| store.load(id)) {
+                Ok(session) => Some(session),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            },
+            None => None,
+        };
+        let options = chat_mode::ChatOptions {
+            system_prompt,
+            record: chat_matches.value_of("record").map(PathBuf::from),
+            resume,
+            raw: chat_matches.is_present("raw"),
+            tui: chat_matches.is_present("tui"),
+            files: chat_matches.values_of("file").map(|files| files.map(String::from).collect()).unwrap_or_default(),
+            ..Default::default()
+        };
+        start_chat(options).await;
+    } else if let Some(ask_matches) = matches.subcommand_matches("ask") {
+        let format = match ask_matches.value_of("format").map(|format| format.parse::<ask_mode::OutputFormat>()) {
+            Some(Ok(format)) => Some(format),
+            Some(Err(e)) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(ask_mode::EXIT_USAGE);
+            }
+            None => None,
+        };
+        let system_prompt = match read_system_prompt(ask_matches) {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(ask_mode::EXIT_USAGE);
+            }
+        };
+        let options = ask_mode::AskOptions {
+            prompt: ask_matches.value_of("prompt").map(String::from),
+            format,
+            system_prompt,
+        };
+        start_ask(options).await;
+    } else if let Some(compare_matches) = matches.subcommand_matches("compare") {
+        if compare_matches.is_present("stats") {
+            if let Err(e) = compare_mode::run_compare_stats() {
+                eprintln!("Error reading comparisons: {}", e);
+            }
+            return;
+        }
+        let layout = match compare_matches.value_of("layout").map(|layout| layout.parse::<compare_mode::Layout>()) {
+            Some(Ok(layout)) => Some(layout),
+            Some(Err(e)) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(ask_mode::EXIT_USAGE);
+            }
+            None => None,
+        };
+        let system_prompt = match read_system_prompt(compare_matches) {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(ask_mode::EXIT_USAGE);
+            }
+        };
+        let options = compare_mode::CompareOptions {
+            prompt: compare_matches.value_of("prompt").map(String::from),
+            models: compare_matches.value_of("models")
+                .map(|models| models.split(',').map(str::trim).filter(|id| !id.is_empty()).map(String::from).collect())
+                .unwrap_or_default(),
+            layout,
+            system_prompt,
+        };
+        start_compare(options).await;
+    } else if let Some(run_matches) = matches.subcommand_matches("run") {
+        let options = run_mode::RunOptions {
+            command: run_matches.values_of("command").map(|args| args.map(String::from).collect()).unwrap_or_default(),
+        };
+        match run_mode::run_command(options).await {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(shell_init_matches) = matches.subcommand_matches("shell-init") {
+        let shell = match shell_init_matches.value_of("shell").unwrap_or_default().parse::<shell_init::Shell>() {
+            Ok(shell) => shell,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(ask_mode::EXIT_USAGE);
+            }
+        };
+        let home = dirs::home_dir().unwrap_or_default();
+        print!("{}", shell_init::script(shell, &home.join(".k-aiti").join(records::SPOOL_FILE)));
+    } else if let Some(last_code_matches) = matches.subcommand_matches("last-code") {
+        let number = match last_code_matches.value_of("number").map(|number| number.parse::<usize>()) {
+            Some(Ok(number)) => Some(number),
+            Some(Err(_)) => {
+                eprintln!("Error: the code block number must be a positive integer");
+                return;
+            }
+            None => None,
+        };
+        let options = code_blocks::LastCodeOptions {
+            session: last_code_matches.value_of("session").unwrap_or("last").to_string(),
+            number,
+            copy: last_code_matches.is_present("copy"),
+            output: last_code_matches.value_of("output").map(PathBuf::from),
+        };
+        if let Err(e) = code_blocks::run_last_code(options) {
+            eprintln!("Error: {}", e);
+        }
+    } else if let Some(usage_matches) = matches.subcommand_matches("usage") {
+        let options = usage_report::UsageOptions {
+            days: usage_matches.value_of("days").and_then(|days| days.parse().ok()).unwrap_or(7),
+            sessions: usage_matches.value_of("sessions").and_then(|sessions| sessions.parse().ok()).unwrap_or(10),
+        };
+        if let Err(e) = usage_report::run_usage_report(options) {
+            eprintln!("Error reading usage: {}", e);
+        }
+    } else if let Some(sessions_matches) = matches.subcommand_matches("sessions") {
+        let result = match sessions_matches.subcommand() {
+            Some(("show", show_matches)) => sessions::show_session(show_matches.value_of("id").unwrap_or("last")),
+            Some(("delete", delete_matches)) => sessions::delete_session(delete_matches.value_of("id").unwrap_or("last")),
+            Some(("export", export_matches)) => {
+                let output = export_matches.value_of("output").map(PathBuf::from);
+                let format = match export_matches.value_of("format") {
+                    Some(format) => format.parse::<sessions::ExportFormat>().map_err(Into::into),
+                    None => Ok(output.as_deref().map(sessions::ExportFormat::from_path).unwrap_or(sessions::ExportFormat::Markdown)),
+                };
+                format.and_then(|format| sessions::export_session(export_matches.value_of("id").unwrap_or("last"), format, output.as_deref()))
+            }
+            _ => sessions::list_sessions(),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+        }
     } else if let Some(_) = matches.subcommand_matches("config") {
         start_config_menu().await;
     }else {
     }
 }
 
-async fn start_chat() {
-    // let chat_history_path = "chat_history.txt";
+fn read_system_prompt(matches: &ArgMatches) -> Result<Option<String>, String> {
+    if let Some(prompt) = matches.value_of("system") {
+        return Ok(Some(prompt.to_string()));
+    }
+    match matches.value_of("system-file") {
+        Some(path) => std::fs::read_to_string(path)
+            .map(|prompt| Some(prompt.trim().to_string()))
+            .map_err(|e| format!("Failed to read system prompt file {}: {}", path, e)),
+        None => Ok(None),
+    }
+}
 
-    // let chat_history = if Path::new(chat_history_path).exists() {
-    //     let content = std::fs::read_to_string(chat_history_path).expect("Failed to read chat history");
-    //     content.lines().map(|line| line.to_string()).collect()
-    // } else {
-    //     Vec::new()
-    // };
+async fn start_chat(mut options: chat_mode::ChatOptions) {
     let config = match crate::config::user::settings::SettingsConfig::read() {
         Ok(path) => path,
         Err(error) => {
             panic!("{}", error);
         }
     };
-    let c_model = match crate::config::get_model_by_mode(&config, crate::config::ModeSelection::Chat) {
+    let chat_model = crate::config::get_model_by_mode(&config, crate::config::ModeSelection::Chat);
+    // Resumed sessions continue with the model they were started with while it is still configured
+    let c_model = match &options.resume {
+        Some(session) => config.models.iter().find(|model| model.id == session.model_id).or_else(|| {
+            println!("Model {} is no longer configured; continuing with the chat model.", session.model_id);
+            chat_model
+        }),
+        None => chat_model,
+    };
+    let c_model = match c_model {
         Some(model) => model,
         _ => {
             println!("Corrupted settings file found.");
             return;
         }
     };
-    match chat_mode::run_chat_mode(c_model).await {
+    options.pricing = config.pricing.clone();
+    options.models = config.models.clone();
+    match chat_mode::run_chat_mode(c_model, options).await {
         Ok(_) => println!("Chat ended."),
         Err(e) => eprintln!("Error: {}", e),
     };
+}
+
+async fn start_ask(options: ask_mode::AskOptions) {
+    let config = match crate::config::user::settings::SettingsConfig::read() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error reading settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let c_model = match crate::config::get_model_by_mode(&config, crate::config::ModeSelection::Completion) {
+        Some(model) => model,
+        None => {
+            eprintln!("No model is configured for completion mode.");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = ask_mode::run_ask(c_model, options).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(ask_mode::exit_code(e.as_ref()));
+    }
+}
 
-    // File::create(chat_history_path)
-    //     .and_then(|mut file| file.write_all(chat_history.join(STOP_PHRASE).as_bytes()))
-    //     .expect("Failed to save chat history");
+async fn start_debug(options: debug_mode::DebugOptions) {
+    let config = match crate::config::user::settings::SettingsConfig::read() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error reading settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let c_model = match crate::config::get_model_by_mode(&config, crate::config::ModeSelection::Completion) {
+        Some(model) => model,
+        None => {
+            eprintln!("No model is configured for completion mode.");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = debug_mode::run_debug(c_model, &config.capture, options).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(ask_mode::exit_code(e.as_ref()));
+    }
+}
+
+async fn start_compare(options: compare_mode::CompareOptions) {
+    let config = match crate::config::user::settings::SettingsConfig::read() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error reading settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = compare_mode::run_compare(&config.models, &config.pricing, options).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(ask_mode::exit_code(e.as_ref()));
+    }
 }
 
 async fn start_config_menu() {
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::process::ExitStatus;
+
+use crate::config::user::settings::SettingsConfig;
+use crate::config::{get_model_by_mode, ConfigTrait, ModeSelection};
+use crate::execution::debug_mode::{self, Failure};
+
+#[cfg(target_family = "unix")]
+mod pty;
+#[cfg(target_family = "unix")]
+use pty::run_captured;
+
+#[cfg(target_family = "windows")]
+mod pipes;
+#[cfg(target_family = "windows")]
+use pipes::run_captured;
+
+/// How much of the end of a command's output is kept for the explanation.
+const MAX_RECORDED: usize = 256 * 1024;
+
+pub struct RunOptions {
+    /// The program and its arguments.
+    pub command: Vec<String>,
+}
+
+/// Runs a command with its output going to the terminal as usual. When it
+/// fails, the end of the output is sent to the debug analysis. Returns the
+/// command's exit code.
+pub async fn run_command(options: RunOptions) -> Result<i32, Box<dyn Error>> {
+    let command = options.command;
+    if command.is_empty() {
+        return Err("No command to run; use kaiti run -- <command> [args...]".into());
+    }
+    let command_line = quote(&command);
+    let (status, recording) = {
+        let command = command.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut recording = Recording::default();
+            run_captured(&command, &mut recording).map(|status| (status, recording))
+        }).await?
+    }.map_err(|e| format!("Failed to run {}: {}", command[0], e))?;
+
+    let exit_code = exit_code(status);
+    if exit_code == 0 {
+        return Ok(0);
+    }
+    eprintln!("\n── {} exited with code {}; asking for an explanation ──\n", command_line, exit_code);
+
+    let failure = Failure { command: command_line, exit_code, output: plain_text(&recording.bytes) };
+    // Scripts still see the command's exit code when the explanation fails
+    if let Err(e) = explain(failure).await {
+        eprintln!("Could not explain the failure: {}", e);
+    }
+    Ok(exit_code)
+}
+
+async fn explain(failure: Failure) -> Result<(), Box<dyn Error>> {
+    let config = SettingsConfig::read()?;
+    let c_model = get_model_by_mode(&config, ModeSelection::Completion).ok_or("No model is configured for completion mode.")?;
+    debug_mode::explain_failure(c_model, failure).await
+}
+
+/// The end of what a command printed, at least its last `MAX_RECORDED` bytes.
+#[derive(Default)]
+pub struct Recording {
+    bytes: Vec<u8>,
+}
+
+impl Recording {
+    pub fn push(&mut self, data: &[u8]) {
+        self.bytes.extend_from_slice(data);
+        // Trimmed in batches so long output is not copied on every write
+        if self.bytes.len() > 2 * MAX_RECORDED {
+            self.bytes.drain(..self.bytes.len() - MAX_RECORDED);
+        }
+    }
+}
+
+/// The exit code a shell would report, including 128 + the signal for a
+/// command that was killed.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// The command line as it could be typed back into a shell.
+fn quote(command: &[String]) -> String {
+    command.iter()
+        .map(|arg| {
+            let plain = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if plain { arg.clone() } else { format!("'{}'", arg.replace('\'', "'\\''")) }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Terminal output as the text it left on screen: escape sequences are
+/// dropped and carriage returns overwrite the line, so progress bars only
+/// keep their final state.
+fn plain_text(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    let mut output = String::new();
+    let mut line = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI runs to a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC runs to BEL or ST
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\r' => line.clear(),
+            '\n' => {
+                output.push_str(line.trim_end());
+                output.push('\n');
+                line.clear();
+            }
+            '\x08' => {
+                line.pop();
+            }
+            c if c.is_control() && c != '\t' => {}
+            c => line.push(c),
+        }
+    }
+    output.push_str(line.trim_end());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_keeps_what_was_left_on_screen() {
+        let raw = b"\x1b[1m\x1b[31merror\x1b[0m: build failed\r\n\
+                    \x1b]0;cargo\x07Downloading 10%\rDownloading 100%\r\n\
+                    typo\x08\x08po\n";
+        assert_eq!(plain_text(raw), "error: build failed\nDownloading 100%\ntypo\n");
+    }
+
+    #[test]
+    fn test_recording_keeps_the_end() {
+        let mut recording = Recording::default();
+        recording.push(&vec![b'a'; MAX_RECORDED * 2]);
+        recording.push(b"end");
+        assert_eq!(recording.bytes.len(), MAX_RECORDED);
+        assert!(recording.bytes.ends_with(b"aend"));
+    }
+
+    #[test]
+    fn test_quote() {
+        let command = ["grep", "-r", "it's here", "src/"].map(String::from);
+        assert_eq!(quote(&command), "grep -r 'it'\\''s here' src/");
+    }
+}
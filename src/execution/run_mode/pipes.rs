@@ -0,0 +1,62 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+
+use super::Recording;
+
+enum Output {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+/// Runs `command` with its stdout and stderr piped through kaiti, which
+/// copies them to its own and to `recording`. Windows has no pty to run
+/// it on, so programs that check for a terminal may print less.
+pub fn run_captured(command: &[String], recording: &mut Recording) -> io::Result<ExitStatus> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let readers = [
+        forward(child.stdout.take(), sender.clone(), Output::Stdout),
+        forward(child.stderr.take(), sender, Output::Stderr),
+    ];
+    // Ends once both pipes are closed
+    for output in receiver {
+        match output {
+            Output::Stdout(data) => {
+                io::stdout().write_all(&data)?;
+                io::stdout().flush()?;
+                recording.push(&data);
+            }
+            Output::Stderr(data) => {
+                io::stderr().write_all(&data)?;
+                recording.push(&data);
+            }
+        }
+    }
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    child.wait()
+}
+
+fn forward<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    sender: mpsc::Sender<Output>,
+    wrap: fn(Vec<u8>) -> Output,
+) -> Option<std::thread::JoinHandle<()>> {
+    let mut pipe = pipe?;
+    Some(std::thread::spawn(move || {
+        let mut buffer = [0; 8192];
+        while let Ok(read) = pipe.read(&mut buffer) {
+            if read == 0 || sender.send(wrap(buffer[..read].to_vec())).is_err() {
+                break;
+            }
+        }
+    }))
+}
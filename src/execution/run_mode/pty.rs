@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+
+use super::Recording;
+
+/// Runs `command` on a new pseudo-terminal, so it behaves as it would when
+/// run directly: colours, progress output and prompts all work. Everything
+/// it prints is copied to stdout and to `recording`, and keystrokes are
+/// passed through to it.
+pub fn run_captured(command: &[String], recording: &mut Recording) -> io::Result<ExitStatus> {
+    let (master, slave) = open_pty()?;
+    let mut child = {
+        let mut process = Command::new(&command[0]);
+        process
+            .args(&command[1..])
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            process.pre_exec(|| {
+                // Make the pty the controlling terminal of a new session so
+                // Ctrl-C and job control reach the command
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        process.spawn()?
+        // Dropping `process` closes our copies of the slave side, so reads
+        // from the master end once the command and its children exit
+    };
+
+    // Keys go straight to the command, which does its own line editing
+    let _raw_mode = RawMode::enable();
+    let mut input = File::from(master.try_clone()?);
+    std::thread::spawn(move || {
+        let mut buffer = [0; 1024];
+        loop {
+            match io::stdin().read(&mut buffer) {
+                Ok(0) => {
+                    // Piped input has ended; pass that on as Ctrl-D
+                    let _ = input.write_all(&[4]);
+                    break;
+                }
+                Ok(read) => {
+                    if input.write_all(&buffer[..read]).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    });
+
+    let mut output = File::from(master);
+    let mut stdout = io::stdout();
+    let mut buffer = [0; 8192];
+    loop {
+        match output.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => {
+                stdout.write_all(&buffer[..read])?;
+                stdout.flush()?;
+                recording.push(&buffer[..read]);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            // Linux reports EIO once the slave side is closed
+            Err(_) => break,
+        }
+    }
+    child.wait()
+}
+
+/// A pty sized like the terminal kaiti is running in.
+fn open_pty() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut size = libc::winsize { ws_row: 24, ws_col: 80, ws_xpixel: 0, ws_ypixel: 0 };
+    let (mut master, mut slave) = (0, 0);
+    // SAFETY: the pointers are valid for the duration of the calls and the
+    // descriptors returned by openpty are owned from here on
+    unsafe {
+        let mut current = size;
+        if libc::ioctl(io::stdout().as_raw_fd(), libc::TIOCGWINSZ, &mut current) == 0 && current.ws_col > 0 {
+            size = current;
+        }
+        if libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), &size) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let (master, slave) = (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave));
+        // Keep the master side out of the command
+        libc::fcntl(master.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+        libc::fcntl(slave.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+        Ok((master, slave))
+    }
+}
+
+/// Raw mode on stdin for as long as this is held, when stdin is a terminal.
+struct RawMode(bool);
+
+impl RawMode {
+    fn enable() -> RawMode {
+        RawMode(io::stdin().is_terminal() && crossterm::terminal::enable_raw_mode().is_ok())
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if self.0 {
+            let _ = crossterm::terminal::disable_raw_mode();
+        }
+    }
+}
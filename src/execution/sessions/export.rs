@@ -0,0 +1,263 @@
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+
+use crate::execution::chat_mode::chat_log::ChatLogEntry;
+use crate::execution::chat_mode::markdown::{syntaxes, theme};
+use super::Session;
+
+const STYLE: &str = "body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; max-width: 52rem; margin: 2rem auto; padding: 0 1rem; color: #1f2328; line-height: 1.5; }
+header { border-bottom: 1px solid #d0d7de; margin-bottom: 1.5rem; }
+.meta { color: #59636e; font-size: 0.9rem; }
+article { border: 1px solid #d0d7de; border-radius: 6px; padding: 0.25rem 1rem; margin-bottom: 1rem; }
+article.you { background: #f6f8fa; }
+article.system, article.summary { color: #59636e; }
+.speaker { font-weight: 600; margin-top: 0.5rem; }
+.speaker time, .speaker .model { color: #59636e; font-weight: normal; font-size: 0.85rem; margin-left: 0.5rem; }
+pre { padding: 0.75rem; border-radius: 6px; overflow-x: auto; font-size: 0.85rem; }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: 0.25rem 0.75rem; }
+.interrupted { color: #9a6700; font-style: italic; }";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "json" => Ok(ExportFormat::Json),
+            "html" | "htm" => Ok(ExportFormat::Html),
+            _ => Err(format!("Unknown export format {}; expected md, json or html", value)),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// The format matching a file's extension, defaulting to Markdown.
+    pub fn from_path(path: &Path) -> ExportFormat {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.to_lowercase().parse().ok())
+            .unwrap_or(ExportFormat::Markdown)
+    }
+}
+
+pub fn export(session: &Session, format: ExportFormat) -> Result<String, Box<dyn Error>> {
+    Ok(match format {
+        ExportFormat::Markdown => export_markdown(session),
+        // The saved session is already the complete record, so it is exported as-is
+        ExportFormat::Json => serde_json::to_string_pretty(session)? + "\n",
+        ExportFormat::Html => export_html(session),
+    })
+}
+
+fn title(session: &Session) -> &str {
+    session.title.as_deref().unwrap_or("Untitled chat")
+}
+
+fn format_time(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// The model and time shown beside a message's speaker, when known.
+fn details(entry: &ChatLogEntry) -> Vec<String> {
+    entry.model.iter().cloned()
+        .chain(entry.created_at.as_deref().map(format_time))
+        .collect()
+}
+
+fn summary_line(session: &Session) -> String {
+    format!("Session {} with {}, started {}, updated {} · {} tokens in {} requests, ${:.4}",
+        session.id,
+        session.model_id,
+        format_time(&session.created_at),
+        format_time(&session.updated_at),
+        session.usage.total_tokens(),
+        session.usage.requests,
+        session.usage.cost)
+}
+
+fn export_markdown(session: &Session) -> String {
+    let mut markdown = format!("# {}\n\n{}\n", title(session), summary_line(session));
+    for entry in &session.messages {
+        let details = details(entry);
+        let details = if details.is_empty() { String::new() } else { format!(" ({})", details.join(", ")) };
+        let interrupted = if entry.interrupted { "\n\n*(interrupted)*" } else { "" };
+        markdown.push_str(&format!("\n## {}{}\n\n{}{}\n", entry.speaker(), details, entry.display_content(), interrupted));
+    }
+    markdown
+}
+
+fn export_html(session: &Session) -> String {
+    let mut body = String::new();
+    for entry in &session.messages {
+        let class = entry.speaker().split_whitespace().next().unwrap_or_default().to_lowercase();
+        let model = entry.model.as_deref()
+            .map(|model| format!("<span class=\"model\">{}</span>", escape_html(model)))
+            .unwrap_or_default();
+        let time = entry.created_at.as_deref()
+            .map(|time| format!("<time datetime=\"{}\">{}</time>", escape_html(time), format_time(time)))
+            .unwrap_or_default();
+        let content = markdown_to_html(entry.display_content());
+        // Attached files are long, so they start collapsed
+        let content = if entry.attachment {
+            format!("<details><summary>{}</summary>\n{}</details>\n", attachment_label(entry), content)
+        } else {
+            content
+        };
+        let interrupted = if entry.interrupted { "<p class=\"interrupted\">(interrupted)</p>\n" } else { "" };
+        body.push_str(&format!("<article class=\"{}\">\n<div class=\"speaker\">{}{}{}</div>\n{}{}</article>\n",
+            class, escape_html(entry.speaker()), model, time, content, interrupted));
+    }
+
+    format!("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{style}\n</style>\n</head>\n<body>\n<header>\n<h1>{title}</h1>\n<p class=\"meta\">{meta}</p>\n</header>\n{body}</body>\n</html>\n",
+        title = escape_html(title(session)),
+        style = STYLE,
+        meta = escape_html(&summary_line(session)),
+        body = body)
+}
+
+fn attachment_label(entry: &ChatLogEntry) -> String {
+    let files = entry.message.content.lines().filter(|line| line.starts_with("File: ")).count();
+    format!("{} file{}", files, if files == 1 { "" } else { "s" })
+}
+
+/// Renders a message's markdown with inline-styled, highlighted code blocks.
+/// Raw HTML in messages is shown as text rather than trusted, and links and
+/// images with a scheme other than http, https or mailto keep only their text.
+fn markdown_to_html(markdown: &str) -> String {
+    let mut events = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    // Whether each open link or image is kept, so its end matches
+    let mut links = Vec::new();
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or_default().to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((language, String::new()));
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, source)) = &mut code {
+                    source.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, source)) = code.take() {
+                    events.push(Event::Html(CowStr::from(highlight(&language, &source))));
+                }
+            }
+            Event::Start(Tag::Link { ref dest_url, .. } | Tag::Image { ref dest_url, .. }) => {
+                let safe = safe_url(dest_url);
+                links.push(safe);
+                if safe {
+                    events.push(event);
+                }
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                if links.pop().unwrap_or(true) {
+                    events.push(event);
+                }
+            }
+            Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+            event => events.push(event),
+        }
+    }
+    let mut output = String::new();
+    html::push_html(&mut output, events.into_iter());
+    output
+}
+
+/// Relative links and http, https and mailto URLs. Browsers ignore tabs,
+/// newlines and leading spaces in a URL, so they are dropped before looking
+/// for the scheme.
+fn safe_url(url: &str) -> bool {
+    let url = url.chars().filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control()).collect::<String>();
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') =>
+            ["http", "https", "mailto"].iter().any(|scheme| url[..end].eq_ignore_ascii_case(scheme)),
+        _ => true,
+    }
+}
+
+fn highlight(language: &str, source: &str) -> String {
+    let syntax = syntaxes().find_syntax_by_token(language)
+        .unwrap_or_else(|| syntaxes().find_syntax_plain_text());
+    syntect::html::highlighted_html_for_string(source, syntaxes(), syntax, theme())
+        .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>\n", escape_html(source)))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::Role;
+
+    fn session() -> Session {
+        let question = ChatLogEntry {
+            created_at: Some(String::from("2024-05-01T09:30:00+00:00")),
+            ..ChatLogEntry::new(Role::User, String::from("How do I list files? <b>now</b>"))
+        };
+        let answer = ChatLogEntry {
+            created_at: Some(String::from("2024-05-01T09:30:05+00:00")),
+            model: Some(String::from("gpt-4o")),
+            ..ChatLogEntry::new(Role::Assistant, String::from("Use:\n\n```sh\nls -la\n```"))
+        };
+        Session {
+            id: String::from("20240501-093000"),
+            title: Some(String::from("How do I list files?")),
+            created_at: String::from("2024-05-01T09:30:00+00:00"),
+            updated_at: String::from("2024-05-01T09:30:05+00:00"),
+            messages: vec![question, answer],
+            ..Session::new(String::from("chatgpt"))
+        }
+    }
+
+    #[test]
+    fn test_exports_markdown_and_json() {
+        let markdown = export(&session(), ExportFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# How do I list files?\n\nSession 20240501-093000 with chatgpt, started 2024-05-01 09:30"));
+        assert!(markdown.contains("\n## You (2024-05-01 09:30)\n\nHow do I list files?"));
+        assert!(markdown.ends_with("\n## AI (gpt-4o, 2024-05-01 09:30)\n\nUse:\n\n```sh\nls -la\n```\n"));
+
+        let json = export(&session(), ExportFormat::Json).unwrap();
+        assert_eq!(serde_json::from_str::<Session>(&json).unwrap(), session());
+        assert_eq!(ExportFormat::from_path(Path::new("chat.HTML")), ExportFormat::Html);
+        assert_eq!(ExportFormat::from_path(Path::new("chat.txt")), ExportFormat::Markdown);
+    }
+
+    #[test]
+    fn test_html_is_self_contained_and_highlighted() {
+        let html = export(&session(), ExportFormat::Html).unwrap();
+        assert!(html.contains("<style>"));
+        assert!(!html.contains("<link") && !html.contains("<script"));
+        assert!(html.contains("&lt;b&gt;now&lt;/b&gt;"));
+        assert!(html.contains("<pre style=\"background-color:"));
+        assert!(html.contains("<span class=\"model\">gpt-4o</span>"));
+
+        let answer = markdown_to_html("[docs](https://example.com) [run](JavaScript:alert(1)) \
+                                       ![chart](data:image/svg+xml,x) [mail](MAILTO:dev@example.com) [up](../notes.md)");
+        assert_eq!(answer, "<p><a href=\"https://example.com\">docs</a> run chart \
+                            <a href=\"MAILTO:dev@example.com\">mail</a> <a href=\"../notes.md\">up</a></p>\n");
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::path::Path;
+
+use crossterm::style::Stylize;
+
+use crate::ai::chat_types::Role;
+
+mod export;
+mod store;
+
+pub use export::{export, ExportFormat};
+pub use store::{Session, SessionStore};
+
+pub fn list_sessions() -> Result<(), Box<dyn Error>> {
+    let sessions = SessionStore::default_location()?.list()?;
+    if sessions.is_empty() {
+        println!("No saved sessions. Sessions are saved as you chat with `kaiti chat`.");
+        return Ok(());
+    }
+    for session in sessions {
+        let updated = chrono::DateTime::parse_from_rfc3339(&session.updated_at)
+            .map(|updated| updated.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or(session.updated_at);
+        println!("{}  {}  {:<10}  {:>4} messages  {:>8} tokens  {}",
+            session.id,
+            updated,
+            session.model_id,
+            session.messages.len(),
+            session.usage.total_tokens(),
+            session.title.as_deref().unwrap_or("(untitled)"));
+    }
+    Ok(())
+}
+
+pub fn show_session(id: &str) -> Result<(), Box<dyn Error>> {
+    let session = SessionStore::default_location()?.load(id)?;
+    println!("{}", session.title.as_deref().unwrap_or("(untitled)").bold());
+    println!("Session {} with {}, started {}", session.id, session.model_id, session.created_at);
+    println!("{} tokens in {} requests, ${:.4}\n",
+        session.usage.total_tokens(), session.usage.requests, session.usage.cost);
+    for entry in &session.messages {
+        let speaker = match entry.message.role {
+            Role::System if entry.summary => "Summary".dark_grey(),
+            Role::System => "System".dark_grey(),
+            Role::User => "You".cyan(),
+            Role::Assistant => "AI ".green(),
+        };
+        println!("{}: {}\n", speaker.bold(), entry.message.content);
+        if !entry.branches.is_empty() {
+            let branch = format!("(branch {} of {} starts here; resume the chat and use /prev and /next to switch)",
+                entry.branch + 1, entry.branches.len() + 1);
+            println!("{}\n", branch.dark_yellow());
+        }
+    }
+    Ok(())
+}
+
+/// Writes a session to `output`, or to stdout without one.
+pub fn export_session(id: &str, format: ExportFormat, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let session = SessionStore::default_location()?.load(id)?;
+    let exported = export(&session, format)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, exported).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            eprintln!("Exported session {} to {}", session.id, path.display());
+        }
+        None => print!("{}", exported),
+    }
+    Ok(())
+}
+
+pub fn delete_session(id: &str) -> Result<(), Box<dyn Error>> {
+    let id = SessionStore::default_location()?.delete(id)?;
+    println!("Deleted session {}", id);
+    Ok(())
+}
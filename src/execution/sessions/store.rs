@@ -0,0 +1,208 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::chat_types::Role;
+use crate::config::user::usage::UsageTotals;
+use crate::execution::chat_mode::chat_log::ChatLogEntry;
+
+const TITLE_LENGTH: usize = 60;
+
+/// A saved chat conversation, stored as `~/.k-aiti/sessions/<id>.json`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub title: Option<String>,
+    /// The `ModelConfig` id the conversation was held with.
+    pub model_id: String,
+    /// RFC 3339 timestamps in local time.
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default)]
+    pub usage: UsageTotals,
+    pub messages: Vec<ChatLogEntry>,
+}
+
+impl Session {
+    /// Starts a session whose id is its start time, so ids sort chronologically.
+    pub fn new(model_id: String) -> Session {
+        let now = chrono::Local::now();
+        Session {
+            id: now.format("%Y%m%d-%H%M%S").to_string(),
+            title: None,
+            model_id,
+            created_at: now.to_rfc3339(),
+            updated_at: now.to_rfc3339(),
+            usage: UsageTotals::default(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// The first question asked, cut at a word boundary.
+    pub fn generate_title(&self) -> Option<String> {
+        let question = self.messages.iter().find(|entry| entry.message.role == Role::User && !entry.attachment)?;
+        let line = question.message.content.lines().map(str::trim).find(|line| !line.is_empty())?;
+        if line.chars().count() <= TITLE_LENGTH {
+            return Some(line.to_string());
+        }
+        let cut = line.char_indices().nth(TITLE_LENGTH).map(|(index, _)| index).unwrap_or(line.len());
+        let shortened = match line[..cut].rfind(' ') {
+            Some(space) if space > TITLE_LENGTH / 2 => &line[..space],
+            _ => &line[..cut],
+        };
+        Some(format!("{}…", shortened.trim_end()))
+    }
+}
+
+pub struct SessionStore {
+    directory: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(directory: PathBuf) -> SessionStore {
+        SessionStore { directory }
+    }
+
+    pub fn default_location() -> Result<SessionStore, Box<dyn Error>> {
+        let home = dirs::home_dir().ok_or("Could not find the user's home directory")?;
+        Ok(SessionStore::new(home.join(".k-aiti").join("sessions")))
+    }
+
+    /// Starts a session whose id does not clash with a saved one, which can
+    /// happen when a chat is reset within a second of starting.
+    pub fn create(&self, model_id: String) -> Session {
+        let mut session = Session::new(model_id);
+        let base_id = session.id.clone();
+        let mut suffix = 1;
+        while self.path(&session.id).exists() {
+            suffix += 1;
+            session.id = format!("{}-{}", base_id, suffix);
+        }
+        session
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", id))
+    }
+
+    pub fn save(&self, session: &Session) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.directory)?;
+        // Write to a temporary file first so an interrupted save cannot corrupt the session
+        let path = self.path(&session.id);
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(session)?)?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: &str) -> Result<Session, Box<dyn Error>> {
+        let id = self.resolve(id)?;
+        read_session(&self.path(&id))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<String, Box<dyn Error>> {
+        let id = self.resolve(id)?;
+        fs::remove_file(self.path(&id))?;
+        Ok(id)
+    }
+
+    /// All saved sessions, most recently updated first.
+    pub fn list(&self) -> Result<Vec<Session>, Box<dyn Error>> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+        let mut sessions = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            match read_session(&path) {
+                Ok(session) => sessions.push(session),
+                Err(e) => eprintln!("Skipping unreadable session {}: {}", path.display(), e),
+            }
+        }
+        sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| b.id.cmp(&a.id)));
+        Ok(sessions)
+    }
+
+    /// Resolves `last` to the most recently updated session and checks that
+    /// any other id exists.
+    pub fn resolve(&self, id: &str) -> Result<String, Box<dyn Error>> {
+        if id == "last" {
+            return self.list()?
+                .into_iter()
+                .next()
+                .map(|session| session.id)
+                .ok_or_else(|| "There are no saved sessions".into());
+        }
+        if self.path(id).is_file() {
+            Ok(id.to_string())
+        } else {
+            Err(format!("No session with id {}", id).into())
+        }
+    }
+}
+
+fn read_session(path: &Path) -> Result<Session, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read session {}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str, updated_at: &str, question: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            updated_at: updated_at.to_string(),
+            messages: vec![
+                ChatLogEntry::new(Role::System, String::from("be brief")),
+                ChatLogEntry::new(Role::User, question.to_string()),
+            ],
+            ..Session::new(String::from("chatgpt"))
+        }
+    }
+
+    #[test]
+    fn test_saves_lists_and_resolves_sessions() {
+        let directory = tempfile::tempdir().expect("temp dir");
+        let store = SessionStore::new(directory.path().join("sessions"));
+        assert!(store.list().expect("empty list").is_empty());
+        assert!(store.resolve("last").is_err());
+
+        let older = session("20240501-090000", "2024-05-01T09:30:00+00:00", "How do I rebase?");
+        let newer = session("20240502-090000", "2024-05-02T09:00:00+00:00", "Why is my build failing?");
+        store.save(&older).expect("save");
+        store.save(&newer).expect("save");
+
+        let ids = store.list().expect("list").into_iter().map(|session| session.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["20240502-090000", "20240501-090000"]);
+        assert_eq!(store.load("last").expect("last"), newer);
+        assert_eq!(store.load("20240501-090000").expect("by id"), older);
+
+        assert_eq!(store.delete("last").expect("delete"), "20240502-090000");
+        assert!(store.load("20240502-090000").is_err());
+        assert_eq!(store.resolve("last").expect("remaining"), "20240501-090000");
+
+        let first = store.create(String::from("chatgpt"));
+        store.save(&first).expect("save");
+        assert_ne!(store.create(String::from("chatgpt")).id, first.id);
+    }
+
+    #[test]
+    fn test_titles_come_from_the_first_question() {
+        assert_eq!(session("a", "", "  \nHow do I rebase?\nmore").generate_title().as_deref(), Some("How do I rebase?"));
+
+        let long = "Explain why the borrow checker rejects this closure that captures a mutable reference twice";
+        assert_eq!(
+            session("b", "", long).generate_title().as_deref(),
+            Some("Explain why the borrow checker rejects this closure that…")
+        );
+        assert_eq!(Session::new(String::from("chatgpt")).generate_title(), None);
+    }
+}
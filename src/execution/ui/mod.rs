@@ -1,3 +1,5 @@
 mod stateful_list;
+mod text;
 
-pub use stateful_list::StatefulList;
\ No newline at end of file
+pub use stateful_list::StatefulList;
+pub use text::wrap;
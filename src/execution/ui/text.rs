@@ -0,0 +1,39 @@
+/// Wraps a line at word boundaries to `width` characters, breaking words
+/// that are longer than a whole line.
+pub fn wrap(line: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    for word in line.split_inclusive(' ') {
+        let word_width = word.trim_end().chars().count();
+        if current_width > 0 && current_width + word_width > width {
+            lines.push(current.trim_end().to_string());
+            current.clear();
+            current_width = 0;
+        }
+        let mut rest = word;
+        while rest.trim_end().chars().count() > width {
+            let split = rest.char_indices().nth(width).map(|(index, _)| index).unwrap_or(rest.len());
+            lines.push(rest[..split].to_string());
+            rest = &rest[split..];
+        }
+        current.push_str(rest);
+        current_width += rest.chars().count();
+    }
+    lines.push(current.trim_end().to_string());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_breaks_at_words() {
+        assert_eq!(wrap("the quick brown fox", 10), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap("abcdefghijkl", 5), vec!["abcde", "fghij", "kl"]);
+        assert_eq!(wrap("    indented", 20), vec!["    indented"]);
+        assert_eq!(wrap("", 20), vec![""]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::error::Error;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::config::user::usage::{UsageLedger, UsageTotals};
+
+pub struct UsageOptions {
+    /// Number of days, ending today, listed in the daily breakdown.
+    pub days: u32,
+    /// Number of most recent sessions listed.
+    pub sessions: usize,
+}
+
+pub fn run_usage_report(options: UsageOptions) -> Result<(), Box<dyn Error>> {
+    let ledger = UsageLedger::load()?;
+    let today = chrono::Local::now().date_naive();
+    print!("{}", format_report(&ledger, today, &options));
+    Ok(())
+}
+
+fn format_totals(totals: &UsageTotals) -> String {
+    let estimate = if totals.estimated_requests > 0 { "~" } else { "" };
+    let mut line = format!("{:>4} requests  {}{:>9} in  {}{:>9} out  ${:>9.4}",
+        totals.requests,
+        estimate, totals.prompt_tokens,
+        estimate, totals.completion_tokens,
+        totals.cost);
+    if totals.unpriced_requests > 0 {
+        line.push_str(&format!("  ({} unpriced)", totals.unpriced_requests));
+    }
+    line
+}
+
+fn format_report(ledger: &UsageLedger, today: NaiveDate, options: &UsageOptions) -> String {
+    let mut report = String::new();
+    let today_key = today.format("%Y-%m-%d").to_string();
+    let today_totals = ledger.days.get(&today_key).cloned().unwrap_or_default();
+    report.push_str(&format!("Today ({})\n  {}\n", today_key, format_totals(&today_totals)));
+
+    let mut period = UsageTotals::default();
+    let mut daily = String::new();
+    for offset in (0..options.days as i64).rev() {
+        let day = (today - Duration::days(offset)).format("%Y-%m-%d").to_string();
+        if let Some(totals) = ledger.days.get(&day) {
+            daily.push_str(&format!("  {}  {}\n", day, format_totals(totals)));
+            period.requests += totals.requests;
+            period.prompt_tokens += totals.prompt_tokens;
+            period.completion_tokens += totals.completion_tokens;
+            period.cost += totals.cost;
+            period.estimated_requests += totals.estimated_requests;
+            period.unpriced_requests += totals.unpriced_requests;
+        }
+    }
+    report.push_str(&format!("\nLast {} days\n", options.days));
+    if daily.is_empty() {
+        report.push_str("  No usage recorded\n");
+    } else {
+        report.push_str(&daily);
+        report.push_str(&format!("  {:<10}  {}\n", "Total", format_totals(&period)));
+    }
+
+    if options.sessions > 0 && !ledger.sessions.is_empty() {
+        report.push_str("\nRecent sessions\n");
+        for (id, session) in ledger.sessions.iter().rev().take(options.sessions) {
+            report.push_str(&format!("  {}  {:<24}  {}\n", id, session.model, format_totals(&session.totals)));
+        }
+    }
+
+    let estimated = ledger.days.values().any(|totals| totals.estimated_requests > 0);
+    if estimated {
+        report.push_str("\n~ includes token counts estimated locally where the provider did not report usage\n");
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::ModelUsage;
+    use crate::config::user::usage::UsageRecord;
+
+    #[test]
+    fn test_report_lists_days_in_range_and_recent_sessions() {
+        let mut ledger = UsageLedger::default();
+        let record = UsageRecord {
+            usage: ModelUsage { prompt_tokens: 1200, completion_tokens: 300, total_tokens: 1500 },
+            estimated: false,
+            cost: Some(0.0105),
+        };
+        ledger.record("2024-04-01", "20240401-100000", "gpt-4o", &record);
+        ledger.record("2024-05-01", "20240501-100000", "gpt-4o", &record);
+        ledger.record("2024-05-03", "20240503-100000", "claude-3-5-sonnet", &record);
+
+        let today = NaiveDate::from_ymd_opt(2024, 5, 3).unwrap();
+        let report = format_report(&ledger, today, &UsageOptions { days: 7, sessions: 1 });
+
+        assert!(report.starts_with("Today (2024-05-03)\n     1 requests"));
+        assert!(report.contains("  2024-05-01  "));
+        assert!(!report.contains("  2024-04-01  "));
+        assert!(report.contains("Total          2 requests       2400 in        600 out  $   0.0210"));
+        assert!(report.contains("20240503-100000  claude-3-5-sonnet"));
+        assert!(!report.contains("20240501-100000"));
+        assert!(!report.contains('~'));
+    }
+}
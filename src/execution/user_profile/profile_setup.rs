@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::config::{
     ConfigTrait, 
-    user::settings::{Application, ModelConfig, SettingsConfig, Mode, InteractionModes }
+    user::settings::{Application, CaptureSettings, ModelConfig, ModelPricing, PriceTable, SettingsConfig, Mode, InteractionModes }
 };
 use crate::config::user::profile::ProfileConfig;
 
@@ -60,6 +60,14 @@ struct GptConfig {
     model: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ClaudeConfig {
+    max_tokens: u32,
+    temperature: f32,
+    model: String,
+    api_key_env: String,
+}
+
 fn settings_setup() -> Result<(), Box<dyn std::error::Error>> {
     let gpt = GptConfig {
         model: String::from("gpt-3.5-turbo"),
@@ -67,6 +75,12 @@ fn settings_setup() -> Result<(), Box<dyn std::error::Error>> {
         n: 1,
         temperature: 0.9
     };
+    let claude = ClaudeConfig {
+        model: String::from("claude-3-5-sonnet-latest"),
+        max_tokens: 1000,
+        temperature: 0.9,
+        api_key_env: String::from("ANTHROPIC_API_KEY")
+    };
     let config = SettingsConfig {
         application: {
             Application { 
@@ -81,6 +95,13 @@ fn settings_setup() -> Result<(), Box<dyn std::error::Error>> {
                     name: String::from("ChatGPT"),
                     config: serde_json::to_value(gpt)?
                 }
+            },
+            {
+                ModelConfig {
+                    id: String::from("claude"),
+                    name: String::from("Claude"),
+                    config: serde_json::to_value(claude)?
+                }
             }
         ],
         modes: InteractionModes {
@@ -90,12 +111,28 @@ fn settings_setup() -> Result<(), Box<dyn std::error::Error>> {
             chat: Mode {
                 id: String::from("chatgpt")
             }
-        }
+        },
+        pricing: default_pricing(),
+        capture: CaptureSettings::default(),
     };
     config.write()?;
     Ok(())
 }
 
+// USD per million tokens; users can edit or extend these in settings.json
+fn default_pricing() -> PriceTable {
+    let prices = [
+        ("gpt-3.5-turbo", 0.5, 1.5),
+        ("gpt-4o", 2.5, 10.0),
+        ("gpt-4o-mini", 0.15, 0.6),
+        ("claude-3-5-sonnet", 3.0, 15.0),
+        ("claude-3-5-haiku", 0.8, 4.0),
+    ];
+    PriceTable(prices.iter()
+        .map(|(model, input, output)| (model.to_string(), ModelPricing { input_per_million: *input, output_per_million: *output }))
+        .collect())
+}
+
 pub fn welcome_message() {
     println!("k-aiti installed!");
     println!("");
@@ -1,6 +1,9 @@
 pub mod ai;
+pub mod anthropic;
 pub mod open_ai_gpt;
+pub mod open_ai_compatible;
 pub mod config;
 pub mod execution;
 pub mod models;
+pub mod replay;
 pub mod terminal_capture;
\ No newline at end of file
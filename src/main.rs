@@ -29,17 +29,251 @@ async fn main() {
         )
         .subcommand(
             SubCommand::with_name("debug")
-                .about("Analyzes an error message")
+                .about("Explains an error and how to fix it, from --error, piped output or the terminal")
                 .arg(
                     Arg::new("error")
                         .short('e')
                         .long("error")
                         .value_name("ERROR_MESSAGE")
-                        // .about("Provides an error message to analyze")
+                        .help("The error message or output to explain")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("lines")
+                        .long("lines")
+                        .short('n')
+                        .value_name("LINES")
+                        .help("Lines of tmux or screen scrollback to read, overriding capture.scrollback_lines")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("chat")
+                .about("Chat with an AI")
+                .arg(
+                    Arg::new("system")
+                        .long("system")
+                        .value_name("PROMPT")
+                        .help("System prompt for the conversation, overriding the model's system_prompt")
+                        .takes_value(true)
+                        .conflicts_with("system-file"),
+                )
+                .arg(
+                    Arg::new("system-file")
+                        .long("system-file")
+                        .value_name("FILE")
+                        .help("Reads the system prompt from a file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("resume")
+                        .long("resume")
+                        .value_name("ID")
+                        .help("Continues a saved session by id, or the most recent one with `last`")
+                        .takes_value(true)
+                        .min_values(0)
+                        .max_values(1)
+                        .default_missing_value("last"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .value_name("PATH")
+                        .help("Attaches a file, directory or glob to the conversation; can be repeated")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("raw")
+                        .long("raw")
+                        .help("Prints responses as plain text instead of rendering their markdown"),
+                )
+                .arg(
+                    Arg::new("tui")
+                        .long("tui")
+                        .help("Runs the chat full screen with a session list and status bar"),
+                )
+                .arg(
+                    Arg::new("record")
+                        .long("record")
+                        .value_name("FIXTURE_FILE")
+                        .help("Records the model's responses to a JSONL fixture for replay")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ask")
+                .about("Answers a single prompt, using anything piped to stdin as context")
+                .arg(Arg::new("prompt").help("The question to ask").takes_value(true))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_name("FORMAT")
+                        .help("Output format: text, markdown, json or ndjson-chunks (default: markdown on a terminal, text otherwise)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("system")
+                        .long("system")
+                        .value_name("PROMPT")
+                        .help("System prompt, overriding the model's system_prompt")
+                        .takes_value(true)
+                        .conflicts_with("system-file"),
+                )
+                .arg(
+                    Arg::new("system-file")
+                        .long("system-file")
+                        .value_name("FILE")
+                        .help("Reads the system prompt from a file")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Sends one prompt to several models at once and compares their answers")
+                .arg(Arg::new("prompt").help("The question to ask every model").takes_value(true))
+                .arg(
+                    Arg::new("models")
+                        .long("models")
+                        .short('m')
+                        .value_name("IDS")
+                        .help("Comma-separated ids of the configured models to compare")
+                        .takes_value(true)
+                        .required_unless_present("stats"),
+                )
+                .arg(
+                    Arg::new("layout")
+                        .long("layout")
+                        .value_name("LAYOUT")
+                        .help("columns or sections (default: columns when the terminal is wide enough)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("system")
+                        .long("system")
+                        .value_name("PROMPT")
+                        .help("System prompt sent to every model")
+                        .takes_value(true)
+                        .conflicts_with("system-file"),
+                )
+                .arg(
+                    Arg::new("system-file")
+                        .long("system-file")
+                        .value_name("FILE")
+                        .help("Reads the system prompt from a file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("stats")
+                        .long("stats")
+                        .help("Shows how often each model's answer was preferred in past comparisons"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a command and, if it fails, explains the error and how to fix it")
+                .trailing_var_arg(true)
+                .arg(
+                    Arg::new("command")
+                        .value_name("COMMAND")
+                        .help("The command to run and its arguments, after --")
+                        .required(true)
+                        .multiple_values(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("shell-init")
+                .about("Prints shell hooks that record commands for kaiti debug; eval them in your shell's startup file")
+                .arg(
+                    Arg::new("shell")
+                        .value_name("SHELL")
+                        .help("The shell to print hooks for")
+                        .required(true)
+                        .possible_values(["bash", "zsh"]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("last-code")
+                .about("Lists the code blocks in the last answer of a chat, or prints, copies or saves one")
+                .arg(Arg::new("number").help("Code block to use, counting from 1").takes_value(true))
+                .arg(
+                    Arg::new("session")
+                        .long("session")
+                        .value_name("ID")
+                        .help("Session to read instead of the most recent one")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("copy")
+                        .long("copy")
+                        .short('c')
+                        .help("Copies the block to the clipboard"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Writes the block to a file")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sessions")
+                .about("Lists, shows, exports and deletes saved chat sessions")
+                .subcommand(SubCommand::with_name("list").about("Lists saved sessions, most recent first"))
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Prints the conversation of a session")
+                        .arg(Arg::new("id").help("Session id, or `last` (the default)").takes_value(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("delete")
+                        .about("Deletes a saved session")
+                        .arg(Arg::new("id").help("Session id, or `last`").required(true).takes_value(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Exports a session as Markdown, JSON or self-contained HTML")
+                        .arg(Arg::new("id").help("Session id, or `last` (the default)").takes_value(true))
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .short('f')
+                                .value_name("FORMAT")
+                                .help("md, json or html (default: from the output file's extension, else md)")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .long("output")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("Writes the export to a file instead of stdout")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("usage")
+                .about("Shows token usage and cost per day and per chat session")
+                .arg(
+                    Arg::new("days")
+                        .long("days")
+                        .value_name("DAYS")
+                        .help("Number of days to list, ending today (default 7)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("sessions")
+                        .long("sessions")
+                        .value_name("COUNT")
+                        .help("Number of recent sessions to list (default 10)")
                         .takes_value(true),
                 ),
         )
-        .subcommand(SubCommand::with_name("chat").about("Chat with an AI"))
         .subcommand(SubCommand::with_name("config")
             .about("Configure your cli environment"))
             .aliases(&["configure", "config"])
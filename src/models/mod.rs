@@ -1,3 +1,5 @@
-// mod model_registry;
+mod model;
+mod model_registry;
 
-// pub use model_registry::ModelRegistry;
\ No newline at end of file
+pub use model::ModelFactory;
+pub use model_registry::{global_registry, ModelRegistry};
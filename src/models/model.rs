@@ -0,0 +1,4 @@
+use crate::ai::chat_model::ChatModel;
+
+/// Builds a chat model from the provider specific `config` of a `ModelConfig`.
+pub type ModelFactory = Box<dyn Fn(serde_json::Value) -> Box<dyn ChatModel> + Send + Sync>;
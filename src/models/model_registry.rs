@@ -1,30 +1,121 @@
-// use std::collections::HashMap;
-
-// pub struct ModelRegistry {
-//     models: HashMap<String, Model>,
-// }
-
-// impl ModelRegistry {
-//     fn new() -> ModelRegistry {
-//         ModelRegistry {
-//             models: HashMap::new(),
-//         }
-//     }
-//     fn register_model(&mut self, id: &str, model: Model) {
-//         let model = Model::new(id, config);
-//         self.models.insert(id.to_string(), model);
-//     }
-
-//     fn get_model(&self, id: &str) -> Option<&Model> {
-//         self.models.get(id)
-//     }
-
-//     // Methods to activate or switch modes
-//     fn activate_chat_mode(&self, id: &str) {
-//         if let Some(model) = self.get_model(id) {
-//             model.chat();
-//         }
-//     }
-
-//     // Similar methods for completion and generate image modes
-// }
\ No newline at end of file
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::{OnceLock, RwLock};
+
+use crate::ai::chat_model::ChatModel;
+use crate::anthropic::ClaudeClient;
+use crate::config::user::settings::ModelConfig;
+use crate::open_ai_compatible::OpenAiCompatibleClient;
+use crate::open_ai_gpt::GptClient;
+use crate::replay::ReplayModel;
+use super::model::ModelFactory;
+
+/// Maps the provider `name` of a `ModelConfig` to the factory that builds it.
+pub struct ModelRegistry {
+    factories: BTreeMap<String, ModelFactory>,
+}
+
+impl ModelRegistry {
+    pub fn new() -> ModelRegistry {
+        ModelRegistry {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// A registry holding the providers that ship with k-aiti.
+    pub fn with_defaults() -> ModelRegistry {
+        let mut registry = ModelRegistry::new();
+        registry.register_model::<GptClient>("ChatGPT");
+        registry.register_model::<ClaudeClient>("Claude");
+        registry.register_model::<OpenAiCompatibleClient>("OpenAICompatible");
+        registry.register_model::<ReplayModel>("Replay");
+        registry
+    }
+
+    /// Registers a provider built through its `ChatModel::new` constructor.
+    pub fn register_model<T: ChatModel + 'static>(&mut self, name: &str) {
+        self.register_factory(name, |config| Box::new(T::new(config)) as Box<dyn ChatModel>);
+    }
+
+    /// Registers a provider built by an arbitrary factory, replacing any
+    /// provider previously registered under the same name.
+    pub fn register_factory<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(serde_json::Value) -> Box<dyn ChatModel> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    pub fn provider_names(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+
+    pub fn create_model(&self, c_model: &ModelConfig) -> Result<Box<dyn ChatModel>, Box<dyn Error>> {
+        match self.factories.get(&c_model.name) {
+            Some(factory) => Ok(factory(c_model.config.clone())),
+            None => Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unsupported model name: {}", c_model.name),
+            ))),
+        }
+    }
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        ModelRegistry::with_defaults()
+    }
+}
+
+/// The process wide registry used by the chat and config commands. Crates
+/// embedding k-aiti register their own providers here before running a command.
+pub fn global_registry() -> &'static RwLock<ModelRegistry> {
+    static REGISTRY: OnceLock<RwLock<ModelRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(ModelRegistry::with_defaults()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::ai::chat_model::ChatModelRequest;
+    use crate::ai::chat_types::ChatCompletionStream;
+
+    struct EchoModel;
+
+    #[async_trait]
+    impl ChatModel for EchoModel {
+        fn new(_: serde_json::Value) -> EchoModel {
+            EchoModel
+        }
+
+        async fn create_response_stream(&mut self, _: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+            Ok(Box::pin(futures::stream::iter(std::iter::empty())))
+        }
+    }
+
+    fn model_config(name: &str) -> ModelConfig {
+        ModelConfig {
+            id: String::from("test"),
+            name: name.to_string(),
+            config: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn test_resolves_registered_providers() {
+        let mut registry = ModelRegistry::with_defaults();
+        registry.register_model::<EchoModel>("Echo");
+
+        assert_eq!(registry.provider_names(), vec!["ChatGPT", "Claude", "Echo", "OpenAICompatible", "Replay"]);
+        assert!(registry.create_model(&model_config("Echo")).is_ok());
+        assert!(registry.create_model(&model_config("Claude")).is_ok());
+
+        let error = registry.create_model(&model_config("Missing")).err().expect("unregistered provider");
+        assert_eq!(error.to_string(), "Unsupported model name: Missing");
+    }
+}
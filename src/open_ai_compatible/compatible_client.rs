@@ -0,0 +1,237 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use crate::ai::chat_error::ChatModelError;
+use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::chat_types::{
+    ChatCompletionChoice, ChatCompletionChunk, ChatCompletionDelta, ChatCompletionRequestMessage, ChatCompletionStream, ModelUsage, Role,
+};
+use crate::ai::config_values;
+use crate::ai::retry::{send_with_retry, RetryListener, RetryPolicy};
+
+const DEFAULT_BASE_URL: &str = "http://localhost:8080/v1";
+
+/// Chat model for any server speaking the OpenAI chat completions protocol,
+/// such as llama.cpp, vLLM or Ollama.
+#[derive(Clone)]
+pub struct OpenAiCompatibleClient {
+    http: reqwest::Client,
+    config: CompatibleConfig,
+}
+
+#[derive(Clone)]
+pub struct CompatibleConfig {
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    model: String,
+    base_url: String,
+    api_key_env: Option<String>,
+    headers: Vec<(String, String)>,
+    include_usage: bool,
+    retry: RetryPolicy,
+}
+
+#[derive(Serialize)]
+struct CompletionRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatCompletionRequestMessage],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+// Local servers are loose about which chunk fields they send, so everything
+// beyond the delta itself is optional.
+#[derive(Deserialize)]
+struct StreamChunk {
+    id: Option<String>,
+    object: Option<String>,
+    created: Option<u32>,
+    model: Option<String>,
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    usage: Option<ModelUsage>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    #[serde(default)]
+    index: u32,
+    #[serde(default)]
+    delta: StreamDelta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct StreamDelta {
+    content: Option<String>,
+    role: Option<Role>,
+}
+
+type SendError = Box<dyn Error + Send + Sync>;
+
+impl CompatibleConfig {
+    fn from_value(config: &serde_json::Value) -> CompatibleConfig {
+        let headers = match config.get("headers") {
+            Some(serde_json::Value::Object(map)) => map.iter()
+                .filter_map(|(name, value)| value.as_str().map(|value| (name.clone(), value.to_string())))
+                .collect(),
+            _ => Vec::new(),
+        };
+        CompatibleConfig {
+            max_tokens: config_values::get_u64(config, "max_tokens").map(|value| value as u32),
+            temperature: config_values::get_f64(config, "temperature").map(|value| value as f32),
+            model: config_values::get_string(config, "model").unwrap_or_default(),
+            base_url: config_values::get_string(config, "base_url").unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            api_key_env: config_values::get_string(config, "api_key_env").filter(|name| !name.is_empty()),
+            headers,
+            // Some older servers reject stream_options, so it can be switched off
+            include_usage: config_values::get_string(config, "include_usage").as_deref() != Some("false"),
+            retry: RetryPolicy::from_config(config),
+        }
+    }
+
+    fn header_map(&self) -> Result<HeaderMap, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+        }
+        Ok(headers)
+    }
+}
+
+fn convert_chunk(data: &str, fallback_model: &str) -> Result<Option<ChatCompletionChunk>, SendError> {
+    // The stream is terminated by a literal [DONE] sentinel rather than JSON
+    if data.trim() == "[DONE]" {
+        return Ok(None);
+    }
+    let chunk: StreamChunk = serde_json::from_str(data)?;
+    Ok(Some(ChatCompletionChunk {
+        id: chunk.id,
+        object: chunk.object.unwrap_or_else(|| String::from("chat.completion.chunk")),
+        created: chunk.created.unwrap_or(0),
+        model: chunk.model.unwrap_or_else(|| fallback_model.to_string()),
+        choices: chunk.choices.into_iter().map(|choice| ChatCompletionChoice {
+            index: choice.index,
+            delta: ChatCompletionDelta {
+                content: choice.delta.content,
+                role: choice.delta.role.or(Some(Role::Assistant)),
+            },
+            finish_reason: choice.finish_reason,
+        }).collect(),
+        usage: chunk.usage,
+    }))
+}
+
+#[async_trait]
+impl ChatModel for OpenAiCompatibleClient {
+    fn new(config: serde_json::Value) -> OpenAiCompatibleClient {
+        OpenAiCompatibleClient {
+            http: reqwest::Client::new(),
+            config: CompatibleConfig::from_value(&config),
+        }
+    }
+
+    fn set_retry_listener(&mut self, listener: RetryListener) {
+        self.config.retry.on_retry = Some(listener);
+    }
+
+    async fn create_response_stream(&mut self, client_request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+        let request = CompletionRequest {
+            model: &self.config.model,
+            messages: &client_request.messages,
+            max_tokens: self.config.max_tokens,
+            temperature: self.config.temperature,
+            stream: true,
+            stream_options: self.config.include_usage.then_some(StreamOptions { include_usage: true }),
+        };
+
+        let api_key = match &self.config.api_key_env {
+            Some(api_key_env) => Some(std::env::var(api_key_env)
+                .map_err(|_| ChatModelError::Auth(format!("environment variable {} is not set", api_key_env)))?),
+            None => None,
+        };
+        let headers = self.config.header_map()?;
+        let url = format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'));
+        let response = send_with_retry(&self.config.retry, || {
+            let builder = self.http.post(&url).headers(headers.clone()).json(&request);
+            match &api_key {
+                Some(api_key) => builder.bearer_auth(api_key),
+                None => builder,
+            }
+        }).await?;
+
+        let model = self.config.model.clone();
+        let stream = response
+            .bytes_stream()
+            .eventsource()
+            .filter_map(move |event| {
+                let result = match event {
+                    Ok(event) => convert_chunk(&event.data, &model).transpose(),
+                    Err(e) => Some(Err(Box::new(ChatModelError::Network(e.to_string())) as SendError)),
+                };
+                futures::future::ready(result)
+            })
+            .map(|result| result.map_err(|e| e as Box<dyn Error>))
+            .boxed();
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::test_server;
+
+    const EVENTS: &str = "data: {\"id\":\"cmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1700000000,\"model\":\"llama\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\"},\"finish_reason\":null}]}\n\n\
+data: {\"id\":\"cmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":null}]}\n\n\
+data: {\"id\":\"cmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"!\"},\"finish_reason\":\"stop\"}]}\n\n\
+data: [DONE]\n\n";
+
+    #[tokio::test]
+    async fn test_stream_against_compatible_server() {
+        let server = test_server::serve(200, "text/event-stream", EVENTS).await;
+        std::env::set_var("KAITI_TEST_COMPATIBLE_KEY", "local-key");
+        let mut client = OpenAiCompatibleClient::new(serde_json::json!({
+            "base_url": format!("{}/v1", server.url),
+            "api_key_env": "KAITI_TEST_COMPATIBLE_KEY",
+            "model": "llama",
+            "headers": { "X-Team": "platform" },
+        }));
+        let request = ChatModelRequest {
+            messages: vec![ChatCompletionRequestMessage { role: Role::User, content: "Hello".to_string(), name: None }],
+        };
+
+        let stream = client.create_response_stream(&request).await.expect("stream");
+        let chunks = stream.map(|chunk| chunk.expect("chunk")).collect::<Vec<_>>().await;
+
+        let text = chunks.iter()
+            .flat_map(|chunk| chunk.choices.iter())
+            .filter_map(|choice| choice.delta.content.clone())
+            .collect::<String>();
+        assert_eq!(text, "Hi!");
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].model, "llama");
+
+        let received = server.request().await.to_lowercase();
+        assert!(received.starts_with("post /v1/chat/completions "));
+        assert!(received.contains("authorization: bearer local-key"));
+        assert!(received.contains("x-team: platform"));
+        assert!(received.contains("\"stream_options\":{\"include_usage\":true}"));
+    }
+}
@@ -0,0 +1,3 @@
+mod compatible_client;
+
+pub use compatible_client::OpenAiCompatibleClient;
@@ -2,19 +2,25 @@ use std::error::Error;
 
 use async_openai::types::{
     CreateChatCompletionRequestArgs,
+    CreateChatCompletionStreamResponse,
     Role,
 };
-use async_openai::Client;
 use async_trait::async_trait;
-use futures::{TryStreamExt, StreamExt};
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
 
+use crate::ai::chat_error::ChatModelError;
 use crate::ai::chat_types::{ChatCompletionStream, ChatCompletionDelta, ChatCompletionChoice, ChatCompletionChunk, ModelUsage};
 use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::config_values;
+use crate::ai::retry::{send_with_retry, RetryListener, RetryPolicy};
 
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_API_KEY_ENV: &str = "OPENAI_API_KEY";
 
 #[derive(Clone)]
 pub struct GptClient {
-    client: Client,
+    http: reqwest::Client,
     config: GptConfig,
 }
 
@@ -25,6 +31,25 @@ pub struct GptConfig {
     n: u8,
     temperature: f32,
     model: String,
+    base_url: String,
+    api_key_env: String,
+    retry: RetryPolicy,
+}
+
+// convert messages to that expected by async_ openai
+fn to_openai_messages(messages: &[crate::ai::chat_types::ChatCompletionRequestMessage]) -> Vec<async_openai::types::ChatCompletionRequestMessage> {
+    messages.iter()
+        .map(|msg| {
+            async_openai::types::ChatCompletionRequestMessage {
+                content: msg.content.clone(),
+                name: msg.name.clone(),
+                role: match msg.role {
+                    crate::ai::chat_types::Role::User      => Role::User,
+                    crate::ai::chat_types::Role::Assistant => Role::Assistant,
+                    crate::ai::chat_types::Role::System    => Role::System,
+                }
+            }
+    }).collect::<Vec<async_openai::types::ChatCompletionRequestMessage>>()
 }
 
 #[async_trait]
@@ -83,32 +108,26 @@ impl ChatModel for GptClient  {
             Some(value) => value.as_str().unwrap_or("gpt-3.5-turbo").to_string(),
             None => String::from("gpt-3.5-turbo"),
         };
-        let client = Client::new();
         GptClient {
-            client,
+            http: reqwest::Client::new(),
             config: GptConfig {
                 max_tokens,
                 n,
                 temperature,
                 model,
+                base_url: config_values::get_string(&config, "base_url").unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+                api_key_env: config_values::get_string(&config, "api_key_env").unwrap_or_else(|| DEFAULT_API_KEY_ENV.to_string()),
+                retry: RetryPolicy::from_config(&config),
             }
         }
     }
 
+    fn set_retry_listener(&mut self, listener: RetryListener) {
+        self.config.retry.on_retry = Some(listener);
+    }
+
     async fn create_response_stream(&mut self, client_request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
-        // convert messages to that expected by async_ openai
-        let messages = client_request.messages.iter()
-            .map(|msg| {
-                async_openai::types::ChatCompletionRequestMessage {
-                    content: msg.content.clone(),
-                    name: msg.name.clone(),
-                    role: match msg.role {
-                        crate::ai::chat_types::Role::User      => Role::User,
-                        crate::ai::chat_types::Role::Assistant => Role::Assistant,
-                        crate::ai::chat_types::Role::System    => Role::Assistant,
-                    }
-                }
-        }).collect::<Vec<async_openai::types::ChatCompletionRequestMessage>>();
+        let messages = to_openai_messages(&client_request.messages);
 
         // Update the generate_response method in the GptClient implementation
         let request = CreateChatCompletionRequestArgs::default()
@@ -117,47 +136,112 @@ impl ChatModel for GptClient  {
             .max_tokens(self.config.max_tokens)
             .temperature(self.config.temperature)
             .messages(messages)
+            .stream(true)
             .build()?;
+        // Without stream_options OpenAI leaves usage out of streamed responses
+        let mut request = serde_json::to_value(request)?;
+        request["stream_options"] = serde_json::json!({ "include_usage": true });
 
-        let response = self.client.chat()
-            .create_stream(request)
-            .await
-            .expect("Failed to create stream");
+        let api_key = std::env::var(&self.config.api_key_env)
+            .map_err(|_| ChatModelError::Auth(format!("environment variable {} is not set", self.config.api_key_env)))?;
+        let url = format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'));
+        let response = send_with_retry(&self.config.retry, || {
+            self.http.post(&url).bearer_auth(&api_key).json(&request)
+        }).await?;
 
         // Transform the stream
         let stream = response
-            .map_err(|e| Box::new(e) as Box<dyn Error> )
-            .map(|result| {
-                result.and_then(|chat_completion_response| {
-                    // Process the chat_completion_response to transform it into the new type
-                    // Assuming you want to convert CreateChatCompletionStreamResponse to ChatCompletionChunck
-                    let choices = chat_completion_response.choices.into_iter().map(|choice_delta| {
-                        ChatCompletionChoice {
-                            index: choice_delta.index,
-                            delta: ChatCompletionDelta {
-                                content: choice_delta.delta.content,
-                                role: Some(crate::ai::chat_types::Role::Assistant),
-                            },
-                            finish_reason: choice_delta.finish_reason,
+            .bytes_stream()
+            .eventsource()
+            // The stream is terminated by a literal [DONE] sentinel rather than JSON
+            .take_while(|event| futures::future::ready(!matches!(event, Ok(event) if event.data.trim() == "[DONE]")))
+            .map(|event| {
+                event
+                    .map_err(|e| Box::new(ChatModelError::Network(e.to_string())) as Box<dyn Error>)
+                    .and_then(|event| {
+                        serde_json::from_str::<CreateChatCompletionStreamResponse>(&event.data)
+                            .map_err(|e| Box::new(e) as Box<dyn Error>)
+                    })
+                    .map(|chat_completion_response| {
+                        // Process the chat_completion_response to transform it into the new type
+                        // Assuming you want to convert CreateChatCompletionStreamResponse to ChatCompletionChunck
+                        let choices = chat_completion_response.choices.into_iter().map(|choice_delta| {
+                            ChatCompletionChoice {
+                                index: choice_delta.index,
+                                delta: ChatCompletionDelta {
+                                    content: choice_delta.delta.content,
+                                    role: Some(crate::ai::chat_types::Role::Assistant),
+                                },
+                                finish_reason: choice_delta.finish_reason,
+                            }
+                        }).collect();
+
+                        ChatCompletionChunk {
+                            // Only the final chunk, which has no choices, carries usage
+                            usage: chat_completion_response.usage.map(|usage| ModelUsage {
+                                completion_tokens: usage.completion_tokens,
+                                prompt_tokens:     usage.prompt_tokens,
+                                total_tokens:      usage.total_tokens,
+                            }),
+                            id: chat_completion_response.id,
+                            object: chat_completion_response.object,
+                            created: chat_completion_response.created,
+                            model: chat_completion_response.model,
+                            choices,
                         }
-                    }).collect();
-
-                    Ok(ChatCompletionChunk {
-                        usage: Some(ModelUsage {
-                            completion_tokens: 0, // todo
-                            prompt_tokens:     0, // todo
-                            total_tokens:      0, // todo
-                        }),
-                        id: chat_completion_response.id,
-                        object: chat_completion_response.object,
-                        created: chat_completion_response.created,
-                        model: chat_completion_response.model,
-                        choices,
                     })
-                })
             })
             .boxed();
         // Box the stream and pin it
         Ok(Box::pin(stream))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::{ChatCompletionRequestMessage, Role as ChatRole};
+    use crate::ai::test_server::{self, TestResponse};
+
+    #[test]
+    fn test_roles_map_to_openai_roles() {
+        let messages = [ChatRole::System, ChatRole::User, ChatRole::Assistant]
+            .into_iter()
+            .map(|role| ChatCompletionRequestMessage { role, content: String::from("text"), name: None })
+            .collect::<Vec<_>>();
+
+        let roles = to_openai_messages(&messages).into_iter().map(|msg| msg.role).collect::<Vec<_>>();
+        assert_eq!(roles, vec![Role::System, Role::User, Role::Assistant]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_retries_after_rate_limit() {
+        let events = "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-test\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hello\"},\"finish_reason\":null}]}\n\n\
+data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-test\",\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":1,\"total_tokens\":10}}\n\n\
+data: [DONE]\n\n";
+        let server = test_server::serve_responses(vec![
+            TestResponse::new(429, "application/json", "{\"error\":{\"message\":\"Rate limit reached\",\"type\":\"requests\"}}")
+                .header("retry-after-ms", "1"),
+            TestResponse::new(200, "text/event-stream", events),
+        ]).await;
+        std::env::set_var("KAITI_TEST_OPENAI_KEY", "sk-test");
+        let mut client = GptClient::new(serde_json::json!({
+            "base_url": server.url,
+            "api_key_env": "KAITI_TEST_OPENAI_KEY",
+            "model": "gpt-test",
+        }));
+        let request = ChatModelRequest {
+            messages: vec![ChatCompletionRequestMessage { role: ChatRole::User, content: String::from("Hi"), name: None }],
+        };
+
+        let stream = client.create_response_stream(&request).await.expect("stream after retry");
+        let chunks = stream.map(|chunk| chunk.expect("chunk")).collect::<Vec<_>>().await;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].choices[0].delta.content.as_deref(), Some("Hello"));
+        assert_eq!(chunks[0].usage, None);
+        assert_eq!(chunks[1].usage, Some(ModelUsage { prompt_tokens: 9, completion_tokens: 1, total_tokens: 10 }));
+        let received = server.request().await;
+        assert!(received.contains("\"stream\":true"));
+        assert!(received.contains("\"include_usage\":true"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::chat_model::ChatModelRequest;
+use crate::ai::chat_types::ChatCompletionChunk;
+
+/// One line of a fixture file. A recorded response is the run of entries for
+/// the same request starting at `sequence` 0.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FixtureEntry {
+    pub request: String,
+    pub sequence: u32,
+    pub chunk: ChatCompletionChunk,
+}
+
+/// Stable key for a request: FNV-1a over the serialized messages, so fixtures
+/// keep matching across runs and compiler versions.
+pub fn request_hash(request: &ChatModelRequest) -> String {
+    let serialized = serde_json::to_string(&request.messages).unwrap_or_default();
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in serialized.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Expands a leading `~/` so fixture paths in settings.json can be written relative to home.
+pub fn resolve_path(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn read_entries(path: &Path) -> Result<Vec<FixtureEntry>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read fixture {}: {}", path.display(), e))?;
+    let mut entries = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str::<FixtureEntry>(line)
+            .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Returns the chunks of the most recent recording for `request`.
+pub fn find_response(entries: &[FixtureEntry], request: &str) -> Option<Vec<ChatCompletionChunk>> {
+    let start = entries.iter().rposition(|entry| entry.request == request && entry.sequence == 0)?;
+    let chunks = entries[start..].iter()
+        .filter(|entry| entry.request == request)
+        .enumerate()
+        .take_while(|(index, entry)| entry.sequence as usize == *index)
+        .map(|(_, entry)| entry.chunk.clone())
+        .collect();
+    Some(chunks)
+}
+
+pub struct FixtureWriter {
+    file: fs::File,
+}
+
+impl FixtureWriter {
+    pub fn open(path: &Path) -> Result<FixtureWriter, Box<dyn Error>> {
+        if let Some(parent_dir) = path.parent() {
+            fs::create_dir_all(parent_dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open fixture {}: {}", path.display(), e))?;
+        Ok(FixtureWriter { file })
+    }
+
+    pub fn append(&mut self, entry: &FixtureEntry) -> std::io::Result<()> {
+        let line = serde_json::to_string(entry)?;
+        writeln!(self.file, "{}", line)
+    }
+}
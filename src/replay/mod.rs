@@ -0,0 +1,6 @@
+pub mod fixture;
+mod recording_model;
+mod replay_model;
+
+pub use recording_model::RecordingModel;
+pub use replay_model::ReplayModel;
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+
+use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::chat_types::ChatCompletionStream;
+use crate::ai::config_values;
+use crate::ai::retry::RetryListener;
+use crate::config::user::settings::ModelConfig;
+use crate::models::global_registry;
+use super::fixture::{self, FixtureEntry, FixtureWriter};
+
+/// Wraps another chat model and appends every chunk it streams to a fixture
+/// file that `ReplayModel` can play back.
+pub struct RecordingModel {
+    /// The model being recorded, or why it could not be created from the config.
+    inner: Result<Box<dyn ChatModel>, String>,
+    fixture: PathBuf,
+}
+
+impl RecordingModel {
+    pub fn wrap(inner: Box<dyn ChatModel>, fixture: PathBuf) -> RecordingModel {
+        RecordingModel { inner: Ok(inner), fixture }
+    }
+}
+
+/// Creates the model named by a recording config's `model` entry.
+fn recorded_model(config: &serde_json::Value) -> Result<Box<dyn ChatModel>, String> {
+    let model = config.get("model")
+        .cloned()
+        .ok_or("recording config requires a model entry")?;
+    let model = serde_json::from_value::<ModelConfig>(model)
+        .map_err(|e| format!("recording config has an invalid model entry: {}", e))?;
+    let registry = global_registry().read().map_err(|_| "model registry is poisoned")?;
+    registry.create_model(&model).map_err(|e| e.to_string())
+}
+
+#[async_trait]
+impl ChatModel for RecordingModel {
+    /// Expects `fixture` and a `model` entry shaped like a `ModelConfig`,
+    /// which is resolved through the global model registry. A config that
+    /// does not resolve fails each request rather than panicking here.
+    fn new(config: serde_json::Value) -> RecordingModel {
+        let fixture = config_values::get_string(&config, "fixture").unwrap_or_default();
+        RecordingModel {
+            inner: recorded_model(&config),
+            fixture: fixture::resolve_path(&fixture),
+        }
+    }
+
+    fn set_retry_listener(&mut self, listener: RetryListener) {
+        if let Ok(inner) = &mut self.inner {
+            inner.set_retry_listener(listener);
+        }
+    }
+
+    async fn create_response_stream(&mut self, client_request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+        let inner = self.inner.as_mut().map_err(|e| e.clone())?;
+        let mut writer = FixtureWriter::open(&self.fixture)?;
+        let request = fixture::request_hash(client_request);
+        let stream = inner.create_response_stream(client_request).await?;
+
+        let mut sequence = 0;
+        let stream = stream
+            .map(move |result| {
+                let chunk = result?;
+                writer.append(&FixtureEntry {
+                    request: request.clone(),
+                    sequence,
+                    chunk: chunk.clone(),
+                })?;
+                sequence += 1;
+                Ok(chunk)
+            })
+            .boxed();
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::chat_types::{
+        ChatCompletionChoice, ChatCompletionChunk, ChatCompletionDelta, ChatCompletionRequestMessage, Role,
+    };
+    use crate::replay::ReplayModel;
+
+    struct ScriptedModel {
+        words: Vec<&'static str>,
+    }
+
+    #[async_trait]
+    impl ChatModel for ScriptedModel {
+        fn new(_: serde_json::Value) -> ScriptedModel {
+            ScriptedModel { words: vec!["recorded", " answer"] }
+        }
+
+        async fn create_response_stream(&mut self, _: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+            let chunks = self.words.iter().map(|word| ChatCompletionChunk {
+                id: Some(String::from("scripted")),
+                object: String::from("chat.completion.chunk"),
+                created: 0,
+                model: String::from("scripted"),
+                choices: vec![ChatCompletionChoice {
+                    index: 0,
+                    delta: ChatCompletionDelta { content: Some(word.to_string()), role: Some(Role::Assistant) },
+                    finish_reason: None,
+                }],
+                usage: None,
+            }).collect::<Vec<_>>();
+            Ok(Box::pin(futures::stream::iter(chunks).map(Ok).boxed()))
+        }
+    }
+
+    async fn collect_text(model: &mut dyn ChatModel, request: &ChatModelRequest) -> String {
+        let stream = model.create_response_stream(request).await.expect("stream");
+        stream
+            .map(|chunk| chunk.expect("chunk").choices[0].delta.content.clone().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .await
+            .concat()
+    }
+
+    #[tokio::test]
+    async fn test_recorded_stream_replays_identically() {
+        let directory = tempfile::tempdir().expect("temp dir");
+        let path = directory.path().join("fixture.jsonl");
+        let request = ChatModelRequest {
+            messages: vec![ChatCompletionRequestMessage { role: Role::User, content: "Hi".to_string(), name: None }],
+        };
+
+        let mut recorder = RecordingModel::wrap(Box::new(ScriptedModel::new(serde_json::json!({}))), path.clone());
+        assert_eq!(collect_text(&mut recorder, &request).await, "recorded answer");
+
+        // Record a second take; replay must use the latest one only
+        let mut second = ScriptedModel::new(serde_json::json!({}));
+        second.words = vec!["second take"];
+        let mut recorder = RecordingModel::wrap(Box::new(second), path.clone());
+        collect_text(&mut recorder, &request).await;
+
+        let mut replay = ReplayModel::new(serde_json::json!({ "fixture": path.to_string_lossy() }));
+        assert_eq!(collect_text(&mut replay, &request).await, "second take");
+
+        let other = ChatModelRequest {
+            messages: vec![ChatCompletionRequestMessage { role: Role::User, content: "Bye".to_string(), name: None }],
+        };
+        assert!(replay.create_response_stream(&other).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bad_config_fails_the_request() {
+        let request = ChatModelRequest { messages: Vec::new() };
+        let mut recorder = RecordingModel::new(serde_json::json!({ "fixture": "unused.jsonl" }));
+        let error = recorder.create_response_stream(&request).await.err().unwrap();
+        assert_eq!(error.to_string(), "recording config requires a model entry");
+
+        let model = serde_json::json!({ "id": "x", "name": "no-such-model", "config": {} });
+        let mut recorder = RecordingModel::new(serde_json::json!({ "fixture": "unused.jsonl", "model": model }));
+        let error = recorder.create_response_stream(&request).await.err().unwrap();
+        assert_eq!(error.to_string(), "Unsupported model name: no-such-model");
+    }
+}
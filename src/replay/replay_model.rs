@@ -0,0 +1,37 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+
+use crate::ai::chat_model::{ChatModel, ChatModelRequest};
+use crate::ai::chat_types::ChatCompletionStream;
+use crate::ai::config_values;
+use super::fixture;
+
+/// Chat model that answers from a fixture file instead of a provider, for
+/// tests and offline demos.
+#[derive(Clone)]
+pub struct ReplayModel {
+    fixture: PathBuf,
+}
+
+#[async_trait]
+impl ChatModel for ReplayModel {
+    fn new(config: serde_json::Value) -> ReplayModel {
+        let fixture = config_values::get_string(&config, "fixture").unwrap_or_default();
+        ReplayModel {
+            fixture: fixture::resolve_path(&fixture),
+        }
+    }
+
+    async fn create_response_stream(&mut self, client_request: &ChatModelRequest) -> Result<ChatCompletionStream, Box<dyn Error>> {
+        let entries = fixture::read_entries(&self.fixture)?;
+        let request = fixture::request_hash(client_request);
+        let chunks = match fixture::find_response(&entries, &request) {
+            Some(chunks) => chunks,
+            None => return Err(format!("No recorded response for request {} in {}", request, self.fixture.display()).into()),
+        };
+        Ok(Box::pin(futures::stream::iter(chunks).map(Ok).boxed()))
+    }
+}
@@ -1,3 +1,7 @@
+use crate::config::user::settings::CaptureSettings;
+
+pub mod records;
+pub mod shell_init;
 pub mod traits;
 
 #[cfg(target_family = "unix")]
@@ -12,6 +16,19 @@ pub use unix::UnixTerminalCapture as TerminalCaptureInstance;
 #[cfg(target_family = "windows")]
 pub use windows::WindowsTerminalCapture as TerminalCaptureInstance;
 
-pub fn capture_instance() -> Box<dyn traits::TerminalCapture> {
-    Box::new(TerminalCaptureInstance)
+/// The capture for the terminal kaiti is running in: the tmux pane or screen
+/// window when inside one, otherwise the platform's own.
+pub fn capture_instance(settings: &CaptureSettings) -> Box<dyn traits::TerminalCapture> {
+    #[cfg(target_family = "unix")]
+    {
+        if std::env::var_os("TMUX").is_some() {
+            return Box::new(unix::TmuxCapture::new(settings.scrollback_lines));
+        }
+        if std::env::var_os("STY").is_some() {
+            return Box::new(unix::ScreenCapture::new(settings.scrollback_lines));
+        }
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = settings;
+    Box::<TerminalCaptureInstance>::default()
 }
\ No newline at end of file
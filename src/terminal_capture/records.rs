@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use chrono::TimeZone;
+
+/// The spool the shell hooks from `kaiti shell-init` append to, in `~/.k-aiti`.
+pub const SPOOL_FILE: &str = "commands.tsv";
+
+/// Only the end of the spool is read; it grows for as long as the hooks are installed.
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// A command run in an interactive shell, as recorded by the shell hooks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandRecord {
+    pub command: String,
+    pub exit_code: i32,
+    /// The directory the shell was in when the command finished.
+    pub cwd: String,
+    /// Unix timestamps, in seconds.
+    pub started_at: i64,
+    pub finished_at: i64,
+    /// The process id of the shell that ran it.
+    pub shell_pid: u32,
+}
+
+impl CommandRecord {
+    pub fn failed(&self) -> bool {
+        self.exit_code != 0
+    }
+
+    /// The command and what is known about how it ran, as shown to the model.
+    pub fn describe(&self) -> String {
+        let finished = chrono::Local.timestamp_opt(self.finished_at, 0).single()
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        format!("$ {}\nexited with code {} after {}s in {} at {}",
+            self.command, self.exit_code, (self.finished_at - self.started_at).max(0), self.cwd, finished)
+    }
+
+    /// Reads one spool line: start, end, exit code, shell pid, cwd and the
+    /// command, separated by tabs.
+    fn parse(line: &str) -> Option<CommandRecord> {
+        let mut fields = line.splitn(6, '\t');
+        Some(CommandRecord {
+            started_at: fields.next()?.parse().ok()?,
+            finished_at: fields.next()?.parse().ok()?,
+            exit_code: fields.next()?.parse().ok()?,
+            shell_pid: fields.next()?.parse().ok()?,
+            cwd: unescape(fields.next()?),
+            command: unescape(fields.next()?),
+        })
+    }
+}
+
+/// Undoes the escaping the hooks apply so each record stays on one line.
+fn unescape(field: &str) -> String {
+    let mut text = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+pub struct CommandSpool {
+    path: PathBuf,
+}
+
+impl CommandSpool {
+    pub fn new(path: PathBuf) -> CommandSpool {
+        CommandSpool { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The most recently recorded commands, oldest first. Those run by
+    /// `shell_pid` are preferred when it has recorded any, so a failure in
+    /// another terminal is not picked up by mistake.
+    pub fn read_recent(&self, shell_pid: Option<u32>) -> io::Result<Vec<CommandRecord>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let start = file.metadata()?.len().saturating_sub(TAIL_BYTES);
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let text = String::from_utf8_lossy(&bytes);
+        // A read that starts mid-file begins part way through a line
+        let skip = if start > 0 { 1 } else { 0 };
+        let records = text.lines().skip(skip).filter_map(CommandRecord::parse).collect::<Vec<_>>();
+
+        let own = records.iter().filter(|record| Some(record.shell_pid) == shell_pid).cloned().collect::<Vec<_>>();
+        Ok(if own.is_empty() { records } else { own })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_read_recent_prefers_the_calling_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = CommandSpool::new(dir.path().join(SPOOL_FILE));
+        assert!(spool.read_recent(None).unwrap().is_empty());
+
+        let mut file = File::create(spool.path()).unwrap();
+        writeln!(file, "100\t103\t101\t42\t/home/me/my\\tproject\tcargo build\\nexit").unwrap();
+        writeln!(file, "not a record").unwrap();
+        writeln!(file, "200\t200\t0\t7\t/tmp\tprintf 'a\\\\tb'\tdone").unwrap();
+
+        let records = spool.read_recent(Some(1)).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], CommandRecord {
+            command: String::from("cargo build\nexit"),
+            exit_code: 101,
+            cwd: String::from("/home/me/my\tproject"),
+            started_at: 100,
+            finished_at: 103,
+            shell_pid: 42,
+        });
+        assert_eq!(records[1].command, "printf 'a\\tb'\tdone");
+        assert!(records[0].failed() && !records[1].failed());
+        assert!(records[0].describe().starts_with("$ cargo build\nexit\nexited with code 101 after 3s in /home/me/my\tproject at "));
+
+        let records = spool.read_recent(Some(42)).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].shell_pid, 42);
+    }
+}
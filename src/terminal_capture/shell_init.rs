@@ -0,0 +1,173 @@
+use std::path::Path;
+use std::str::FromStr;
+
+/// A shell `kaiti shell-init` has hooks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            other => Err(format!("Unsupported shell '{}'; expected bash or zsh", other)),
+        }
+    }
+}
+
+/// Appends a record, escaping it so it stays on one tab-separated line. Shared
+/// by both shells; the quoted patterns and replacements are literal in each.
+const RECORD: &str = r#"__kaiti_record() {
+    local bs='\' tab=$'\t' nl=$'\n' cwd=$__kaiti_cwd command=$__kaiti_command
+    cwd=${cwd//"$bs"/"$bs$bs"}; cwd=${cwd//"$tab"/"${bs}t"}; cwd=${cwd//"$nl"/"${bs}n"}
+    command=${command//"$bs"/"$bs$bs"}; command=${command//"$tab"/"${bs}t"}; command=${command//"$nl"/"${bs}n"}
+    printf '%s\t%s\t%s\t%s\t%s\t%s\n' "$__kaiti_started" "${EPOCHSECONDS:-$(date +%s)}" "$1" "$$" "$cwd" "$command" \
+        >> "$__kaiti_spool" 2>/dev/null
+}
+"#;
+
+/// Bash has no preexec hook of its own. The hooks join bash-preexec's when it
+/// is loaded; otherwise the DEBUG trap stands in, chained after any trap
+/// already set. It fires for every simple command, so only the first one
+/// after a prompt is recorded.
+const BASH: &str = r#"__kaiti_preexec() {
+    __kaiti_command=$1
+    __kaiti_cwd=$PWD
+    __kaiti_started=${EPOCHSECONDS:-$(date +%s)}
+}
+
+__kaiti_debug() {
+    local status=$? command
+    [ -n "$__kaiti_ready" ] && [ -z "$COMP_LINE" ] || return $status
+    case "$BASH_COMMAND" in __kaiti_*) return $status ;; esac
+    __kaiti_ready=
+    command=$(HISTTIMEFORMAT= builtin history 1)
+    __kaiti_preexec "${command#*[0-9][* ] }"
+    return $status
+}
+
+__kaiti_precmd() {
+    local exit_code=$?
+    [ -n "$__kaiti_started" ] && __kaiti_record "$exit_code"
+    __kaiti_started=
+}
+
+__kaiti_prompt() {
+    __kaiti_ready=1
+}
+
+# Installed from the first prompt, as a function or a sourced file would not see
+# the DEBUG trap already set. That trap runs first so it keeps the command's $_ and $?
+__kaiti_install=$'__kaiti_previous_trap=$(trap -p DEBUG)\n__kaiti_trap'
+
+__kaiti_trap() {
+    PROMPT_COMMAND=${PROMPT_COMMAND//"$__kaiti_install;"/}
+    eval "set -- $__kaiti_previous_trap"
+    __kaiti_previous_trap=$3
+    trap 'eval -- "$__kaiti_previous_trap"; __kaiti_debug' DEBUG
+}
+
+if [ -z "$__kaiti_installed" ]; then
+    __kaiti_installed=1
+    if [ -n "${bash_preexec_imported:-}${__bp_imported:-}" ]; then
+        preexec_functions+=(__kaiti_preexec)
+        precmd_functions=(__kaiti_precmd "${precmd_functions[@]}")
+    else
+        PROMPT_COMMAND="$__kaiti_install;__kaiti_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND};__kaiti_prompt"
+    fi
+fi
+"#;
+
+const ZSH: &str = r#"zmodload zsh/datetime 2>/dev/null
+
+__kaiti_preexec() {
+    __kaiti_command=$1
+    __kaiti_cwd=$PWD
+    __kaiti_started=${EPOCHSECONDS:-$(date +%s)}
+}
+
+__kaiti_precmd() {
+    local exit_code=$?
+    [[ -n $__kaiti_started ]] && __kaiti_record "$exit_code"
+    __kaiti_started=
+}
+
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec __kaiti_preexec
+# First, so it sees the command's exit status
+precmd_functions=(__kaiti_precmd ${precmd_functions:#__kaiti_precmd})
+"#;
+
+/// The hooks to `eval` in the shell's startup file. They append each
+/// command's start and end time, exit status, shell pid, directory and
+/// command line to `spool`.
+pub fn script(shell: Shell, spool: &Path) -> String {
+    let spool = spool.to_string_lossy().replace('\'', "'\\''");
+    let hooks = match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+    };
+    format!("# kaiti shell integration; records commands for `kaiti debug`\n\
+             __kaiti_spool='{}'\n\
+             mkdir -p \"$(dirname \"$__kaiti_spool\")\"\n\n\
+             {}\n{}", spool, RECORD, hooks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use crate::terminal_capture::records::CommandSpool;
+
+    #[test]
+    fn test_script_quotes_the_spool_path() {
+        let script = script(Shell::Zsh, Path::new("/home/o'neil/.k-aiti/commands.tsv"));
+        assert!(script.contains("__kaiti_spool='/home/o'\\''neil/.k-aiti/commands.tsv'\n"));
+        assert!(script.contains("add-zsh-hook preexec __kaiti_preexec"));
+        assert_eq!("fish".parse::<Shell>(), Err(String::from("Unsupported shell 'fish'; expected bash or zsh")));
+    }
+
+    #[test]
+    fn test_bash_hooks_record_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = dir.path().join("spool").join("commands.tsv");
+        std::fs::write(dir.path().join("init.sh"), script(Shell::Bash, &spool)).unwrap();
+        // An interactive shell reading the commands from stdin runs the prompt hooks between them
+        let child = Command::new("bash")
+            .args(["--norc", "-i"])
+            .current_dir(dir.path())
+            .env("HISTFILE", dir.path().join("history"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        // Nothing to check where bash is not installed
+        let Ok(mut child) = child else { return };
+        child.stdin.take().unwrap()
+            .write_all(b"log=$PWD/debug.log; trap 'echo \"$BASH_COMMAND\" >> \"$log\"' DEBUG\n\
+                         source init.sh\ntest -e \"two  words\"\n\nprintf 'a\\\\tb' | cat; (exit 3)\n\
+                         mkdir sub && cd sub && false\n")
+            .unwrap();
+        child.wait().unwrap();
+
+        let records = CommandSpool::new(spool).read_recent(None).unwrap();
+        let commands = records.iter().map(|record| (record.command.as_str(), record.exit_code)).collect::<Vec<_>>();
+        assert_eq!(commands, [
+            ("test -e \"two  words\"", 1),
+            ("printf 'a\\\\tb' | cat; (exit 3)", 3),
+            ("mkdir sub && cd sub && false", 1),
+        ]);
+        // Where the command was started, not where it left the shell
+        assert_eq!(records[2].cwd, dir.path().to_string_lossy());
+        // The trap that was already set keeps running
+        let debug_log = std::fs::read_to_string(dir.path().join("debug.log")).unwrap();
+        assert!(debug_log.contains("mkdir sub"));
+    }
+}
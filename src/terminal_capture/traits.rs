@@ -1,3 +1,17 @@
+use super::records::CommandRecord;
+
 pub trait TerminalCapture {
     fn capture_output(&self) -> Result<String, std::io::Error>;
-}
\ No newline at end of file
+
+    /// The commands recently run in the terminal, oldest first, where the
+    /// platform records them.
+    fn recent_commands(&self) -> Result<Vec<CommandRecord>, std::io::Error> {
+        Ok(Vec::new())
+    }
+
+    /// Whether `capture_output` returns what is on screen, rather than a
+    /// description of the last failed command.
+    fn captures_screen(&self) -> bool {
+        false
+    }
+}
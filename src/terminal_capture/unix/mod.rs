@@ -1,2 +1,32 @@
+pub mod screen;
 pub mod terminal_capture;
-pub use terminal_capture::UnixTerminalCapture;
\ No newline at end of file
+pub mod tmux;
+pub use screen::ScreenCapture;
+pub use terminal_capture::UnixTerminalCapture;
+pub use tmux::TmuxCapture;
+
+/// The last `lines` lines of a window's contents, leaving out the blank rows
+/// under the prompt and the prompt that started kaiti.
+pub(crate) fn trim_scrollback(contents: &str, lines: usize) -> String {
+    let mut rows = contents.lines().map(str::trim_end).collect::<Vec<_>>();
+    while rows.last().is_some_and(|row| row.is_empty()) {
+        rows.pop();
+    }
+    if rows.last().is_some_and(|row| row.contains("kaiti")) {
+        rows.pop();
+    }
+    rows[rows.len().saturating_sub(lines)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_scrollback() {
+        let contents = "$ make\ncc -c main.c\nmain.c:3: error: expected ';'   \n$ kaiti debug\n\n\n";
+        assert_eq!(trim_scrollback(contents, 2), "cc -c main.c\nmain.c:3: error: expected ';'");
+        assert_eq!(trim_scrollback(contents, 10), "$ make\ncc -c main.c\nmain.c:3: error: expected ';'");
+        assert_eq!(trim_scrollback("\n\n", 5), "");
+    }
+}
@@ -0,0 +1,64 @@
+use std::io;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::terminal_capture::records::CommandRecord;
+use crate::terminal_capture::traits::TerminalCapture;
+use super::{trim_scrollback, UnixTerminalCapture};
+
+/// How long to wait for screen to write the hardcopy, which it does after
+/// `screen -X` has returned.
+const HARDCOPY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reads the scrollback of the GNU screen window kaiti is running in. How
+/// far back it goes is also limited by screen's own `defscrollback`.
+pub struct ScreenCapture {
+    lines: usize,
+    /// Commands recorded by the shell hooks, when they are installed.
+    hooks: UnixTerminalCapture,
+}
+
+impl ScreenCapture {
+    pub fn new(lines: usize) -> ScreenCapture {
+        ScreenCapture { lines, hooks: UnixTerminalCapture::default() }
+    }
+}
+
+impl TerminalCapture for ScreenCapture {
+    fn capture_output(&self) -> Result<String, io::Error> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hardcopy");
+        let mut command = Command::new("screen");
+        if let Ok(session) = std::env::var("STY") {
+            command.args(["-S", &session]);
+        }
+        if let Ok(window) = std::env::var("WINDOW") {
+            command.args(["-p", &window]);
+        }
+        let status = command.args(["-X", "hardcopy", "-h"]).arg(&path).status()?;
+        if !status.success() {
+            return Err(io::Error::other("screen hardcopy failed"));
+        }
+
+        let started = Instant::now();
+        loop {
+            match std::fs::read(&path) {
+                Ok(contents) if !contents.is_empty() => {
+                    return Ok(trim_scrollback(&String::from_utf8_lossy(&contents), self.lines));
+                }
+                _ if started.elapsed() > HARDCOPY_TIMEOUT => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "screen did not write the hardcopy"));
+                }
+                _ => std::thread::sleep(Duration::from_millis(20)),
+            }
+        }
+    }
+
+    fn recent_commands(&self) -> Result<Vec<CommandRecord>, io::Error> {
+        self.hooks.recent_commands()
+    }
+
+    fn captures_screen(&self) -> bool {
+        true
+    }
+}
@@ -1,10 +1,41 @@
+use std::io;
+use std::path::PathBuf;
+
+use crate::terminal_capture::records::{CommandRecord, CommandSpool, SPOOL_FILE};
 use crate::terminal_capture::traits::TerminalCapture;
 
-pub struct UnixTerminalCapture;
+/// Reads the commands recorded by the hooks from `kaiti shell-init`.
+pub struct UnixTerminalCapture {
+    spool: CommandSpool,
+}
+
+impl UnixTerminalCapture {
+    pub fn new(spool: PathBuf) -> UnixTerminalCapture {
+        UnixTerminalCapture { spool: CommandSpool::new(spool) }
+    }
+}
+
+impl Default for UnixTerminalCapture {
+    fn default() -> Self {
+        UnixTerminalCapture::new(dirs::home_dir().unwrap_or_default().join(".k-aiti").join(SPOOL_FILE))
+    }
+}
 
 impl TerminalCapture for UnixTerminalCapture {
-    fn capture_output(&self) -> Result<String, std::io::Error> {
-        // TODO: Read from history
-        Ok("hello".to_string())
+    /// The last failed command and what was recorded about it.
+    fn capture_output(&self) -> Result<String, io::Error> {
+        let records = self.recent_commands()?;
+        if records.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound,
+                "No commands have been recorded; add `eval \"$(kaiti shell-init bash)\"` to ~/.bashrc or `eval \"$(kaiti shell-init zsh)\"` to ~/.zshrc"));
+        }
+        records.iter().rev().find(|record| record.failed())
+            .map(CommandRecord::describe)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "None of the recently recorded commands failed"))
+    }
+
+    /// Commands from the shell kaiti was started from, when it has recorded any.
+    fn recent_commands(&self) -> Result<Vec<CommandRecord>, io::Error> {
+        self.spool.read_recent(Some(std::os::unix::process::parent_id()))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,44 @@
+use std::io;
+use std::process::Command;
+
+use crate::terminal_capture::records::CommandRecord;
+use crate::terminal_capture::traits::TerminalCapture;
+use super::{trim_scrollback, UnixTerminalCapture};
+
+/// Reads the scrollback of the tmux pane kaiti is running in.
+pub struct TmuxCapture {
+    lines: usize,
+    /// Commands recorded by the shell hooks, when they are installed.
+    hooks: UnixTerminalCapture,
+}
+
+impl TmuxCapture {
+    pub fn new(lines: usize) -> TmuxCapture {
+        TmuxCapture { lines, hooks: UnixTerminalCapture::default() }
+    }
+}
+
+impl TerminalCapture for TmuxCapture {
+    fn capture_output(&self) -> Result<String, io::Error> {
+        let mut command = Command::new("tmux");
+        // -J joins lines tmux wrapped to the pane's width
+        command.args(["capture-pane", "-p", "-J", "-S", &format!("-{}", self.lines)]);
+        if let Ok(pane) = std::env::var("TMUX_PANE") {
+            command.args(["-t", &pane]);
+        }
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(
+                format!("tmux capture-pane failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+        }
+        Ok(trim_scrollback(&String::from_utf8_lossy(&output.stdout), self.lines))
+    }
+
+    fn recent_commands(&self) -> Result<Vec<CommandRecord>, io::Error> {
+        self.hooks.recent_commands()
+    }
+
+    fn captures_screen(&self) -> bool {
+        true
+    }
+}
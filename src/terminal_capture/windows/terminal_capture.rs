@@ -1,6 +1,7 @@
 use std::process::Command;
 use crate::terminal_capture::traits::TerminalCapture;
 
+#[derive(Default)]
 pub struct WindowsTerminalCapture;
 
 impl TerminalCapture for WindowsTerminalCapture {
@@ -20,4 +21,8 @@ impl TerminalCapture for WindowsTerminalCapture {
             ))
         }
     }
+
+    fn captures_screen(&self) -> bool {
+        true
+    }
 }
\ No newline at end of file